@@ -0,0 +1,81 @@
+use vec::{Vec2, Vec3};
+
+/// The edge functions of a triangle with integer vertices, for rasterizing it
+/// incrementally.
+///
+/// Each edge function is a linear function `a x + b y + c` of the pixel
+/// position, which is zero along the edge opposite to one vertex and grows to
+/// twice the area of the triangle at that vertex. A pixel is inside the
+/// triangle when all three are non-negative, and dividing them by twice the
+/// area gives its barycentric coordinates.
+///
+/// Since the functions are linear, moving one pixel to the right or down just
+/// adds a constant to each of them. Rasterizers can evaluate them once per
+/// triangle and then step them along with integer additions, instead of
+/// calling `barycentric` for every pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edges {
+    /// The coefficients `(a, b, c)` of the edge function for each vertex
+    pub edges: [Vec3<isize>; 3],
+    /// Twice the area of the triangle, which is always positive
+    pub area: isize,
+}
+
+impl Edges {
+    /// Set up the edge functions of the triangle `t0`, `t1`, `t2`, with either
+    /// winding order. Returns `None` if the triangle has no area.
+    pub fn new(t0: Vec2<isize>, t1: Vec2<isize>, t2: Vec2<isize>) -> Option<Self> {
+        let area = (t1.0 - t0.0) * (t2.1 - t0.1) - (t2.0 - t0.0) * (t1.1 - t0.1);
+        if area == 0 {
+            return None;
+        }
+        // Flip the functions of clockwise triangles so they're positive inside
+        let sign = area.signum();
+        let edge = |a: Vec2<isize>, b: Vec2<isize>| {
+            Vec3((a.1 - b.1) * sign, (b.0 - a.0) * sign, (a.0 * b.1 - a.1 * b.0) * sign)
+        };
+        Some(Edges {
+            edges: [edge(t1, t2), edge(t2, t0), edge(t0, t1)],
+            area: area * sign,
+        })
+    }
+
+    /// The value of each edge function at `p`
+    pub fn at(&self, p: Vec2<isize>) -> Vec3<isize> {
+        let e = |e: Vec3<isize>| e.0 * p.0 + e.1 * p.1 + e.2;
+        Vec3(e(self.edges[0]), e(self.edges[1]), e(self.edges[2]))
+    }
+
+    /// How much each edge function changes when moving one pixel right
+    pub fn step_x(&self) -> Vec3<isize> {
+        Vec3(self.edges[0].0, self.edges[1].0, self.edges[2].0)
+    }
+
+    /// How much each edge function changes when moving one pixel down
+    pub fn step_y(&self) -> Vec3<isize> {
+        Vec3(self.edges[0].1, self.edges[1].1, self.edges[2].1)
+    }
+
+    /// The range of pixels `[start, end]` in the row `y` that are inside the
+    /// triangle, restricted to `[x0, x1]`, or `None` if there aren't any.
+    pub fn span(&self, y: isize, x0: isize, x1: isize) -> Option<(isize, isize)> {
+        let (mut start, mut end) = (x0, x1);
+        for e in &self.edges {
+            // Solve e.0 x + k >= 0 for x
+            let k = e.1 * y + e.2;
+            if e.0 > 0 {
+                start = start.max(-div_floor(k, e.0));
+            } else if e.0 < 0 {
+                end = end.min(div_floor(k, -e.0));
+            } else if k < 0 {
+                return None;
+            }
+        }
+        if start <= end { Some((start, end)) } else { None }
+    }
+}
+
+/// Integer division rounding towards negative infinity, for positive `d`
+fn div_floor(n: isize, d: isize) -> isize {
+    if n < 0 { -((d - 1 - n) / d) } else { n / d }
+}
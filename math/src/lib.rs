@@ -1,8 +1,12 @@
 pub mod vec;
 pub mod mat;
+pub mod quat;
+pub mod edge;
 
 pub use self::vec::{Vec2, Vec3, Vec4};
 pub use self::mat::{Mat2, Mat3, Mat4};
+pub use self::quat::Quat;
+pub use self::edge::Edges;
 
 pub fn barycentric((t0, t1, t2): (Vec2<isize>, Vec2<isize>, Vec2<isize>),
                point: Vec2<isize>)
@@ -125,7 +125,7 @@ impl Mat4<f32> {
 
     pub fn translate(offset: Vec3<f32>) -> Self {
         let mut m = Mat4::identity();
-        for i in 0..3 { m[(0, i)] = offset[i]; }
+        for i in 0..3 { m[(i, 3)] = offset[i]; }
         m
     }
 
@@ -134,6 +134,33 @@ impl Mat4<f32> {
         for i in 0..3 { m[(i, i)] = factor[i]; }
         m
     }
+
+    /// A counter-clockwise rotation by `angle` radians around the x axis
+    pub fn rotate_x(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Mat4::new([[1.0, 0.0, 0.0, 0.0],
+                   [0.0,   c,  -s, 0.0],
+                   [0.0,   s,   c, 0.0],
+                   [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// A counter-clockwise rotation by `angle` radians around the y axis
+    pub fn rotate_y(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Mat4::new([[  c, 0.0,   s, 0.0],
+                   [0.0, 1.0, 0.0, 0.0],
+                   [ -s, 0.0,   c, 0.0],
+                   [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    /// A counter-clockwise rotation by `angle` radians around the z axis
+    pub fn rotate_z(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Mat4::new([[  c,  -s, 0.0, 0.0],
+                   [  s,   c, 0.0, 0.0],
+                   [0.0, 0.0, 1.0, 0.0],
+                   [0.0, 0.0, 0.0, 1.0]])
+    }
 }
 
 
@@ -0,0 +1,117 @@
+use std::ops::{Mul, Neg};
+
+use super::{Vec3, Mat4};
+
+
+// Type Definitions ////////////////////////////////////////////////////////////
+
+/// A quaternion `w + xi + yj + zk`, stored as `Quat(x, y, z, w)`.
+///
+/// Unit quaternions represent rotations, and unlike matrices or Euler angles
+/// they can be smoothly interpolated with `slerp`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quat(pub f32, pub f32, pub f32, pub f32);
+
+
+// Constructors ////////////////////////////////////////////////////////////////
+
+impl Quat {
+    /// The quaternion that doesn't rotate at all
+    pub fn identity() -> Self { Quat(0.0, 0.0, 0.0, 1.0) }
+
+    /// A counter-clockwise rotation by `angle` radians around `axis`, which
+    /// doesn't need to be normalized
+    pub fn from_axis_angle(axis: Vec3<f32>, angle: f32) -> Self {
+        let axis = axis.normalized();
+        let (s, c) = (angle / 2.0).sin_cos();
+        Quat(axis.0 * s, axis.1 * s, axis.2 * s, c)
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self { Quat::identity() }
+}
+
+
+// Rotation ////////////////////////////////////////////////////////////////////
+
+impl Quat {
+    pub fn dot(&self, other: Quat) -> f32 {
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2 + self.3 * other.3
+    }
+
+    pub fn len(&self) -> f32 { self.dot(*self).sqrt() }
+
+    pub fn normalized(self) -> Quat {
+        let l = self.len();
+        Quat(self.0 / l, self.1 / l, self.2 / l, self.3 / l)
+    }
+
+    /// The opposite rotation, for unit quaternions
+    pub fn conjugate(self) -> Quat {
+        Quat(-self.0, -self.1, -self.2, self.3)
+    }
+
+    /// Rotate `v` by this quaternion, which should be normalized
+    pub fn rotate(&self, v: Vec3<f32>) -> Vec3<f32> {
+        let q = Vec3(self.0, self.1, self.2);
+        let t = q.cross(v) * 2.0;
+        v + t * self.3 + q.cross(t)
+    }
+
+    /// Spherical linear interpolation from `self` at `t = 0` to `other` at
+    /// `t = 1`, which rotates at a constant speed along the shortest path
+    pub fn slerp(self, other: Quat, t: f32) -> Quat {
+        // q and -q are the same rotation, so flip one if that's shorter
+        let mut cos = self.dot(other);
+        let other = if cos < 0.0 { cos = -cos; -other } else { other };
+
+        let (a, b) = if cos > 0.9995 {
+            // Close enough that sin(θ) would be near 0, so lerp instead
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Quat(self.0 * a + other.0 * b, self.1 * a + other.1 * b,
+             self.2 * a + other.2 * b, self.3 * a + other.3 * b).normalized()
+    }
+
+    /// The rotation matrix for this quaternion, which should be normalized
+    pub fn to_mat4(&self) -> Mat4<f32> {
+        let Quat(x, y, z, w) = *self;
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (w * x, w * y, w * z);
+        Mat4::new([[1.0 - 2.0 * (yy + zz), 2.0 * (xy - wz), 2.0 * (xz + wy), 0.0],
+                   [2.0 * (xy + wz), 1.0 - 2.0 * (xx + zz), 2.0 * (yz - wx), 0.0],
+                   [2.0 * (xz - wy), 2.0 * (yz + wx), 1.0 - 2.0 * (xx + yy), 0.0],
+                   [0.0, 0.0, 0.0, 1.0]])
+    }
+}
+
+
+// Arithmetic //////////////////////////////////////////////////////////////////
+
+/// The Hamilton product, which rotates by `other` and then by `self`
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, other: Quat) -> Quat {
+        let Quat(x1, y1, z1, w1) = self;
+        let Quat(x2, y2, z2, w2) = other;
+        Quat(w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+             w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+             w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+             w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2)
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        Quat(-self.0, -self.1, -self.2, -self.3)
+    }
+}
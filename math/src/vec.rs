@@ -149,10 +149,10 @@ vector_op!(Vec4: Div (div) (/) { 0, 1, 2, 3 });
 
 macro_rules! impl_negate {
     ($V:ident { $($x:tt),* }) => {
-        impl<T> Neg for $V<T> where T: Neg + Clone + Copy {
+        impl<T> Neg for $V<T> where T: Neg<Output=T> + Clone + Copy {
             type Output = Self;
             fn neg(self) -> Self {
-                $V($(expr!(self.$x)),*)
+                $V($(expr!(-self.$x)),*)
             }
         }
     }
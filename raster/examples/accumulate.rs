@@ -0,0 +1,67 @@
+extern crate cgl;
+
+use cgl::{Camera, Color, Image, Model, Renderer, Shader, Vert, Mat4, Vec3, Vec4};
+use cgl::accumulate::{Accumulation, Sample};
+
+mod demo;
+
+const SIZE: usize = 240;
+
+/// A row of heads rendered once, then with anti-aliasing, depth of field
+/// focused on the middle head, and motion blur as they slide sideways
+fn main() {
+    let model = demo::african_head();
+    let camera = Camera::perspective(35f32.to_radians(), 1.0, 0.1, 100.0)
+        .look_at(Vec3(2.0, 0.6, 6.5), Vec3(0.0, 0.0, -1.0), Vec3(0.0, 1.0, 0.0));
+    let focus = (camera.position() - Vec3(0.0, 0.0, -1.0)).len() as f32;
+
+    let panels = [Accumulation::new(1).antialias(false),
+                  Accumulation::new(32),
+                  Accumulation::new(64).aperture(0.15, focus),
+                  Accumulation::new(32).shutter(1.0)];
+    let mut image = Image::with_dimensions(SIZE * 2, SIZE * 2);
+    let mut renderer = Renderer::with_dimensions(SIZE, SIZE);
+    for (i, accumulation) in panels.iter().enumerate() {
+        let motion = accumulation.shutter > 0.0;
+        let average = accumulation.render(&mut renderer, |renderer, sample| {
+            draw(renderer, &model, camera, sample, motion);
+        });
+        let (left, top) = (i % 2 * SIZE, i / 2 * SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                image[(left + x, top + y)] = Color::from_linear(average[(x, y)]);
+            }
+        }
+    }
+
+    demo::save(&image, 32);
+}
+
+/// Draw three heads going back into the distance, sliding to the right over
+/// the shutter interval if `motion` is set
+fn draw(renderer: &mut Renderer, model: &Model<Vert>, camera: Camera, sample: &Sample,
+        motion: bool) {
+    let camera = sample.camera(camera);
+    let view_projection = Mat4::viewport(SIZE as i32, SIZE as i32) * camera.view_projection();
+    for i in 0..3 {
+        let shift = if motion { sample.time * 0.2 * (i + 1) as f32 } else { 0.0 };
+        let position = Vec3(i as f32 - 1.0 + shift, 0.0, -2.0 * i as f32 + 1.0);
+        renderer.model(&Diffuse, &(view_projection * Mat4::translate(position)), model);
+    }
+}
+
+struct Diffuse;
+
+impl Shader<Vert, Mat4<f32>> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, vert: Vert, _: &Mat4<f32>) -> Color {
+        let c = vert.norm.normalized().dot(Vec3(0.3f32, 1.0, 0.8).normalized()).max(0.1);
+        Color::float_rgb(c, c, c)
+    }
+}
@@ -0,0 +1,65 @@
+extern crate cgl;
+
+use cgl::{Camera, Color, Image, Renderer, Shader, Vert, Mat4, Vec3, Vec4};
+
+mod demo;
+
+const WIDTH: usize = 384;
+const HEIGHT: usize = 256;
+
+/// The same head through a perspective, an orthographic, and an off-axis
+/// camera, all on a wide image without stretching
+fn main() {
+    let model = demo::african_head();
+    let aspect = WIDTH as f32 / HEIGHT as f32;
+    let (eye, target, up) = (Vec3(1.2, 0.8, 2.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let cameras = [
+        Camera::perspective(45f32.to_radians(), aspect, 0.1, 10.0),
+        Camera::orthographic(2.2, aspect, 0.1, 10.0),
+        // Looking up and to the right without turning the camera
+        Camera::off_axis(-0.03, 0.09, -0.02, 0.06, 0.1, 10.0),
+    ];
+
+    let mut image = Image::with_dimensions(WIDTH * cameras.len(), HEIGHT);
+    for (i, camera) in cameras.iter().enumerate() {
+        let camera = camera.look_at(eye, target, up);
+        let matrix = Mat4::viewport(WIDTH as i32, HEIGHT as i32) * camera.view_projection();
+        let mut renderer = Renderer::with_dimensions(WIDTH, HEIGHT);
+        renderer.model(&Diffuse, &matrix, &model);
+
+        // Mark where the ray through the middle of the image hits the origin's
+        // depth, which should land back on the middle pixel
+        let ray = camera.ray(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0, WIDTH, HEIGHT);
+        let distance = (camera.position() - target).len() as f32;
+        let hit = (matrix * ray.at(distance).augment()).retro_project();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let on_cross = (x as f32 - hit.0).abs() < 1.0 || (y as f32 - hit.1).abs() < 1.0;
+                let near_hit = (x as f32 - hit.0).abs() < 6.0 && (y as f32 - hit.1).abs() < 6.0;
+                image[(x + i * WIDTH, y)] = if on_cross && near_hit {
+                    Color::rgb(255, 40, 40)
+                } else {
+                    renderer.image()[(x, y)]
+                };
+            }
+        }
+    }
+
+    demo::save(&image, 22);
+}
+
+struct Diffuse;
+
+impl Shader<Vert, Mat4<f32>> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, vert: Vert, _: &Mat4<f32>) -> Color {
+        let c = vert.norm.normalized().dot(Vec3(0.3f32, 1.0, 0.8).normalized()).max(0.0);
+        Color::float_rgb(c, c, c)
+    }
+}
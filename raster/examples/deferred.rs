@@ -0,0 +1,73 @@
+extern crate cgl;
+
+use std::fs::File;
+use std::f32::consts::PI;
+
+use cgl::{Color, HdrColor, Texture, read_bmp};
+use cgl::{DeferredRenderer, GBufferMaterial, PointLight, Surface};
+use cgl::{Shader, Vert, Mat4, Vec3, Vec4};
+use cgl::tonemap::Reinhard;
+
+mod demo;
+
+fn main() {
+    let model = demo::african_head();
+    let mut renderer = DeferredRenderer::with_dimensions(512, 512);
+    let matrix = demo::african_head_matrix();
+
+    let texture = {
+        let mut file = File::open("assets/african_head/diffuse.bmp")
+            .expect("Should open assets/african_head/diffuse.bmp");
+        Texture::srgb(read_bmp(&mut file).expect("Should read image"))
+    };
+
+    renderer.model(&GeometryPass, &(matrix, texture), &model);
+
+    // A ring of small colored lights around the head
+    let lights = (0..48).map(|i| {
+        let angle = i as f32 / 48.0 * 2.0 * PI;
+        let color = match i % 3 {
+            0 => HdrColor::rgb(1.0, 0.3, 0.2),
+            1 => HdrColor::rgb(0.2, 1.0, 0.3),
+            _ => HdrColor::rgb(0.3, 0.4, 1.0),
+        };
+        let height = (i % 4) as f32 * 0.5 - 0.75;
+        PointLight::new(Vec3(angle.cos() * 0.9, height, angle.sin() * 0.9),
+                        color, 1.5, 0.8)
+    }).collect::<Vec<_>>();
+
+    let view = Mat4::lookat(Vec3(0.0, 0.0, 0.0),
+                            Vec3(0.3, 0.2, 0.5),
+                            Vec3(0.0, 1.0, 0.0));
+    let eye = (view.inverted().expect("Should invert view") *
+               Vec4(0.0, 0.0, 1.0, 1.0)).retro_project();
+
+    renderer.ambient(HdrColor::gray(0.05));
+    renderer.lights(&lights, &matrix, eye);
+
+    demo::save(&renderer.resolve(&Reinhard::default()), 14);
+}
+
+struct GeometryPass;
+
+impl Shader<Vert, (Mat4<f32>, Texture), Surface> for GeometryPass {
+    type VOut = Vert;
+
+    fn vertex(&self, vertex: Vert, &(matrix, _): &(Mat4<f32>, Texture),
+              pos: &mut Vec4<f32>)
+              -> Vert
+    {
+        *pos = matrix * vertex.pos.augment();
+        vertex
+    }
+
+    fn fragment(&self, input: Vert, (_, texture): &(Mat4<f32>, Texture))
+                -> Surface
+    {
+        Surface {
+            albedo: Color::from_linear(texture.sample_clamp(input.tex.0, input.tex.1)),
+            normal: input.norm,
+            material: GBufferMaterial { specular: 0.4, shininess: 24.0 },
+        }
+    }
+}
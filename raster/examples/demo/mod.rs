@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use cgl::{Color, Obj, Image, Model, Vert, Mat4, Vec3, read_bmp, write_bmp};
+use cgl::{Color, CubeMap, HdrColor, Obj, Image, Model, Vert, Mat4, Vec3, read_bmp, write_bmp};
 
 use std::fs::File;
 use std::io::BufReader;
@@ -36,6 +36,27 @@ pub fn african_head_matrix() -> Mat4<f32> {
     viewport * perspective * view * model
 }
 
+/// A procedural outdoor environment, with a sky fading into the horizon, a
+/// bright sun, and a checkered ground
+pub fn environment(size: usize) -> CubeMap<HdrColor> {
+    let sun = Vec3(-0.4f32, 0.5, -0.6).normalized();
+    CubeMap::from_fn(size, |dir| {
+        let dir = dir.normalized();
+        if dir.1 >= 0.0 {
+            let t = dir.1.powf(0.5);
+            let sky = HdrColor::rgb(0.9, 0.9, 1.0) * (1.0 - t) + HdrColor::rgb(0.2, 0.4, 0.9) * t;
+            let glow = dir.dot(sun).max(0.0).powf(500.0) * 40.0;
+            sky + HdrColor::rgb(1.0, 0.9, 0.7) * glow
+        } else {
+            let (x, z) = (dir.0 / -dir.1, dir.2 / -dir.1);
+            let check = (x.floor() + z.floor()) as i32 % 2 == 0;
+            let ground = if check { HdrColor::rgb(0.3, 0.25, 0.2) } else { HdrColor::rgb(0.5, 0.45, 0.35) };
+            let haze = (-dir.1).powf(0.3);
+            HdrColor::rgb(0.8, 0.8, 0.85) * (1.0 - haze) + ground * haze
+        }
+    })
+}
+
 pub fn image_filename(number: u32) -> String {
     format!("output/demo{:03}.bmp", number)
 }
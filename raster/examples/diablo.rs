@@ -2,9 +2,10 @@ extern crate cgl;
 
 use std::fs::File;
 
-use cgl::{Color, Image, Renderer, read_bmp};
+use cgl::{Color, HdrColor, Image, Renderer, Texture, read_bmp};
 use cgl::{Shader, TanVert, Mat4, Vec3, Vec4};
 use cgl::model::compute_tangent_space;
+use cgl::tonemap::{self, Aces};
 
 mod demo;
 
@@ -19,7 +20,7 @@ const HEIGHT: usize = 2048;
 
 fn main() {
     let model = compute_tangent_space(demo::diablo_model());
-    let mut renderer = Renderer::with_dimensions(WIDTH, HEIGHT);
+    let mut renderer: Renderer<HdrColor> = Renderer::with_dimensions(WIDTH, HEIGHT);
 
     let view_matrix = {
         let viewport = Mat4::viewport(WIDTH as i32, HEIGHT as i32);
@@ -34,9 +35,9 @@ fn main() {
         view * model
     };
 
-    let diffuse = load_image("assets/diablo/diffuse.bmp");
+    let diffuse = Texture::srgb(load_image("assets/diablo/diffuse.bmp"));
     let normals = load_image("assets/diablo/normal.bmp");
-    let glow = load_image("assets/diablo/glow.bmp");
+    let glow = Texture::srgb(load_image("assets/diablo/glow.bmp"));
     let spec = load_image("assets/diablo/spec.bmp");
 
     let shader = Diablo;
@@ -50,21 +51,21 @@ fn main() {
         spec: spec,
     }, &model);
 
-    demo::save(renderer.image(), 13);
+    demo::save(&tonemap::resolve(renderer.image(), &Aces::default()), 13);
 }
 
 struct Uniform {
     view: Mat4<f32>,
     model: Mat4<f32>,
-    diff: Image<Color>,
+    diff: Texture,
     norm: Image<Color>,
-    glow: Image<Color>,
+    glow: Texture,
     spec: Image<Color>,
 }
 
 struct Diablo;
 
-impl Shader<TanVert, Uniform> for Diablo {
+impl Shader<TanVert, Uniform, HdrColor> for Diablo {
     type VOut = TanVert;
 
     fn vertex(&self, vertex: TanVert, uniform: &Uniform, pos: &mut Vec4<f32>) -> TanVert {
@@ -72,7 +73,7 @@ impl Shader<TanVert, Uniform> for Diablo {
         vertex
     }
 
-    fn fragment(&self, input: TanVert, uniform: &Uniform) -> Color {
+    fn fragment(&self, input: TanVert, uniform: &Uniform) -> HdrColor {
         let normal = {
             let norm = uniform.norm.sample_clamp(input.tex.0, input.tex.1);
             let tspace = Vec3(norm.r as f32, norm.g as f32, norm.b as f32);
@@ -91,11 +92,11 @@ impl Shader<TanVert, Uniform> for Diablo {
         let pos = (Vec3(pos.0, pos.1, pos.2) + Vec3(0.0, 0.0, -1.0)) *
             Vec3(1.0, 1.0, -1.0);
         let mut spec =
-            (-light_vector + pos.normalized()).normalized().dot(normal).powf(18.0);
-        spec += (-light_vector2 + pos.normalized()).normalized().dot(normal).powf(18.0);
+            (light_vector + pos.normalized()).normalized().dot(normal).powf(18.0);
+        spec += (light_vector2 + pos.normalized()).normalized().dot(normal).powf(18.0);
         spec *= 0.3;
         // let spec_color = uniform.spec.sample_clamp(input.tex.0, input.tex.1);
-        let spec_color = Color::float_rgb(1.0, 1.0, 1.0);
+        let spec_color = HdrColor::gray(1.0);
 
         (albedo * light) + (glow * 5.0) + (spec_color * spec)
     }
@@ -0,0 +1,81 @@
+extern crate cgl;
+
+use cgl::{Background, Camera, Color, CubeMap, HdrColor, Image, Renderer, SceneUniform, Shader,
+          Vert, Mat4, Vec3, Vec4};
+use cgl::cubemap::{reflect, refract};
+use cgl::tonemap::{self, Aces};
+
+mod demo;
+
+const SIZE: usize = 320;
+
+/// A chrome head and a glass head in the middle of an environment, along with
+/// the environment unwrapped into an equirectangular image
+fn main() {
+    let environment = demo::environment(128);
+    let background = Background::CubeMap(environment.clone());
+    let model = demo::african_head();
+    let camera = Camera::perspective(50f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(0.8, 0.4, 2.4), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(SIZE as i32, SIZE as i32) * camera.projection(),
+                                    vec![]);
+
+    let mut image = Image::with_dimensions(SIZE * 2, SIZE * 2);
+    let shaders = [Mirror::Chrome(&environment), Mirror::Glass(&environment)];
+    for (i, shader) in shaders.iter().enumerate() {
+        let mut renderer: Renderer<HdrColor> = Renderer::with_dimensions(SIZE, SIZE);
+        renderer.model(shader, &uniform, &model);
+
+        renderer.background(&background, &camera);
+        blit(&mut image, &tonemap::resolve(renderer.image(), &Aces::default()), i * SIZE, 0);
+    }
+
+    let flat = environment.to_equirectangular(SIZE * 2, SIZE);
+    blit(&mut image, &tonemap::resolve(&flat, &Aces::default()), 0, SIZE);
+
+    demo::save(&image, 26);
+}
+
+fn blit(image: &mut Image<Color>, tile: &Image<Color>, x0: usize, y0: usize) {
+    for y in 0..tile.height {
+        for x in 0..tile.width {
+            image[(x0 + x, y0 + y)] = tile[(x, y)];
+        }
+    }
+}
+
+enum Mirror<'a> {
+    Chrome(&'a CubeMap<HdrColor>),
+    Glass(&'a CubeMap<HdrColor>),
+}
+
+impl<'a> Shader<Vert, SceneUniform, HdrColor> for Mirror<'a> {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vert {
+        *pos = uniform.project(vert.pos);
+        Vert {
+            pos: uniform.world_position(vert.pos),
+            tex: vert.tex,
+            norm: uniform.world_normal(vert.norm),
+        }
+    }
+
+    fn fragment(&self, vert: Vert, uniform: &SceneUniform) -> HdrColor {
+        let normal = vert.norm.normalized();
+        let incident = (vert.pos - uniform.eye).normalized();
+        match *self {
+            Mirror::Chrome(environment) => {
+                environment.sample(reflect(incident, normal)) * HdrColor::rgb(0.9, 0.9, 0.95)
+            }
+            Mirror::Glass(environment) => {
+                let reflected = environment.sample(reflect(incident, normal));
+                let refracted = refract(incident, normal, 1.0 / 1.5)
+                    .map_or(reflected, |dir| environment.sample(dir));
+                let fresnel = 0.04 + 0.96 * (1.0 + incident.dot(normal)).max(0.0).powi(5);
+                reflected * fresnel + refracted * HdrColor::rgb(0.8, 0.95, 0.9) * (1.0 - fresnel)
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+extern crate cgl;
+
+use cgl::{Color, Mat4, Primitive, Renderer, Shader, Vec3, Vec4, Vert};
+
+mod demo;
+
+fn main() {
+    let model = demo::african_head();
+    let mut renderer = Renderer::with_dimensions(512, 512);
+    let matrix = demo::african_head_matrix();
+
+    renderer.model(&Explode, &matrix, &model);
+
+    demo::save(renderer.image(), 16);
+}
+
+/// Pushes every face of the model outwards and shrinks it, and draws a short
+/// line sticking out of each face along its normal.
+struct Explode;
+
+impl Shader<Vert, Mat4<f32>> for Explode {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn geometry(&self, [(_, a), (_, b), (_, c)]: [(Vec4<f32>, Vert); 3],
+                mat: &Mat4<f32>, emit: &mut dyn FnMut(Primitive<Vert>))
+    {
+        let normal = (b.pos - a.pos).cross(c.pos - a.pos).normalized();
+        let centroid = (a.pos + b.pos + c.pos) / 3.0;
+        let center = centroid + normal * 0.05;
+        let shrink = |v: Vert| {
+            let pos = center + (v.pos - centroid) * 0.7;
+            (*mat * pos.augment(), Vert { pos, ..v })
+        };
+        emit(Primitive::Triangle([shrink(a), shrink(b), shrink(c)]));
+
+        // The fragment stage draws vertices without a normal as highlights
+        let tip = center + normal * 0.04;
+        let spike = |pos: Vec3<f32>| {
+            (*mat * pos.augment(), Vert { pos, tex: a.tex, norm: Vec3(0.0, 0.0, 0.0) })
+        };
+        emit(Primitive::Line([spike(center), spike(tip)]));
+    }
+
+    fn fragment(&self, input: Vert, _uniform: &Mat4<f32>) -> Color {
+        if input.norm == Vec3(0.0, 0.0, 0.0) {
+            return Color::rgb(255, 220, 60);
+        }
+        let c = input.norm.normalized()
+            .dot(Vec3(0.0f32, 1.0, 0.5).normalized());
+        Color::float_rgb(c * 1.2, c, c * 0.8)
+    }
+}
@@ -0,0 +1,88 @@
+extern crate cgl;
+
+use cgl::{Background, Camera, Color, DirectionalLight, HdrColor, Image, Model, Renderer,
+          SceneUniform, Vert, Mat4, Vec2, Vec3};
+use cgl::fog::{Falloff, Fog};
+use cgl::phong::{BlinnPhong, Material};
+
+mod demo;
+
+const WIDTH: usize = 480;
+const HEIGHT: usize = 240;
+
+/// Rolling hills with no fog, distance fog, and height fog that settles in
+/// the valleys, one above the other
+fn main() {
+    let terrain = terrain(120, 60.0);
+    let camera = Camera::perspective(50f32.to_radians(), WIDTH as f32 / HEIGHT as f32, 0.1, 100.0)
+        .look_at(Vec3(0.0, 3.0, 28.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let sun = DirectionalLight::new(Vec3(-1.0, -0.8, -0.5), HdrColor::rgb(1.0, 0.95, 0.85), 1.0);
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(WIDTH as i32, HEIGHT as i32) *
+                                    camera.projection(),
+                                    vec![sun.into()]);
+    let shader = BlinnPhong(Material {
+        specular: HdrColor::gray(0.05),
+        ..Material::new(HdrColor::rgb(0.35, 0.5, 0.25), HdrColor::black(), 8.0)
+    });
+    let haze = HdrColor::rgb(0.75, 0.8, 0.85);
+    let sky = Background::Gradient {
+        top: Color::from_linear(HdrColor::rgb(0.25, 0.45, 0.85)),
+        bottom: Color::from_linear(haze),
+    };
+
+    let fogs = [
+        None,
+        Some(Fog::new(haze, Falloff::ExponentialSquared { density: 0.03 })),
+        Some(Fog::new(haze, Falloff::Exponential { density: 0.3 }).with_height(-1.0, 1.5)),
+    ];
+    let mut image = Image::with_dimensions(WIDTH, HEIGHT * fogs.len());
+    for (i, fog) in fogs.iter().enumerate() {
+        let mut renderer = Renderer::with_dimensions(WIDTH, HEIGHT);
+        renderer.background(&sky, &camera);
+        renderer.model(&shader, &uniform, &terrain);
+        if let Some(ref fog) = *fog {
+            renderer.fog(fog, &camera);
+        }
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                image[(x, y + i * HEIGHT)] = renderer.image()[(x, y)];
+            }
+        }
+    }
+
+    demo::save(&image, 28);
+}
+
+fn height(x: f32, z: f32) -> f32 {
+    (x * 0.3).sin() * (z * 0.25).cos() * 1.5 + (x * 0.11 + z * 0.07).sin() * 2.0 - 0.5
+}
+
+/// A square grid of `cells` by `cells` quads, `size` units across
+fn terrain(cells: usize, size: f32) -> Model<Vert> {
+    let mut vertices = Vec::new();
+    for j in 0..cells + 1 {
+        for i in 0..cells + 1 {
+            let x = (i as f32 / cells as f32 - 0.5) * size;
+            let z = (j as f32 / cells as f32 - 0.5) * size;
+            let e = 0.01;
+            let norm = Vec3(height(x - e, z) - height(x + e, z), 2.0 * e,
+                            height(x, z - e) - height(x, z + e)).normalized();
+            vertices.push(Vert {
+                pos: Vec3(x, height(x, z), z),
+                tex: Vec2(i as f32 / cells as f32, j as f32 / cells as f32),
+                norm,
+            });
+        }
+    }
+    let mut triangles = Vec::new();
+    let row = cells + 1;
+    for j in 0..cells {
+        for i in 0..cells {
+            let corner = j * row + i;
+            triangles.push([corner, corner + row, corner + 1]);
+            triangles.push([corner + 1, corner + row, corner + row + 1]);
+        }
+    }
+    Model { vertices, triangles }
+}
@@ -0,0 +1,54 @@
+extern crate cgl;
+
+use std::rc::Rc;
+
+use cgl::{Background, Camera, HdrColor, Image, Renderer, SceneUniform, Mat4, Vec3};
+use cgl::ibl::Environment;
+use cgl::model::compute_tangent_space;
+use cgl::pbr::{CookTorrance, Material};
+use cgl::tonemap::{self, Aces};
+
+mod demo;
+
+const SIZE: usize = 160;
+const COLUMNS: usize = 5;
+
+/// The heads from the PBR demo, lit only by the environment from the
+/// environment mapping demo instead of by lights
+fn main() {
+    let cube = demo::environment(128);
+    let environment = Rc::new(Environment::new(&cube));
+    let background = Background::CubeMap(cube);
+    let model = compute_tangent_space(demo::african_head());
+    let camera = Camera::perspective(35f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(0.8, 0.3, 3.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(SIZE as i32, SIZE as i32) * camera.projection(),
+                                    vec![]);
+
+    let rows = [
+        (HdrColor::rgb(0.6, 0.05, 0.05), 0.0),
+        (HdrColor::rgb(1.0, 0.78, 0.34), 1.0),
+    ];
+    let mut image = Image::with_dimensions(SIZE * COLUMNS, SIZE * rows.len());
+    for (row, &(base_color, metallic)) in rows.iter().enumerate() {
+        for column in 0..COLUMNS {
+            let roughness = column as f32 / (COLUMNS - 1) as f32;
+            let shader = CookTorrance(Material {
+                environment: Some(environment.clone()),
+                ..Material::new(base_color, metallic, roughness)
+            });
+            let mut renderer: Renderer<HdrColor> = Renderer::with_dimensions(SIZE, SIZE);
+            renderer.model(&shader, &uniform, &model);
+            renderer.background(&background, &camera);
+            let tile = tonemap::resolve(renderer.image(), &Aces::default());
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    image[(x + column * SIZE, y + row * SIZE)] = tile[(x, y)];
+                }
+            }
+        }
+    }
+
+    demo::save(&image, 27);
+}
@@ -0,0 +1,62 @@
+extern crate cgl;
+
+use std::fs::File;
+
+use cgl::{Camera, Color, DirectionalLight, Encoding, HdrColor, Image, Model, PointLight,
+          Renderer, SceneUniform, Shader, SpotLight, TanVert, Texture, Mat4, Vec3};
+use cgl::model::compute_tangent_space;
+use cgl::phong::{BlinnPhong, Gouraud, Material, NormalMapped, Phong};
+
+mod demo;
+
+const SIZE: usize = 256;
+
+fn load_texture(fname: &str, encoding: Encoding) -> Texture {
+    let mut file = File::open(fname)
+        .unwrap_or_else(|_| panic!("Should open {}", fname));
+    Texture::read_bmp(&mut file, encoding).expect("Should read image")
+}
+
+/// The head lit by a directional, a point and a spot light, through each of
+/// the Blinn-Phong library's shaders
+fn main() {
+    let model = compute_tangent_space(demo::african_head());
+    let camera = Camera::perspective(40f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(0.6, 0.3, 2.6), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let lights = vec![
+        DirectionalLight::new(Vec3(-1.0, -0.5, -1.0), HdrColor::rgb(1.0, 0.95, 0.9), 0.4).into(),
+        PointLight::new(Vec3(-1.5, 0.5, 1.0), HdrColor::rgb(0.3, 0.5, 1.0), 1.5, 5.0).into(),
+        SpotLight::new(Vec3(0.0, 2.0, 1.0), Vec3(0.0, -2.0, -1.0), HdrColor::rgb(1.0, 0.7, 0.3),
+                       2.5, 6.0, 10f32.to_radians(), 20f32.to_radians()).into(),
+    ];
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(SIZE as i32, SIZE as i32) * camera.projection(),
+                                    lights);
+
+    let material = Material::new(HdrColor::gray(0.6), HdrColor::gray(0.4), 40.0);
+    let textured = NormalMapped::new(
+        Material { diffuse: HdrColor::gray(1.0), ..material },
+        load_texture("assets/african_head/diffuse.bmp", Encoding::Srgb),
+        load_texture("assets/african_head/normal.bmp", Encoding::Linear));
+
+    let mut image = Image::with_dimensions(SIZE * 4, SIZE);
+    draw(&mut image, 0, &Gouraud(material), &uniform, &model);
+    draw(&mut image, 1, &Phong(material), &uniform, &model);
+    draw(&mut image, 2, &BlinnPhong(material), &uniform, &model);
+    draw(&mut image, 3, &textured, &uniform, &model);
+
+    demo::save(&image, 24);
+}
+
+fn draw<S>(image: &mut Image<Color>, panel: usize, shader: &S, uniform: &SceneUniform,
+           model: &Model<TanVert>)
+    where S: Shader<TanVert, SceneUniform, Color>
+{
+    let mut renderer = Renderer::with_dimensions(SIZE, SIZE);
+    renderer.model(shader, uniform, model);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            image[(x + panel * SIZE, y)] = renderer.image()[(x, y)];
+        }
+    }
+}
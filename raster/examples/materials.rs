@@ -0,0 +1,94 @@
+extern crate cgl;
+
+use std::env;
+use std::fs::File;
+
+use cgl::{Color, Encoding, Image, Renderer, Texture, Vert, Mat4, Vec2, Vec3, Vec4};
+use cgl::shader::{BoxedShader, FnShader, boxed};
+
+mod demo;
+
+const SIZE: usize = 256;
+
+/// Renders the model with a material chosen by name on the command line, or
+/// with each of them side by side if there isn't one.
+fn main() {
+    let model = demo::african_head();
+    let matrix = Mat4::viewport(SIZE as i32, SIZE as i32) * Mat4::perspective(1.0) *
+        Mat4::lookat(Vec3(0.0, 0.0, 0.0), Vec3(0.3, 0.2, 0.5), Vec3(0.0, 1.0, 0.0));
+
+    let mut file = File::open("assets/african_head/diffuse.bmp")
+        .expect("Should open assets/african_head/diffuse.bmp");
+    let diffuse = Texture::read_bmp(&mut file, Encoding::Srgb)
+        .expect("Should read image");
+
+    let materials = materials(&diffuse);
+    let chosen: Vec<_> = match env::args().nth(1) {
+        Some(name) => {
+            let material = materials.iter()
+                .find(|&(n, _)| *n == name)
+                .unwrap_or_else(|| panic!("There's no material called {:?}", name));
+            vec![material]
+        }
+        None => materials.iter().collect(),
+    };
+
+    let mut image = Image::with_dimensions(SIZE * chosen.len(), SIZE);
+    for (i, (_, shader)) in chosen.into_iter().enumerate() {
+        let mut renderer = Renderer::with_dimensions(SIZE, SIZE);
+        renderer.model(shader, &matrix, &model);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                image[(x + i * SIZE, y)] = renderer.image()[(x, y)];
+            }
+        }
+    }
+
+    demo::save(&image, 20);
+}
+
+fn position(vert: &Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) {
+    *pos = *mat * vert.pos.augment();
+}
+
+fn lambert(norm: Vec3<f32>) -> f32 {
+    norm.normalized().dot(Vec3(0.0f32, 1.0, 0.5).normalized()).max(0.0)
+}
+
+fn materials(diffuse: &Texture) -> Vec<(&'static str, BoxedShader<'_, Vert, Mat4<f32>>)> {
+    vec![
+        ("normals", boxed(FnShader::new(
+            |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+                position(&vert, mat, pos);
+                vert.norm
+            },
+            |norm: Vec3<f32>, _: &Mat4<f32>| {
+                let n = norm.normalized() * 0.5 + Vec3(0.5, 0.5, 0.5);
+                Color::float_rgb(n.0, n.1, n.2)
+            }))),
+        ("gouraud", boxed(FnShader::new(
+            |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+                position(&vert, mat, pos);
+                Vec2(lambert(vert.norm), 0.0)
+            },
+            |light: Vec2<f32>, _: &Mat4<f32>| Color::float_rgb(light.0, light.0, light.0)))),
+        ("toon", boxed(FnShader::new(
+            |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+                position(&vert, mat, pos);
+                vert.norm
+            },
+            |norm: Vec3<f32>, _: &Mat4<f32>| {
+                let c = (lambert(norm) * 4.0).ceil() / 4.0;
+                Color::float_rgb(c, c * 0.7, c * 0.4)
+            }))),
+        ("textured", boxed(FnShader::new(
+            |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+                position(&vert, mat, pos);
+                vert
+            },
+            move |vert: Vert, _: &Mat4<f32>| {
+                let albedo = diffuse.sample_clamp(vert.tex.0, vert.tex.1);
+                Color::from_linear(albedo * lambert(vert.norm))
+            }))),
+    ]
+}
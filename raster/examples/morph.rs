@@ -0,0 +1,71 @@
+extern crate cgl;
+
+use cgl::{Camera, Color, DirectionalLight, HdrColor, Image, Model, Renderer, SceneUniform, Vert,
+          Mat4, Vec3};
+use cgl::morph::MorphModel;
+use cgl::phong::{BlinnPhong, Material};
+
+mod demo;
+
+const SIZE: usize = 200;
+
+/// A smooth step from 0 at `a` to 1 at `b`
+fn smoothstep(a: f32, b: f32, x: f32) -> f32 {
+    let t = ((x - a) / (b - a)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// A copy of `model` with every vertex moved by `f`
+fn edited<F: Fn(Vert) -> Vert>(model: &Model<Vert>, f: F) -> Model<Vert> {
+    Model {
+        vertices: model.vertices.iter().map(|&vert| f(vert)).collect(),
+        triangles: model.triangles.clone(),
+    }
+}
+
+/// The head with its jaw dropping, its cheeks puffing out, and both at once.
+/// The targets are sculpted from the base mesh here, but would usually be
+/// loaded with `MorphModel::open_objs`.
+fn main() {
+    let base = demo::african_head();
+    let jaw = edited(&base, |vert| {
+        let amount = smoothstep(-0.25, -0.45, vert.pos.1) * smoothstep(-0.2, 0.3, vert.pos.2);
+        let turn = Mat4::translate(Vec3(0.0, -0.2, -0.3)) * Mat4::rotate_x(0.35 * amount) *
+            Mat4::translate(Vec3(0.0, 0.2, 0.3));
+        Vert { pos: (turn * vert.pos.augment()).retro_project(), ..vert }
+    });
+    let puff = edited(&base, |vert| {
+        let cheek = smoothstep(0.45, 0.2, (vert.pos.1 + 0.2).abs() * 1.5 + vert.pos.0.abs() * 0.4 -
+                               (vert.pos.0.abs() - 0.35).min(0.0)) *
+            smoothstep(0.0, 0.3, vert.pos.2);
+        Vert { pos: vert.pos + vert.norm * (0.08 * cheek), ..vert }
+    });
+    let mut model = MorphModel::new(base);
+    model.add_target("jaw", &jaw).expect("Same faces");
+    model.add_target("puff", &puff).expect("Same faces");
+
+    let camera = Camera::perspective(40f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(1.2, 0.1, 2.6), Vec3(0.0, -0.1, 0.0), Vec3(0.0, 1.0, 0.0));
+    let light = DirectionalLight::new(Vec3(-1.0, -0.6, -1.0), HdrColor::gray(1.0), 1.0);
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(SIZE as i32, SIZE as i32) * camera.projection(),
+                                    vec![light.into()]);
+    let shader = BlinnPhong(Material::new(HdrColor::rgb(0.7, 0.55, 0.45), HdrColor::gray(0.3),
+                                          30.0));
+
+    let weights = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+    let mut image = Image::with_dimensions(SIZE * weights.len(), SIZE);
+    let mut blended = Model { vertices: Vec::new(), triangles: model.base.triangles.clone() };
+    for (i, weights) in weights.iter().enumerate() {
+        model.blend_into(weights, &mut blended.vertices);
+        let mut renderer: Renderer<Color> = Renderer::with_dimensions(SIZE, SIZE);
+        renderer.model(&shader, &uniform, &blended);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                image[(x + i * SIZE, y)] = renderer.image()[(x, y)];
+            }
+        }
+    }
+
+    demo::save(&image, 30);
+}
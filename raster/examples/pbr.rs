@@ -0,0 +1,49 @@
+extern crate cgl;
+
+use cgl::{Camera, DirectionalLight, HdrColor, Image, PointLight, Renderer, SceneUniform, Mat4,
+          Vec3};
+use cgl::model::compute_tangent_space;
+use cgl::pbr::{CookTorrance, Material};
+use cgl::tonemap::{self, Aces};
+
+mod demo;
+
+const SIZE: usize = 160;
+const COLUMNS: usize = 5;
+
+/// Heads going from smooth to rough, as plastic on the top row and gold on
+/// the bottom row
+fn main() {
+    let model = compute_tangent_space(demo::african_head());
+    let camera = Camera::perspective(35f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(0.8, 0.3, 3.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let lights = vec![
+        DirectionalLight::new(Vec3(-1.0, -0.6, -1.0), HdrColor::gray(1.0), 3.0).into(),
+        PointLight::new(Vec3(-2.0, 0.5, -1.0), HdrColor::rgb(0.4, 0.6, 1.0), 20.0, 6.0).into(),
+    ];
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(SIZE as i32, SIZE as i32) * camera.projection(),
+                                    lights);
+
+    let rows = [
+        (HdrColor::rgb(0.6, 0.05, 0.05), 0.0),
+        (HdrColor::rgb(1.0, 0.78, 0.34), 1.0),
+    ];
+    let mut image = Image::with_dimensions(SIZE * COLUMNS, SIZE * rows.len());
+    for (row, &(base_color, metallic)) in rows.iter().enumerate() {
+        for column in 0..COLUMNS {
+            let roughness = (column as f32 + 1.0) / COLUMNS as f32;
+            let shader = CookTorrance(Material::new(base_color, metallic, roughness));
+            let mut renderer: Renderer<HdrColor> = Renderer::with_dimensions(SIZE, SIZE);
+            renderer.model(&shader, &uniform, &model);
+            let tile = tonemap::resolve(renderer.image(), &Aces::default());
+            for y in 0..SIZE {
+                for x in 0..SIZE {
+                    image[(x + column * SIZE, y + row * SIZE)] = tile[(x, y)];
+                }
+            }
+        }
+    }
+
+    demo::save(&image, 25);
+}
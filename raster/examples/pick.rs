@@ -0,0 +1,54 @@
+extern crate cgl;
+
+use cgl::{Color, Renderer, Shader, Vert, Mat4, Vec3, Vec4};
+
+mod demo;
+
+fn main() {
+    let model = demo::african_head();
+    let matrix = demo::african_head_matrix();
+    let mut renderer = Renderer::with_dimensions(512, 512);
+    renderer.enable_picking();
+    renderer.set_draw_id(1);
+    renderer.model(&Diffuse, &matrix, &model);
+
+    // Pretend the cursor is hovering over the cheek
+    let (x, y) = (256, 300);
+    let hover = renderer.pick(x, y).expect("Should hit the model");
+    println!("({}, {}) is {:?}", x, y, hover);
+
+    // Highlight the triangle under the cursor, and every other pixel that
+    // was drawn by the same model
+    let mut image = renderer.image().clone();
+    for y in 0..image.height {
+        for x in 0..image.width {
+            match renderer.pick(x, y) {
+                Some(pick) if pick.triangle == hover.triangle => {
+                    image[(x, y)] = Color::rgb(255, 40, 40);
+                }
+                Some(pick) if pick.draw == hover.draw => {
+                    image[(x, y)] = image[(x, y)] * Color::rgb(180, 200, 255);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    demo::save(&image, 19);
+}
+
+struct Diffuse;
+
+impl Shader<Vert, Mat4<f32>> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, vert: Vert, _: &Mat4<f32>) -> Color {
+        let c = vert.norm.normalized().dot(Vec3(0.0f32, 1.0, 0.5).normalized());
+        Color::float_rgb(c, c, c)
+    }
+}
@@ -0,0 +1,46 @@
+extern crate cgl;
+
+use cgl::{Color, Mat4, Renderer, Shader, Vec3, Vec4, Vert};
+use cgl::post::{Chain, Pass, Bloom, ColorGrade, Fxaa, Lut3d, Outline, Vignette};
+
+mod demo;
+
+fn main() {
+    let model = demo::african_head();
+    let mut renderer = Renderer::with_dimensions(512, 512);
+    let matrix = demo::african_head_matrix();
+
+    renderer.model(&DiffuseShader, &matrix, &model);
+
+    // A warm, slightly desaturated grade
+    let lut = Lut3d::from_fn(16, |c| {
+        let gray = (c.0 + c.1 + c.2) / 3.0;
+        (c * 0.8 + Vec3(gray, gray, gray) * 0.2) * Vec3(1.1, 1.0, 0.85)
+    });
+
+    let post = Chain::new()
+        .then(Outline::new(Color::rgb(255, 220, 180), 8.0))
+        .then(Fxaa::default())
+        .then(Bloom::new(0.6, 0.8))
+        .then(ColorGrade { lut })
+        .then(Vignette::new(0.6));
+
+    demo::save(&post.apply(renderer.image(), renderer.depth()), 15);
+}
+
+struct DiffuseShader;
+
+impl Shader<Vert, Mat4<f32>> for DiffuseShader {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, input: Vert, _uniform: &Mat4<f32>) -> Color {
+        let c = input.norm.normalized()
+            .dot(Vec3(0.0f32, 1.0, 0.5).normalized());
+        Color::float_rgb(c * 1.2, c, c * 0.8)
+    }
+}
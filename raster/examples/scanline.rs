@@ -0,0 +1,76 @@
+extern crate cgl;
+
+use std::time::{Duration, Instant};
+
+use cgl::{Color, Image, Model, Rasterizer, Renderer, Shader, Vert, Mat4, Vec3, Vec4};
+
+mod demo;
+
+const RUNS: u32 = 20;
+
+/// Compares the speed of the two rasterizers on the African head model, and
+/// checks that they cover the same pixels.
+fn main() {
+    let model = demo::african_head();
+    let matrix = demo::african_head_matrix();
+
+    let (bbox, bbox_time) = bench(Rasterizer::BoundingBox, &model, &matrix);
+    let (scanline, scanline_time) = bench(Rasterizer::Scanline, &model, &matrix);
+
+    for y in 0..bbox.height() {
+        for x in 0..bbox.width() {
+            assert_eq!(bbox.depth()[(x, y)] == f32::MIN,
+                       scanline.depth()[(x, y)] == f32::MIN,
+                       "Coverage differs at {:?}", (x, y));
+        }
+    }
+
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0 / RUNS as f64;
+    println!("bounding box: {:8.3} ms/frame", ms(bbox_time));
+    println!("scanline:     {:8.3} ms/frame", ms(scanline_time));
+    println!("speedup:      {:8.2}x", ms(bbox_time) / ms(scanline_time));
+
+    demo::save(&side_by_side(bbox.image(), scanline.image()), 18);
+}
+
+fn bench(rasterizer: Rasterizer, model: &Model<Vert>, matrix: &Mat4<f32>)
+         -> (Renderer, Duration)
+{
+    let mut total = Duration::default();
+    let mut renderer = Renderer::with_dimensions(512, 512);
+    for _ in 0..RUNS {
+        renderer = Renderer::with_dimensions(512, 512);
+        renderer.set_rasterizer(rasterizer);
+        let start = Instant::now();
+        renderer.model(&Diffuse, matrix, model);
+        total += start.elapsed();
+    }
+    (renderer, total)
+}
+
+fn side_by_side(left: &Image<Color>, right: &Image<Color>) -> Image<Color> {
+    let mut image = Image::with_dimensions(left.width + right.width, left.height);
+    for y in 0..left.height {
+        for x in 0..left.width {
+            image[(x, y)] = left[(x, y)];
+            image[(x + left.width, y)] = right[(x, y)];
+        }
+    }
+    image
+}
+
+struct Diffuse;
+
+impl Shader<Vert, Mat4<f32>> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, vert: Vert, _: &Mat4<f32>) -> Color {
+        let c = vert.norm.normalized().dot(Vec3(0.0f32, 1.0, 0.5).normalized());
+        Color::float_rgb(c, c, c)
+    }
+}
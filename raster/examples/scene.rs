@@ -0,0 +1,38 @@
+extern crate cgl;
+
+use std::rc::Rc;
+
+use cgl::{Camera, HdrColor, Node, PointLight, Renderer, Scene, Mat4, Vec3};
+use cgl::phong::{BlinnPhong, Material};
+use cgl::shader::boxed;
+
+mod demo;
+
+/// Three heads on a turntable, lit by a light that circles with them
+fn main() {
+    let head = Rc::new(demo::african_head());
+    let clay = Material::new(HdrColor::rgb(0.8, 0.6, 0.5), HdrColor::gray(0.4), 24.0);
+    let shader = Rc::new(boxed(BlinnPhong(clay)));
+
+    let mut scene = Scene::new();
+    let root = scene.root();
+    let turntable = scene.add(root, Node::empty(Mat4::rotate_y(30f32.to_radians())));
+    for i in 0..3 {
+        let angle = (i as f32 * 120.0).to_radians();
+        let place = Mat4::rotate_y(angle) * Mat4::translate(Vec3(0.0, 0.0, 1.2)) *
+            Mat4::scale(Vec3(0.6, 0.6, 0.6));
+        scene.add(turntable, Node::mesh(place, head.clone(), shader.clone()));
+    }
+    scene.add(turntable, Node::light(Mat4::identity(),
+                                     PointLight::new(Vec3(1.5, 1.5, 2.0),
+                                                     HdrColor::rgb(1.0, 0.9, 0.8),
+                                                     8.0, 10.0)));
+
+    let rig = scene.add(root, Node::empty(Mat4::rotate_x(-20f32.to_radians())));
+    let camera = scene.add(rig, Node::camera(Mat4::translate(Vec3(0.0, 0.0, 4.0)),
+                                             Camera::default()));
+
+    let mut renderer = Renderer::with_dimensions(512, 512);
+    scene.render(camera, &mut renderer);
+    demo::save(renderer.image(), 21);
+}
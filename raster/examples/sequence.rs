@@ -0,0 +1,64 @@
+extern crate cgl;
+
+use std::fs::File;
+
+use cgl::{Camera, Image, Renderer, Shader, Vert, Mat4, Vec3, Vec4, Color, read_bmp};
+use cgl::controller::{Orbit, Turntable};
+use cgl::sequence::Sequence;
+
+mod demo;
+
+const SIZE: usize = 160;
+const FRAMES: usize = 12;
+
+/// A turntable around the head written out as numbered frames, then read back
+/// in to make a contact sheet. Running it again skips the frames that are
+/// already there.
+fn main() {
+    let model = demo::african_head();
+    let camera = Camera::perspective(40f32.to_radians(), 1.0, 0.1, 100.0);
+    let orbit = Orbit { pitch: 10f32.to_radians(), ..Orbit::framing(&model, &camera) };
+    let sequence = Sequence::new("output/sequence", "head", FRAMES)
+        .fps(FRAMES as f32)
+        .camera(camera)
+        .path(Turntable::new(orbit, 1.0))
+        .resume(true);
+
+    let mut renderer = Renderer::with_dimensions(SIZE, SIZE);
+    let progress = sequence.render(&mut renderer, |renderer, frame| {
+        let matrix = Mat4::viewport(SIZE as i32, SIZE as i32) * frame.camera.view_projection();
+        renderer.model(&Diffuse, &matrix, &model);
+    }).expect("Should write frames");
+    println!("Rendered {} frames, skipped {}", progress.rendered, progress.skipped);
+
+    let mut image = Image::with_dimensions(SIZE * FRAMES / 2, SIZE * 2);
+    for index in 0..FRAMES {
+        let path = sequence.path_of(index);
+        let mut file = File::open(&path).expect("Should open frame");
+        let frame = read_bmp(&mut file).expect("Should read frame");
+        let (left, top) = (index % (FRAMES / 2) * SIZE, index / (FRAMES / 2) * SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                image[(left + x, top + y)] = frame[(x, y)];
+            }
+        }
+    }
+
+    demo::save(&image, 31);
+}
+
+struct Diffuse;
+
+impl Shader<Vert, Mat4<f32>> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, vert: Vert, _: &Mat4<f32>) -> Color {
+        let c = vert.norm.normalized().dot(Vec3(0.3f32, 1.0, 0.8).normalized()).max(0.1);
+        Color::float_rgb(c, c, c)
+    }
+}
@@ -0,0 +1,67 @@
+extern crate cgl;
+
+use std::f32::consts::PI;
+
+use cgl::{Camera, Color, DirectionalLight, HdrColor, Image, Model, Renderer, SceneUniform, Mat4,
+          Quat, Vec3};
+use cgl::phong::{BlinnPhong, Material};
+use cgl::skeleton::{Clip, Key, Skeleton, Skinned, SkinnedVert, Track, Transform};
+
+mod demo;
+
+const SIZE: usize = 200;
+const FRAMES: usize = 8;
+
+/// The head bound to a two joint neck, nodding and then shaking no over the
+/// course of a looping clip
+fn main() {
+    let mut skeleton = Skeleton::new();
+    let neck = skeleton.add("neck", None, Transform::translation(Vec3(0.0, -0.8, 0.0)));
+    let head = skeleton.add("head", Some(neck), Transform::translation(Vec3(0.0, 0.3, 0.0)));
+
+    // Everything above the neck follows the head, with a soft blend between
+    let model = demo::african_head();
+    let model = Model {
+        vertices: model.vertices.iter().map(|&vert| {
+            let t = ((vert.pos.1 + 0.65) / 0.3).clamp(0.0, 1.0);
+            let t = t * t * (3.0 - 2.0 * t);
+            SkinnedVert::new(vert, &[(neck, 1.0 - t), (head, t)])
+        }).collect(),
+        triangles: model.triangles,
+    };
+
+    let turn = |axis: Vec3<f32>, degrees: f32| Quat::from_axis_angle(axis, degrees * PI / 180.0);
+    let keys = |angles: &[(f32, Vec3<f32>, f32)]| {
+        angles.iter().map(|&(time, axis, degrees)| Key { time, value: turn(axis, degrees) })
+            .collect()
+    };
+    let (x, y) = (Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let clip = Clip::new(4.0)
+        .channel(head, Track::Rotation(keys(&[(0.0, x, 0.0), (0.5, x, 20.0), (1.0, x, -10.0),
+                                              (1.5, x, 15.0), (2.0, y, 0.0), (2.5, y, 35.0),
+                                              (3.0, y, -35.0), (3.5, y, 20.0), (4.0, y, 0.0)])));
+
+    let camera = Camera::perspective(40f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(0.8, 0.3, 3.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let light = DirectionalLight::new(Vec3(-1.0, -0.6, -1.0), HdrColor::gray(1.0), 1.0);
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(SIZE as i32, SIZE as i32) * camera.projection(),
+                                    vec![light.into()]);
+    let material = Material::new(HdrColor::rgb(0.7, 0.55, 0.45), HdrColor::gray(0.3), 30.0);
+
+    let mut image = Image::with_dimensions(SIZE * FRAMES / 2, SIZE * 2);
+    for frame in 0..FRAMES {
+        let pose = clip.sample(&skeleton, frame as f32 * clip.duration / FRAMES as f32);
+        let shader = Skinned::new(BlinnPhong(material), skeleton.skin_matrices(&pose));
+        let mut renderer: Renderer<Color> = Renderer::with_dimensions(SIZE, SIZE);
+        renderer.model(&shader, &uniform, &model);
+        let (left, top) = (frame % (FRAMES / 2) * SIZE, frame / (FRAMES / 2) * SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                image[(left + x, top + y)] = renderer.image()[(x, y)];
+            }
+        }
+    }
+
+    demo::save(&image, 29);
+}
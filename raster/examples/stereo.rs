@@ -0,0 +1,46 @@
+extern crate cgl;
+
+use cgl::{Camera, DirectionalLight, HdrColor, Image, Renderer, SceneUniform, Mat4, Vec3};
+use cgl::phong::{BlinnPhong, Material};
+use cgl::stereo::{Anaglyph, Layout, StereoCamera};
+
+mod demo;
+
+const SIZE: usize = 256;
+
+/// The head in stereo, converging on its nose. The top is for viewing with
+/// crossed eyes, and the bottom has color and Dubois anaglyphs for red/cyan
+/// glasses.
+fn main() {
+    let model = demo::african_head();
+    let camera = Camera::perspective(40f32.to_radians(), 1.0, 0.1, 10.0)
+        .look_at(Vec3(0.8, 0.3, 2.8), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+    let stereo = StereoCamera::new(camera, 0.12, 2.4);
+
+    let light = DirectionalLight::new(Vec3(-1.0, -0.6, -1.0), HdrColor::gray(1.0), 1.0);
+    let shader = BlinnPhong(Material::new(HdrColor::rgb(0.7, 0.55, 0.45), HdrColor::gray(0.3),
+                                          30.0));
+    let mut renderer = Renderer::with_dimensions(SIZE, SIZE);
+    let pair = stereo.render(&mut renderer, |renderer, camera, _| {
+        let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                        Mat4::viewport(SIZE as i32, SIZE as i32) *
+                                        camera.projection(),
+                                        vec![light.into()]);
+        renderer.model(&shader, &uniform, &model);
+    });
+
+    let mut image = Image::with_dimensions(SIZE * 2, SIZE * 2);
+    let cross_eye = pair.combine(Layout::CrossEye);
+    let color = pair.combine(Layout::Anaglyph(Anaglyph::Color));
+    let dubois = pair.combine(Layout::Anaglyph(Anaglyph::Dubois));
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            image[(x, y)] = cross_eye[(x, y)];
+            image[(x + SIZE, y)] = cross_eye[(x + SIZE, y)];
+            image[(x, y + SIZE)] = color[(x, y)];
+            image[(x + SIZE, y + SIZE)] = dubois[(x, y)];
+        }
+    }
+
+    demo::save(&image, 33);
+}
@@ -0,0 +1,63 @@
+extern crate cgl;
+
+use std::fs::File;
+
+use cgl::{Color, Pixel, Renderer, Shader, Texture, Encoding, Vert, Mat4, Vec3, Vec4};
+use cgl::tessellation::{Displace, Distance, Phong};
+
+mod demo;
+
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 1024;
+
+fn main() {
+    let model = demo::diablo_model();
+    let mut renderer = Renderer::with_dimensions(WIDTH, HEIGHT);
+
+    let eye = Vec3(0.0, 0.1, 0.6);
+    let matrix = {
+        let viewport = Mat4::viewport(WIDTH as i32, HEIGHT as i32);
+        let perspective = Mat4::perspective(1.0);
+        let view = Mat4::lookat(Vec3(0.0, 0.0, 0.0), eye, Vec3(0.0, 1.0, 0.0));
+        viewport * perspective * view
+    };
+
+    let mut file = File::open("assets/diablo/diffuse.bmp")
+        .expect("Should open assets/diablo/diffuse.bmp");
+    let diffuse = Texture::read_bmp(&mut file, Encoding::Srgb)
+        .expect("Should read image");
+
+    // There's no height map for the model, so emboss it with its own colors
+    let height = diffuse.image.map(|c| f32::from_rgb(c.to_rgb()) - 0.5);
+    let tessellator = Displace::new(Phong::new(Distance {
+        eye,
+        near: 0.5,
+        far: 1.5,
+        max: 6,
+    }, 0.75), height, 0.01);
+
+    renderer.tessellated_model(&Diffuse, &tessellator, &(matrix, diffuse), &model);
+
+    demo::save(renderer.image(), 17);
+}
+
+struct Diffuse;
+
+impl Shader<Vert, (Mat4<f32>, Texture)> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, (mat, _): &(Mat4<f32>, Texture), pos: &mut Vec4<f32>)
+              -> Vert
+    {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, input: Vert, (_, diffuse): &(Mat4<f32>, Texture)) -> Color {
+        let c = input.norm.normalized()
+            .dot(Vec3(0.0f32, 1.0, 0.5).normalized())
+            .max(0.0);
+        let albedo = diffuse.sample_clamp(input.tex.0, input.tex.1);
+        Color::from_linear(albedo * (c * 0.9 + 0.1))
+    }
+}
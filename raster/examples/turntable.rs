@@ -0,0 +1,49 @@
+extern crate cgl;
+
+use cgl::{Camera, Color, Image, Renderer, Shader, Vert, Mat4, Vec3, Vec4};
+use cgl::controller::{Controller, Orbit, Turntable};
+
+mod demo;
+
+const SIZE: usize = 192;
+const FRAMES: usize = 6;
+
+/// A contact sheet of a turntable around the devilman, framed automatically
+/// from its bounding sphere
+fn main() {
+    let model = demo::diablo_model();
+    let camera = Camera::perspective(40f32.to_radians(), 1.0, 0.1, 100.0);
+    let orbit = Orbit { pitch: 15f32.to_radians(), ..Orbit::framing(&model, &camera) };
+    let turntable = Turntable::new(orbit, FRAMES as f32);
+
+    let mut image = Image::with_dimensions(SIZE * FRAMES, SIZE);
+    for frame in 0..FRAMES {
+        let camera = turntable.place(frame as f32, camera);
+        let matrix = Mat4::viewport(SIZE as i32, SIZE as i32) * camera.view_projection();
+        let mut renderer = Renderer::with_dimensions(SIZE, SIZE);
+        renderer.model(&Diffuse, &matrix, &model);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                image[(x + frame * SIZE, y)] = renderer.image()[(x, y)];
+            }
+        }
+    }
+
+    demo::save(&image, 23);
+}
+
+struct Diffuse;
+
+impl Shader<Vert, Mat4<f32>> for Diffuse {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>) -> Vert {
+        *pos = *mat * vert.pos.augment();
+        vert
+    }
+
+    fn fragment(&self, vert: Vert, _: &Mat4<f32>) -> Color {
+        let c = vert.norm.normalized().dot(Vec3(0.3f32, 1.0, 0.8).normalized()).max(0.1);
+        Color::float_rgb(c, c, c)
+    }
+}
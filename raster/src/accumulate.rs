@@ -0,0 +1,271 @@
+//! An accumulation buffer, for averaging many renders of the same scene.
+//!
+//! Rendering a scene several times with something changed slightly each time
+//! and averaging the results gives effects that a single pass can't, without
+//! touching any shaders:
+//!
+//! - Shifting the camera by a fraction of a pixel anti-aliases every edge.
+//! - Spreading the time of each render over a shutter interval blurs anything
+//!   that moves.
+//! - Moving the camera across an aperture while keeping one plane in focus
+//!   gives depth of field.
+//!
+//! An [`Accumulation`] picks the jitter for each of its samples, and calls back
+//! with a cleared `Renderer` and a [`Sample`] to draw the scene with. The
+//! results are averaged in floating point, in linear light.
+//!
+//! ```rust,no_run
+//! use cgl::{Camera, Color, Renderer};
+//! use cgl::accumulate::Accumulation;
+//!
+//! let camera = Camera::default();
+//! let accumulation = Accumulation::new(16).shutter(0.02).aperture(0.05, 4.0);
+//! let mut renderer: Renderer<Color> = Renderer::with_dimensions(512, 512);
+//! let image = accumulation.render(&mut renderer, |renderer, sample| {
+//!     let camera = sample.camera(camera);
+//!     // Draw the scene at `sample.time` with `camera.view_projection()`...
+//! });
+//! let image = image.map(Color::from_linear);
+//! ```
+//!
+//! [`Accumulation`]: struct.Accumulation.html
+//! [`Sample`]: struct.Sample.html
+
+use std::f32::consts::PI;
+
+use camera::Camera;
+use cgl_math::{Vec2, Vec3};
+use image::{Image, HdrColor, Pixel};
+use renderer::Renderer;
+
+/// A running sum of images, which can be averaged at any point
+pub struct Accumulator {
+    sum: Image<Vec3<f32>>,
+    count: usize,
+}
+
+impl Accumulator {
+    pub fn with_dimensions(width: usize, height: usize) -> Self {
+        Accumulator { sum: Image::with_dimensions(width, height), count: 0 }
+    }
+
+    pub fn width(&self) -> usize { self.sum.width }
+    pub fn height(&self) -> usize { self.sum.height }
+
+    /// How many images have been added
+    pub fn count(&self) -> usize { self.count }
+
+    /// Add another image to the sum, after converting it to linear RGB
+    ///
+    /// # Panics
+    ///
+    /// Panics if `image` isn't the same size as the accumulator.
+    pub fn add<P: Pixel>(&mut self, image: &Image<P>) {
+        assert!(image.width == self.width() && image.height == self.height(),
+                "Can't add a {}x{} image to a {}x{} accumulator",
+                image.width, image.height, self.width(), self.height());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                self.sum[(x, y)] = self.sum[(x, y)] + image[(x, y)].to_rgb();
+            }
+        }
+        self.count += 1;
+    }
+
+    /// The average of every image added so far, or black if there aren't any
+    pub fn average(&self) -> Image<HdrColor> {
+        let scale = 1.0 / self.count.max(1) as f32;
+        self.sum.map(|sum| HdrColor::from_rgb(sum * scale))
+    }
+
+    /// Forget every image that's been added
+    pub fn clear(&mut self) {
+        self.sum.fill(Vec3(0.0, 0.0, 0.0));
+        self.count = 0;
+    }
+}
+
+/// The changes to make to the scene for one render of an `Accumulation`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Which sample this is, from 0 up to the number of samples
+    pub index: usize,
+    /// How far into the shutter interval this sample is taken, in seconds.
+    /// Add it to the time of the frame to place anything that moves.
+    pub time: f32,
+    /// How far to shift the image, in pixels
+    pub pixel: Vec2<f32>,
+    /// Where on the aperture to look from, in world units from its center
+    pub lens: Vec2<f32>,
+    /// The distance to the plane that stays in focus
+    pub focus: f32,
+    width: usize,
+    height: usize,
+}
+
+impl Sample {
+    /// `camera`, shifted by the sample's fraction of a pixel and moved to its
+    /// point on the aperture
+    pub fn camera(&self, camera: Camera) -> Camera {
+        let camera = camera.offset_pixels(self.pixel.0, self.pixel.1, self.width, self.height);
+        if self.lens == Vec2(0.0, 0.0) {
+            camera
+        } else {
+            camera.offset_lens(self.lens, self.focus)
+        }
+    }
+}
+
+/// How to jitter the renders that are averaged together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accumulation {
+    /// How many times to render the scene
+    pub samples: usize,
+    /// Whether to shift each render by a different fraction of a pixel
+    pub antialias: bool,
+    /// How long the shutter is open for, in seconds. Samples are spread
+    /// evenly over this, so 0 means no motion blur.
+    pub shutter: f32,
+    /// The radius of the aperture, in world units. 0 means no depth of field.
+    pub aperture: f32,
+    /// The distance to the plane that stays in focus
+    pub focus: f32,
+}
+
+impl Accumulation {
+    /// Render `samples` times with anti-aliasing, but no motion blur or depth
+    /// of field
+    pub fn new(samples: usize) -> Self {
+        Accumulation { samples, antialias: true, shutter: 0.0, aperture: 0.0, focus: 1.0 }
+    }
+
+    pub fn antialias(self, antialias: bool) -> Self {
+        Accumulation { antialias, ..self }
+    }
+
+    pub fn shutter(self, shutter: f32) -> Self {
+        Accumulation { shutter, ..self }
+    }
+
+    /// Look through an aperture of `radius` world units, focused `focus` units
+    /// in front of the camera
+    pub fn aperture(self, radius: f32, focus: f32) -> Self {
+        Accumulation { aperture: radius, focus, ..self }
+    }
+
+    /// The jitter for sample `index` of a `width` by `height` render.
+    ///
+    /// The pixel offsets and lens positions come from a Halton sequence, which
+    /// covers the pixel and aperture evenly for any number of samples.
+    pub fn sample(&self, index: usize, width: usize, height: usize) -> Sample {
+        // The first point of the sequence is always 0, so skip it
+        let n = index + 1;
+        let pixel = if self.antialias {
+            Vec2(halton(n, 2) - 0.5, halton(n, 3) - 0.5)
+        } else {
+            Vec2(0.0, 0.0)
+        };
+        let lens = if self.aperture > 0.0 {
+            let r = self.aperture * halton(n, 5).sqrt();
+            let (sin, cos) = (2.0 * PI * halton(n, 7)).sin_cos();
+            Vec2(r * cos, r * sin)
+        } else {
+            Vec2(0.0, 0.0)
+        };
+        let time = self.shutter * (index as f32 + 0.5) / self.samples as f32;
+        Sample { index, time, pixel, lens, focus: self.focus, width, height }
+    }
+
+    /// Render the scene once for each sample with `draw`, and average the
+    /// results.
+    ///
+    /// The renderer is cleared before each sample. The average is in linear
+    /// light, so tone map it or convert it with `Color::from_linear` for
+    /// display.
+    pub fn render<P, D>(&self, renderer: &mut Renderer<P>, mut draw: D) -> Image<HdrColor>
+        where P: Pixel, D: FnMut(&mut Renderer<P>, &Sample)
+    {
+        let (width, height) = (renderer.width(), renderer.height());
+        let mut accumulator = Accumulator::with_dimensions(width, height);
+        for index in 0..self.samples {
+            renderer.clear();
+            draw(renderer, &self.sample(index, width, height));
+            accumulator.add(renderer.image());
+        }
+        accumulator.average()
+    }
+}
+
+/// The `i`th element of the Halton sequence in `base`, which is in [0, 1)
+fn halton(mut i: usize, base: usize) -> f32 {
+    let (mut result, mut scale) = (0.0, 1.0);
+    while i > 0 {
+        scale /= base as f32;
+        result += scale * (i % base) as f32;
+        i /= base;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{halton, Accumulation, Accumulator};
+    use cgl_math::{Vec2, Vec3};
+    use image::{Color, HdrColor, Image};
+    use renderer::Renderer;
+
+    #[test]
+    fn averages_in_linear_light() {
+        let mut accumulator = Accumulator::with_dimensions(2, 1);
+        assert_eq!(accumulator.average()[(0, 0)], HdrColor::black());
+        accumulator.add(&Image::filled(2, 1, Color::white()));
+        accumulator.add(&Image::filled(2, 1, Color::black()));
+        assert_eq!(accumulator.count(), 2);
+        // Half of white is only 50% in linear light, which is brighter than a
+        // gray of 128 would suggest
+        let average = accumulator.average()[(1, 0)];
+        assert!((average.r - 0.5).abs() < 1e-6, "{:?}", average);
+        assert!(Color::from_linear(average).r > 180);
+
+        accumulator.clear();
+        accumulator.add(&Image::filled(2, 1, HdrColor::gray(4.0)));
+        assert_eq!(accumulator.average()[(0, 0)], HdrColor::gray(4.0));
+    }
+
+    #[test]
+    fn samples_cover_pixel_shutter_and_aperture() {
+        assert_eq!((halton(1, 2), halton(2, 2), halton(3, 2)), (0.5, 0.25, 0.75));
+        assert!((halton(5, 3) - 7.0 / 9.0).abs() < 1e-6);
+
+        let accumulation = Accumulation::new(8).shutter(0.5).aperture(0.1, 3.0);
+        let mut center = Vec2(0.0, 0.0);
+        for i in 0..8 {
+            let sample = accumulation.sample(i, 64, 64);
+            assert!(sample.pixel.0.abs() <= 0.5 && sample.pixel.1.abs() <= 0.5);
+            assert!(sample.lens.0.hypot(sample.lens.1) <= 0.1);
+            assert!((sample.time - (i as f32 + 0.5) / 16.0).abs() < 1e-6);
+            center = center + sample.pixel;
+        }
+        assert!(center.0.abs() < 0.5 && center.1.abs() < 0.5, "{:?}", center);
+
+        let still = Accumulation::new(4).antialias(false).sample(3, 64, 64);
+        assert_eq!((still.pixel, still.lens, still.time), (Vec2(0.0, 0.0), Vec2(0.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn renders_every_sample() {
+        let mut renderer: Renderer<HdrColor> = Renderer::with_dimensions(4, 4);
+        let mut count = 0;
+        let image = Accumulation::new(4).render(&mut renderer, |renderer, sample| {
+            assert_eq!(renderer.image()[(0, 0)], HdrColor::black());
+            count += 1;
+            // Light the top row in every other sample
+            if sample.index % 2 == 0 {
+                renderer.line(Vec3(0, 0, 0), Vec3(3, 0, 0), HdrColor::gray(1.0));
+            }
+        });
+        assert_eq!(count, 4);
+        assert_eq!(image[(2, 0)], HdrColor::gray(0.5));
+        assert_eq!(image[(2, 2)], HdrColor::black());
+    }
+}
@@ -0,0 +1,172 @@
+//! Backgrounds, drawn behind everything else wherever the scene doesn't cover
+//! the image.
+//!
+//! A [`Background`] is drawn with `Renderer::background`, which only fills the
+//! pixels that are still at the far depth. It can be drawn before or after the
+//! scene, since it leaves the depth buffer alone either way and so never
+//! hides any geometry.
+//!
+//! [`Background`]: enum.Background.html
+
+use std::ops::{Add, Mul};
+
+use camera::Camera;
+use cgl_math::{Vec3, Vec4};
+use cubemap::{CubeMap, sample_equirectangular};
+use image::Image;
+
+/// What to show behind the scene
+#[derive(Clone)]
+pub enum Background<P> {
+    /// The same color everywhere
+    Solid(P),
+    /// A gradient from the top of the image to the bottom, which stays put as
+    /// the camera moves
+    Gradient { top: P, bottom: P },
+    /// An equirectangular panorama, seen through the camera, with its middle
+    /// in the -z direction
+    Equirectangular(Image<P>),
+    /// An environment cube map, seen through the camera
+    CubeMap(CubeMap<P>),
+}
+
+impl<P> Background<P> where P: Mul<f32, Output=P> + Add<Output=P> + Copy + Default {
+    /// The background at the pixel `(x, y)` of a `width` by `height` image, as
+    /// seen through `camera`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the camera's transformation isn't invertible.
+    pub fn sample(&self, camera: &Camera, x: usize, y: usize, width: usize, height: usize) -> P {
+        self.sampler(camera, width, height)(x, y)
+    }
+
+    /// Fill every pixel of `color` that's still at the far depth in `depth`
+    /// with the background, as seen through `camera`. Both images should be
+    /// the same size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the camera's transformation isn't invertible.
+    pub fn draw(&self, camera: &Camera, color: &mut Image<P>, depth: &Image<f32>) {
+        let (width, height) = (color.width, color.height);
+        let sample = self.sampler(camera, width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if depth[(x, y)] == f32::MIN {
+                    color[(x, y)] = sample(x, y);
+                }
+            }
+        }
+    }
+
+    /// A function giving the background at each pixel of a `width` by `height`
+    /// image, which only has to invert the camera's transformation once
+    fn sampler<'a>(&'a self, camera: &Camera, width: usize, height: usize)
+                   -> Box<dyn Fn(usize, usize) -> P + 'a>
+    {
+        match *self {
+            Background::Solid(color) => Box::new(move |_, _| color),
+            Background::Gradient { top, bottom } => Box::new(move |_, y| {
+                let t = (y as f32 + 0.5) / height as f32;
+                top * (1.0 - t) + bottom * t
+            }),
+            Background::Equirectangular(ref image) => {
+                let direction = directions(camera, width, height);
+                Box::new(move |x, y| sample_equirectangular(image, direction(x, y)))
+            }
+            Background::CubeMap(ref cube) => {
+                let direction = directions(camera, width, height);
+                Box::new(move |x, y| cube.sample(direction(x, y)))
+            }
+        }
+    }
+}
+
+/// A function giving the world-space direction the camera looks in through
+/// the middle of each pixel, the same as `Camera::ray`
+fn directions(camera: &Camera, width: usize, height: usize) -> impl Fn(usize, usize) -> Vec3<f32> {
+    let inverse = camera.view_projection().inverted()
+        .expect("The camera's transformation should be invertible");
+    move |x, y| {
+        let ndc_x = 2.0 * (x as f32 + 0.5) / width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * (y as f32 + 0.5) / height as f32;
+        let near = (inverse * Vec4(ndc_x, ndc_y, 1.0, 1.0)).retro_project();
+        let far = (inverse * Vec4(ndc_x, ndc_y, -1.0, 1.0)).retro_project();
+        far - near
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Background;
+    use camera::Camera;
+    use cgl_math::Vec3;
+    use cubemap::{CubeMap, Face};
+    use image::{Color, Image};
+    use renderer::Renderer;
+
+    fn camera() -> Camera {
+        Camera::perspective(90f32.to_radians(), 1.0, 0.1, 10.0)
+    }
+
+    #[test]
+    fn fills_behind_geometry() {
+        let red = Background::Solid(Color::red());
+        let triangle = |renderer: &mut Renderer| {
+            renderer.triangle(Vec3(0, 0, 1), Vec3(7, 0, 1), Vec3(0, 7, 1), Color::white());
+        };
+
+        // Before or after drawing, the background ends up in the same place
+        let mut after = Renderer::with_dimensions(8, 8);
+        triangle(&mut after);
+        after.background(&red, &camera());
+        let mut before = Renderer::with_dimensions(8, 8);
+        before.background(&red, &camera());
+        triangle(&mut before);
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(after.image()[(x, y)], before.image()[(x, y)]);
+                assert_eq!(after.depth()[(x, y)], before.depth()[(x, y)]);
+            }
+        }
+
+        assert_eq!(after.image()[(1, 1)], Color::white());
+        assert_eq!(after.image()[(7, 7)], Color::red());
+        assert_eq!(after.depth()[(7, 7)], f32::MIN);
+    }
+
+    #[test]
+    fn gradient_goes_down_the_screen() {
+        let gradient = Background::Gradient { top: 1.0f32, bottom: 0.0 };
+        let mut renderer: Renderer<f32> = Renderer::with_dimensions(4, 4);
+        renderer.background(&gradient, &camera());
+        let image = renderer.image();
+        assert_eq!(image[(0, 0)], image[(3, 0)]);
+        assert!(image[(0, 0)] > image[(0, 1)] && image[(0, 2)] > image[(0, 3)]);
+        assert!((image[(0, 0)] + image[(0, 3)] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn environments_follow_the_camera() {
+        let cube = CubeMap::from_fn(4, |dir| Face::locate(dir).0 as usize as f32);
+        let background = Background::CubeMap(cube);
+        let ahead = background.sample(&camera(), 2, 2, 5, 5);
+        assert_eq!(ahead, Face::NegativeZ as usize as f32);
+        let up = camera().look_at(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0));
+        assert_eq!(background.sample(&up, 2, 2, 5, 5), Face::PositiveY as usize as f32);
+
+        // The middle of a panorama is straight ahead, and its top is up
+        let mut panorama = Image::with_dimensions(8, 4);
+        panorama[(4, 2)] = 1.0f32;
+        panorama[(3, 2)] = 1.0f32;
+        panorama[(3, 1)] = 1.0f32;
+        panorama[(4, 1)] = 1.0f32;
+        for x in 0..8 {
+            panorama[(x, 0)] = 2.0;
+        }
+        let background = Background::Equirectangular(panorama);
+        assert_eq!(background.sample(&camera(), 2, 2, 5, 5), 1.0);
+        assert_eq!(background.sample(&up, 2, 2, 5, 5), 2.0);
+    }
+}
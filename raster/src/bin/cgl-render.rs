@@ -0,0 +1,445 @@
+//! Renders an OBJ model to a BMP file from the command line.
+//!
+//! Run `cgl-render --help` for the options.
+
+extern crate cgl;
+
+use std::env;
+use std::fs::File;
+use std::process;
+
+use cgl::{Camera, Color, DirectionalLight, HdrColor, Image, Obj, Primitive, Renderer,
+          SceneUniform, Shader, TanVert, Texture, Vert, Encoding, Mat4, Vec3, Vec4, write_bmp};
+use cgl::controller::Orbit;
+use cgl::model::compute_tangent_space;
+use cgl::phong::{BlinnPhong, Gouraud, Material, NormalMapped, Textured};
+
+const USAGE: &str = "\
+Usage: cgl-render MODEL [OPTIONS]
+
+Renders the OBJ file MODEL to a BMP.
+
+Options:
+  -o, --output PATH    Where to write the BMP [default: render.bmp]
+  --diffuse PATH       A diffuse texture, as a BMP
+  --normal PATH        A tangent space normal map, as a BMP
+  --specular PATH      A specular map, as a BMP
+  --eye X,Y,Z          The camera position [default: framing the whole model]
+  --target X,Y,Z       Where the camera looks [default: the model's center]
+  --fov DEGREES        The vertical field of view [default: 45]
+  --size WIDTHxHEIGHT  The size of the image [default: 512x512]
+  --shading MODE       One of flat, gouraud, textured, normal-mapped,
+                       wireframe, depth or normals [default: normal-mapped with
+                       a normal map, textured with a diffuse texture, and
+                       gouraud otherwise]
+  -h, --help           Show this message";
+
+/// How the model is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shading {
+    /// Lit with one normal per face
+    Flat,
+    /// Lit at the vertices, with the colors blended in between
+    Gouraud,
+    /// Lit at each pixel, with the diffuse and specular textures
+    Textured,
+    /// Lit at each pixel, with the diffuse, normal and specular textures
+    NormalMapped,
+    /// The edges of every triangle
+    Wireframe,
+    /// The depth buffer, with nearer pixels brighter
+    Depth,
+    /// The world space normals, as colors
+    Normals,
+}
+
+impl Shading {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "flat" => Shading::Flat,
+            "gouraud" => Shading::Gouraud,
+            "textured" => Shading::Textured,
+            "normal-mapped" => Shading::NormalMapped,
+            "wireframe" => Shading::Wireframe,
+            "depth" => Shading::Depth,
+            "normals" => Shading::Normals,
+            _ => return Err(format!("Unknown shading mode {:?}", name)),
+        })
+    }
+}
+
+/// Everything given on the command line
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    model: String,
+    output: String,
+    diffuse: Option<String>,
+    normal: Option<String>,
+    specular: Option<String>,
+    eye: Option<Vec3<f32>>,
+    target: Option<Vec3<f32>>,
+    fov: f32,
+    width: usize,
+    height: usize,
+    shading: Option<Shading>,
+}
+
+impl Options {
+    /// Parse the arguments after the program name. Returns `Ok(None)` if the
+    /// usage was asked for.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Self>, String> {
+        let mut args = args.into_iter();
+        let mut model = None;
+        let mut options = Options {
+            model: String::new(),
+            output: "render.bmp".to_owned(),
+            diffuse: None,
+            normal: None,
+            specular: None,
+            eye: None,
+            target: None,
+            fov: 45.0,
+            width: 512,
+            height: 512,
+            shading: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match &arg[..] {
+                "-h" | "--help" => return Ok(None),
+                "-o" | "--output" => options.output = value()?,
+                "--diffuse" => options.diffuse = Some(value()?),
+                "--normal" => options.normal = Some(value()?),
+                "--specular" => options.specular = Some(value()?),
+                "--eye" => options.eye = Some(parse_vec3(&value()?)?),
+                "--target" => options.target = Some(parse_vec3(&value()?)?),
+                "--fov" => {
+                    let fov = value()?;
+                    options.fov = match fov.parse() {
+                        Ok(degrees) if degrees > 0.0 && degrees < 180.0 => degrees,
+                        _ => return Err(format!("Invalid FOV {:?}, expected 0 to 180 degrees",
+                                                fov)),
+                    };
+                }
+                "--size" => {
+                    let (width, height) = parse_size(&value()?)?;
+                    options.width = width;
+                    options.height = height;
+                }
+                "--shading" => options.shading = Some(Shading::parse(&value()?)?),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+                _ if model.is_none() => model = Some(arg),
+                _ => return Err(format!("Unexpected argument {:?}", arg)),
+            }
+        }
+        options.model = model.ok_or("No model was given")?;
+        Ok(Some(options))
+    }
+
+    /// The shading mode that was asked for, or the best one for the textures
+    /// that were given
+    fn shading(&self) -> Shading {
+        match (self.shading, &self.diffuse, &self.normal) {
+            (Some(shading), _, _) => shading,
+            (None, _, &Some(_)) => Shading::NormalMapped,
+            (None, &Some(_), _) => Shading::Textured,
+            (None, _, _) => Shading::Gouraud,
+        }
+    }
+}
+
+/// Parse a vector written as `x,y,z`
+fn parse_vec3(text: &str) -> Result<Vec3<f32>, String> {
+    let error = || format!("Invalid vector {:?}, expected X,Y,Z", text);
+    let parts = text.split(',').map(|part| part.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| error())?;
+    match parts[..] {
+        [x, y, z] if x.is_finite() && y.is_finite() && z.is_finite() => Ok(Vec3(x, y, z)),
+        _ => Err(error()),
+    }
+}
+
+/// Parse a size written as `widthxheight`
+fn parse_size(text: &str) -> Result<(usize, usize), String> {
+    let error = || format!("Invalid size {:?}, expected WIDTHxHEIGHT", text);
+    let mut parts = text.splitn(2, 'x').map(|part| part.parse::<usize>());
+    match (parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+fn load_texture(path: &str, encoding: Encoding) -> Result<Texture, String> {
+    File::open(path)
+        .and_then(|mut file| Texture::read_bmp(&mut file, encoding))
+        .map_err(|err| format!("Couldn't read {}: {}", path, err))
+}
+
+/// A texture that's the same everywhere, for the maps that weren't given
+fn constant_texture(color: Color) -> Texture {
+    Texture::linear(Image::filled(1, 1, color))
+}
+
+/// `camera` looking from `eye` at `target`, with its near and far planes
+/// fitted around a model of `radius`
+fn place_camera(camera: Camera, eye: Vec3<f32>, target: Vec3<f32>, radius: f32)
+                -> Result<Camera, String>
+{
+    if radius <= 0.0 {
+        return Err("The model has no size, so there's nothing to frame".to_owned());
+    }
+    let near = radius * 1e-3;
+    let distance = (eye - target).len() as f32;
+    if distance <= near {
+        return Err(format!("The eye {:?} is too close to the target {:?} to look at it",
+                           eye, target));
+    }
+    // Looking straight up or down, +y has no sideways part to build the rest
+    // of the camera's axes from, so keep +z at the top of the image instead
+    let up = if ((target - eye) / distance).1.abs() > 0.999 {
+        Vec3(0.0, 0.0, 1.0)
+    } else {
+        Vec3(0.0, 1.0, 0.0)
+    };
+    Ok(Camera { near, far: distance + radius * 4.0, ..camera }.look_at(eye, target, up))
+}
+
+fn render(options: &Options) -> Result<Image<Color>, String> {
+    let obj = Obj::from_file(&options.model)
+        .map_err(|err| format!("Couldn't read {}: {}", options.model, err))?;
+    let model = obj.model()
+        .map_err(|err| format!("Couldn't use {}: {}", options.model, err))?;
+    let model = compute_tangent_space(model);
+
+    let (width, height) = (options.width, options.height);
+    let camera = Camera::perspective(options.fov.to_radians(), width as f32 / height as f32,
+                                     0.01, 1000.0);
+    let (center, radius) = model.bounding_sphere();
+    let target = options.target.unwrap_or(center);
+    let eye = options.eye.unwrap_or_else(|| {
+        let orbit = Orbit { target, pitch: 15f32.to_radians(), ..Orbit::framing(&model, &camera) };
+        orbit.eye()
+    });
+    let camera = place_camera(camera, eye, target, radius)?;
+
+    // A light from above the camera and off to its left
+    let axis = |x, y, z| {
+        let axis = camera.transform * Vec4(x, y, z, 0.0);
+        Vec3(axis.0, axis.1, axis.2)
+    };
+    let direction = axis(0.0, 0.0, -1.0) + axis(1.0, 0.0, 0.0) * 0.6 - axis(0.0, 1.0, 0.0) * 0.6;
+    let light = DirectionalLight::new(direction, HdrColor::gray(1.0), 1.0);
+    let uniform = SceneUniform::new(Mat4::identity(), camera.view(),
+                                    Mat4::viewport(width as i32, height as i32) *
+                                    camera.projection(),
+                                    vec![light.into()]);
+    let material = Material {
+        ambient: HdrColor::gray(0.15),
+        ..Material::new(HdrColor::gray(0.5), HdrColor::gray(0.2), 32.0)
+    };
+
+    let mut renderer = Renderer::with_dimensions(width, height);
+    match options.shading() {
+        Shading::Flat => renderer.model(&Flat(material), &uniform, &model),
+        Shading::Gouraud => renderer.model(&Gouraud(material), &uniform, &model),
+        Shading::Textured | Shading::NormalMapped => {
+            let white = Material { diffuse: HdrColor::gray(1.0), ..material };
+            let diffuse = match options.diffuse {
+                Some(ref path) => load_texture(path, Encoding::Srgb)?,
+                None => constant_texture(Color::white()),
+            };
+            let specular = match options.specular {
+                Some(ref path) => Some(load_texture(path, Encoding::Linear)?),
+                None => None,
+            };
+            if options.shading() == Shading::Textured {
+                let shader = Textured { specular, ..Textured::new(white, diffuse) };
+                renderer.model(&shader, &uniform, &model);
+            } else {
+                let normal = match options.normal {
+                    Some(ref path) => load_texture(path, Encoding::Linear)?,
+                    None => return Err("normal-mapped shading needs --normal".to_owned()),
+                };
+                let shader = NormalMapped { specular, ..NormalMapped::new(white, diffuse, normal) };
+                renderer.model(&shader, &uniform, &model);
+            }
+        }
+        Shading::Wireframe => renderer.model(&Wireframe, &uniform, &model),
+        Shading::Depth => {
+            renderer.model(&Flat(material), &uniform, &model);
+            return Ok(depth_image(renderer.depth()));
+        }
+        Shading::Normals => renderer.model(&Normals, &uniform, &model),
+    }
+    Ok(renderer.image().clone())
+}
+
+/// The depth buffer in grayscale, stretched so the nearest pixel is white and
+/// the furthest is dark gray. Pixels that weren't drawn are black.
+fn depth_image(depth: &Image<f32>) -> Image<Color> {
+    let drawn = |z: f32| z > f32::MIN;
+    let mut range = (f32::MAX, f32::MIN);
+    for y in 0..depth.height {
+        for x in 0..depth.width {
+            let z = depth[(x, y)];
+            if drawn(z) {
+                range = (range.0.min(z), range.1.max(z));
+            }
+        }
+    }
+    let scale = 1.0 / (range.1 - range.0).max(1e-6);
+    depth.map(|z| if drawn(z) {
+        let c = 0.2 + 0.8 * (z - range.0) * scale;
+        Color::float_rgb(c, c, c)
+    } else {
+        Color::black()
+    })
+}
+
+/// Blinn-Phong lighting with every pixel of a triangle facing the same way
+struct Flat(Material);
+
+impl Shader<TanVert, SceneUniform> for Flat {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: TanVert, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vert {
+        Shader::<TanVert, SceneUniform, Color>::vertex(&BlinnPhong(self.0), vert, uniform, pos)
+    }
+
+    fn geometry(&self, [(p0, v0), (p1, v1), (p2, v2)]: [(Vec4<f32>, Vert); 3],
+                _: &SceneUniform, emit: &mut dyn FnMut(Primitive<Vert>))
+    {
+        let norm = (v1.pos - v0.pos).cross(v2.pos - v0.pos).normalized();
+        let face = |vert| Vert { norm, ..vert };
+        emit(Primitive::Triangle([(p0, face(v0)), (p1, face(v1)), (p2, face(v2))]));
+    }
+
+    fn fragment(&self, vert: Vert, uniform: &SceneUniform) -> Color {
+        Shader::<TanVert, SceneUniform, Color>::fragment(&BlinnPhong(self.0), vert, uniform)
+    }
+}
+
+/// White lines along the edges of each triangle
+struct Wireframe;
+
+impl Shader<TanVert, SceneUniform> for Wireframe {
+    type VOut = Vec3<f32>;
+
+    fn vertex(&self, vert: TanVert, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vec3<f32> {
+        *pos = uniform.project(vert.pos);
+        vert.pos
+    }
+
+    fn geometry(&self, [a, b, c]: [(Vec4<f32>, Vec3<f32>); 3], _: &SceneUniform,
+                emit: &mut dyn FnMut(Primitive<Vec3<f32>>))
+    {
+        emit(Primitive::Line([a, b]));
+        emit(Primitive::Line([b, c]));
+        emit(Primitive::Line([c, a]));
+    }
+
+    fn fragment(&self, _: Vec3<f32>, _: &SceneUniform) -> Color {
+        Color::white()
+    }
+}
+
+/// The world space normal, with each axis mapped from [-1, 1] to [0, 1]
+struct Normals;
+
+impl Shader<TanVert, SceneUniform> for Normals {
+    type VOut = Vec3<f32>;
+
+    fn vertex(&self, vert: TanVert, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vec3<f32> {
+        *pos = uniform.project(vert.pos);
+        uniform.world_normal(vert.norm)
+    }
+
+    fn fragment(&self, norm: Vec3<f32>, _: &SceneUniform) -> Color {
+        let n = norm.normalized() * 0.5 + Vec3(0.5, 0.5, 0.5);
+        Color::float_rgb(n.0, n.1, n.2)
+    }
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = render(&options).and_then(|image| {
+        File::create(&options.output)
+            .and_then(|mut file| write_bmp(&image, &mut file))
+            .map_err(|err| format!("Couldn't write {}: {}", options.output, err))
+    });
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, Shading, place_camera};
+    use cgl::{Camera, Vec3};
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        Options::parse(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse(&["head.obj", "--eye", "1,2.5,-3", "--size", "320x200",
+                              "--fov", "60", "--shading", "wireframe", "-o", "out.bmp"])
+            .unwrap().unwrap();
+        assert_eq!(options.model, "head.obj");
+        assert_eq!(options.output, "out.bmp");
+        assert_eq!(options.eye, Some(Vec3(1.0, 2.5, -3.0)));
+        assert_eq!((options.width, options.height, options.fov), (320, 200, 60.0));
+        assert_eq!(options.shading(), Shading::Wireframe);
+
+        assert_eq!(parse(&["--help"]), Ok(None));
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["head.obj", "--size", "320"]).is_err());
+        assert!(parse(&["head.obj", "--eye", "1,2"]).is_err());
+        assert!(parse(&["head.obj", "--target", "1,NaN,2"]).is_err());
+        assert!(parse(&["head.obj", "--shading", "toon"]).is_err());
+        assert!(parse(&["head.obj", "--fov"]).is_err());
+        for fov in &["0", "-30", "180", "270", "NaN"] {
+            assert!(parse(&["head.obj", "--fov", fov]).is_err(), "{}", fov);
+        }
+
+        // Eyes on the target, and models with no size, can't be looked at
+        let (eye, target) = (Vec3(1.0, 2.0, 3.0), Vec3(0.0, 0.0, 0.0));
+        assert!(place_camera(Camera::default(), eye, target, 1.0).is_ok());
+        assert!(place_camera(Camera::default(), target, target, 1.0).is_err());
+        assert!(place_camera(Camera::default(), Vec3(1e-5, 0.0, 0.0), target, 1.0).is_err());
+        assert!(place_camera(Camera::default(), eye, target, 0.0).is_err());
+
+        // Straight above or below the target, the camera can't keep +y up
+        let target = Vec3(0.3, 0.3, 0.3);
+        for &eye in &[Vec3(0.3, 5.0, 0.3), Vec3(0.3, -5.0, 0.3)] {
+            let camera = place_camera(Camera::default(), eye, target, 1.0).unwrap();
+            let view = camera.view() * target.augment();
+            assert!((view.0.abs() + view.1.abs()) < 1e-5, "{:?}", view);
+            assert!((view.2 + (eye - target).len() as f32).abs() < 1e-5, "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn picks_shading_from_textures() {
+        let options = parse(&["head.obj"]).unwrap().unwrap();
+        assert_eq!(options.shading(), Shading::Gouraud);
+        let options = parse(&["head.obj", "--diffuse", "d.bmp"]).unwrap().unwrap();
+        assert_eq!(options.shading(), Shading::Textured);
+        let options = parse(&["head.obj", "--diffuse", "d.bmp", "--normal", "n.bmp"])
+            .unwrap().unwrap();
+        assert_eq!(options.shading(), Shading::NormalMapped);
+    }
+}
@@ -0,0 +1,402 @@
+//! Cameras, which describe how a scene is projected onto the screen.
+//!
+//! A [`Camera`] combines a [`Lens`], which decides the shape of the volume the
+//! camera can see, with a transformation placing the camera in the world.
+//! From those it can build the view and projection matrices for the vertex
+//! stage, turn a pixel back into a [`Ray`] through the world, and give the
+//! [`Frustum`] it sees for culling.
+//!
+//! [`Camera`]: struct.Camera.html
+//! [`Lens`]: enum.Lens.html
+//! [`Ray`]: struct.Ray.html
+//! [`Frustum`]: struct.Frustum.html
+
+use cgl_math::{Vec2, Vec3, Vec4, Mat4};
+
+/// The shape of the volume a camera can see
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lens {
+    /// Things further away look smaller. `fov` is the vertical field of view,
+    /// in radians.
+    Perspective { fov: f32 },
+    /// Things stay the same size however far away they are. `height` is the
+    /// height of the visible area, in world units.
+    Orthographic { height: f32 },
+    /// A perspective projection that isn't necessarily centered on the view
+    /// direction, given by the edges of the visible area on the near plane.
+    /// Useful for stereo pairs and for splitting an image into tiles.
+    Frustum { left: f32, right: f32, bottom: f32, top: f32 },
+}
+
+/// A camera, which looks down the -z axis of its own space with +y pointing
+/// up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub lens: Lens,
+    /// The width of the image divided by its height. `Lens::Frustum` ignores
+    /// this, since its edges are given directly.
+    pub aspect: f32,
+    /// The distance to the near clipping plane
+    pub near: f32,
+    /// The distance to the far clipping plane
+    pub far: f32,
+    /// The transformation from the camera's space to world space
+    pub transform: Mat4<f32>,
+}
+
+impl Camera {
+    pub fn new(lens: Lens, aspect: f32, near: f32, far: f32) -> Self {
+        Camera { lens, aspect, near, far, transform: Mat4::identity() }
+    }
+
+    pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Camera::new(Lens::Perspective { fov }, aspect, near, far)
+    }
+
+    pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Camera::new(Lens::Orthographic { height }, aspect, near, far)
+    }
+
+    /// An off-axis perspective camera, seeing from `left` to `right` and
+    /// `bottom` to `top` on its near plane
+    pub fn off_axis(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32)
+                    -> Self
+    {
+        let aspect = (right - left) / (top - bottom);
+        Camera::new(Lens::Frustum { left, right, bottom, top }, aspect, near, far)
+    }
+
+    /// Place the camera at `eye`, looking towards `target`, with `up` pointing
+    /// roughly towards the top of the image.
+    pub fn look_at(self, eye: Vec3<f32>, target: Vec3<f32>, up: Vec3<f32>) -> Self {
+        let z = (eye - target).normalized();
+        let x = up.cross(z).normalized();
+        let y = z.cross(x);
+        let transform = Mat4::new([[x.0, y.0, z.0, eye.0],
+                                   [x.1, y.1, z.1, eye.1],
+                                   [x.2, y.2, z.2, eye.2],
+                                   [0.0, 0.0, 0.0, 1.0]]);
+        Camera { transform, ..self }
+    }
+
+    /// The world-space position of the camera
+    pub fn position(&self) -> Vec3<f32> {
+        (self.transform * Vec3(0.0f32, 0.0, 0.0).augment()).retro_project()
+    }
+
+    /// The matrix taking points from world space to the camera's space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the camera's transformation isn't invertible.
+    pub fn view(&self) -> Mat4<f32> {
+        self.transform.inverted()
+            .expect("The camera's transformation should be invertible")
+    }
+
+    /// The matrix taking points from the camera's space to clip space.
+    ///
+    /// Clip space goes from -1 to 1 in every direction. Unlike OpenGL, points
+    /// on the near plane end up with a depth of 1 and points on the far plane
+    /// with -1, since the renderer keeps the fragments with the largest depth.
+    pub fn projection(&self) -> Mat4<f32> {
+        let (n, f) = (self.near, self.far);
+        match self.lens {
+            Lens::Perspective { .. } | Lens::Frustum { .. } => {
+                let (left, right, bottom, top) = self.near_rect()
+                    .expect("Perspective lenses should have a near rectangle");
+                off_axis(left, right, bottom, top, n, f)
+            }
+            Lens::Orthographic { height } => {
+                let (h, w) = (height, height * self.aspect);
+                Mat4::new([[2.0 / w, 0.0,     0.0,             0.0],
+                           [0.0,     2.0 / h, 0.0,             0.0],
+                           [0.0,     0.0,     2.0 / (f - n),   (f + n) / (f - n)],
+                           [0.0,     0.0,     0.0,             1.0]])
+            }
+        }
+    }
+
+    /// The edges of the visible area on the near plane, as `(left, right,
+    /// bottom, top)`, or `None` for an orthographic lens
+    fn near_rect(&self) -> Option<(f32, f32, f32, f32)> {
+        match self.lens {
+            Lens::Perspective { fov } => {
+                let top = self.near * (fov / 2.0).tan();
+                let right = top * self.aspect;
+                Some((-right, right, -top, top))
+            }
+            Lens::Frustum { left, right, bottom, top } => Some((left, right, bottom, top)),
+            Lens::Orthographic { .. } => None,
+        }
+    }
+
+    /// The camera with its image shifted by `(dx, dy)` pixels of a `width` by
+    /// `height` image, so that what used to be seen at `(x + dx, y + dy)` is
+    /// seen at `(x, y)`. Pixels are numbered as by `Mat4::viewport`.
+    ///
+    /// Averaging renders shifted by different fractions of a pixel
+    /// anti-aliases the result. A perspective camera becomes `Lens::Frustum`.
+    pub fn offset_pixels(self, dx: f32, dy: f32, width: usize, height: usize) -> Self {
+        match self.near_rect() {
+            Some((left, right, bottom, top)) => {
+                let x = dx * (right - left) / width as f32;
+                let y = dy * (top - bottom) / height as f32;
+                let lens = Lens::Frustum {
+                    left: left + x, right: right + x, bottom: bottom - y, top: top - y,
+                };
+                Camera { lens, ..self }
+            }
+            None => {
+                // An orthographic camera sees the same thing wherever it is, so
+                // it can just slide sideways
+                let h = match self.lens { Lens::Orthographic { height } => height, _ => 0.0 };
+                let x = dx * h * self.aspect / width as f32;
+                let y = dy * h / height as f32;
+                let transform = self.transform * Mat4::translate(Vec3(x, -y, 0.0));
+                Camera { transform, ..self }
+            }
+        }
+    }
+
+    /// The camera seeing through a different point of a lens, moved by
+    /// `offset` across its own x and y axes but still seeing the plane
+    /// `focus` units in front of it exactly the same way.
+    ///
+    /// Averaging renders from points spread over a disk blurs everything
+    /// that's off the focal plane, giving depth of field. Orthographic cameras
+    /// have no depth of field, so they're returned unchanged. A perspective
+    /// camera becomes `Lens::Frustum`.
+    pub fn offset_lens(self, offset: Vec2<f32>, focus: f32) -> Self {
+        match self.near_rect() {
+            Some((left, right, bottom, top)) => {
+                // Shear the frustum back so the focal plane stays put
+                let (x, y) = (offset.0 * self.near / focus, offset.1 * self.near / focus);
+                let lens = Lens::Frustum {
+                    left: left - x, right: right - x, bottom: bottom - y, top: top - y,
+                };
+                let transform = self.transform * Mat4::translate(Vec3(offset.0, offset.1, 0.0));
+                Camera { lens, transform, ..self }
+            }
+            None => self,
+        }
+    }
+
+    /// How far from the center of a sphere of `radius` the camera has to be
+    /// for all of the sphere to fit in its view.
+    ///
+    /// An orthographic camera sees the same amount from any distance, so this
+    /// only keeps the sphere in front of its near plane. Its `height` has to be
+    /// at least the sphere's diameter for the sphere to fit.
+    pub fn framing_distance(&self, radius: f32) -> f32 {
+        let half_height = match self.lens {
+            Lens::Perspective { fov } => (fov / 2.0).tan(),
+            Lens::Frustum { bottom, top, .. } => (top - bottom) / 2.0 / self.near,
+            Lens::Orthographic { .. } => return 2.0 * radius + self.near,
+        };
+        // The narrower of the two directions decides how far back to go
+        let half_angle = half_height.min(half_height * self.aspect).atan();
+        radius / half_angle.sin()
+    }
+
+    /// `projection * view`, taking points from world space to clip space
+    pub fn view_projection(&self) -> Mat4<f32> {
+        self.projection() * self.view()
+    }
+
+    /// The ray through the pixel at `(x, y)` of a `width` by `height` image,
+    /// starting on the near plane. Pixels are numbered the same way as by
+    /// `Mat4::viewport`, so use `x + 0.5` for the middle of a pixel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the camera's transformation isn't invertible.
+    pub fn ray(&self, x: f32, y: f32, width: usize, height: usize) -> Ray {
+        let ndc_x = 2.0 * x / width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height as f32;
+        let inverse = self.view_projection().inverted()
+            .expect("The camera's transformation should be invertible");
+        let near = (inverse * Vec4(ndc_x, ndc_y, 1.0, 1.0)).retro_project();
+        let far = (inverse * Vec4(ndc_x, ndc_y, -1.0, 1.0)).retro_project();
+        Ray { origin: near, direction: (far - near).normalized() }
+    }
+
+    /// The volume of world space the camera can see
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection())
+    }
+}
+
+impl Default for Camera {
+    /// A square perspective camera with a 60° field of view
+    fn default() -> Self {
+        Camera::perspective(60f32.to_radians(), 1.0, 0.1, 100.0)
+    }
+}
+
+/// The off-axis perspective projection through the rectangle from `(l, b)` to
+/// `(r, t)` on the near plane
+fn off_axis(l: f32, r: f32, b: f32, t: f32, n: f32, f: f32) -> Mat4<f32> {
+    Mat4::new([[2.0 * n / (r - l), 0.0,               (r + l) / (r - l),  0.0],
+               [0.0,               2.0 * n / (t - b), (t + b) / (t - b),  0.0],
+               [0.0,               0.0,               (f + n) / (f - n),  2.0 * f * n / (f - n)],
+               [0.0,               0.0,              -1.0,                0.0]])
+}
+
+/// A half-line through world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3<f32>,
+    /// The direction of the ray, which is normalized
+    pub direction: Vec3<f32>,
+}
+
+impl Ray {
+    /// The point `t` units along the ray
+    pub fn at(&self, t: f32) -> Vec3<f32> {
+        self.origin + self.direction * t
+    }
+}
+
+/// A convex volume bounded by six planes, used to skip things a camera can't
+/// see.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes. Each is `(a, b, c,
+    /// d)` with `(a, b, c)` a normal pointing into the frustum, so a point `p`
+    /// is on the inside when `a*p.0 + b*p.1 + c*p.2 + d` is positive.
+    pub planes: [Vec4<f32>; 6],
+}
+
+impl Frustum {
+    /// The volume that `matrix` takes into clip space
+    pub fn from_matrix(matrix: &Mat4<f32>) -> Self {
+        let w = matrix.row(3);
+        let (x, y, z) = (matrix.row(0), matrix.row(1), matrix.row(2));
+        let normalize = |plane: Vec4<f32>| {
+            plane / (Vec3(plane.0, plane.1, plane.2).len() as f32)
+        };
+        Frustum {
+            planes: [normalize(w + x), normalize(w - x),
+                     normalize(w + y), normalize(w - y),
+                     normalize(w - z), normalize(w + z)],
+        }
+    }
+
+    /// The signed distance from `plane` to `point`, positive on the inside
+    fn distance(plane: Vec4<f32>, point: Vec3<f32>) -> f32 {
+        plane.dot(point.augment())
+    }
+
+    pub fn contains_point(&self, point: Vec3<f32>) -> bool {
+        self.planes.iter().all(|&plane| Frustum::distance(plane, point) >= 0.0)
+    }
+
+    /// Whether any of the sphere might be inside the frustum. This is
+    /// conservative, so a sphere near a corner can be reported as visible
+    /// even though it's just outside.
+    pub fn intersects_sphere(&self, center: Vec3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|&plane| Frustum::distance(plane, center) >= -radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Camera;
+    use cgl_math::{Vec2, Vec3, Mat4};
+
+    fn close(a: Vec3<f32>, b: Vec3<f32>) -> bool {
+        (a - b).len() < 1e-4
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far() {
+        let camera = Camera::perspective(90f32.to_radians(), 2.0, 1.0, 10.0);
+        let projection = camera.projection();
+        let near = (projection * Vec3(2.0f32, 1.0, -1.0).augment()).retro_project();
+        let far = (projection * Vec3(-20.0f32, -10.0, -10.0).augment()).retro_project();
+        assert!(close(near, Vec3(1.0, 1.0, 1.0)), "{:?}", near);
+        assert!(close(far, Vec3(-1.0, -1.0, -1.0)), "{:?}", far);
+    }
+
+    #[test]
+    fn symmetric_frustum_matches_perspective() {
+        let perspective = Camera::perspective(90f32.to_radians(), 1.5, 0.5, 20.0);
+        let frustum = Camera::off_axis(-0.75, 0.75, -0.5, 0.5, 0.5, 20.0);
+        assert_eq!(frustum.aspect, 1.5);
+        let (a, b) = (perspective.projection(), frustum.projection());
+        for row in 0..4 {
+            for col in 0..4 {
+                assert!((a[(row, col)] - b[(row, col)]).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn rays_go_through_pixels() {
+        let camera = Camera::default()
+            .look_at(Vec3(0.0, 0.0, 5.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+        assert!(close(camera.position(), Vec3(0.0, 0.0, 5.0)));
+
+        let ray = camera.ray(50.0, 50.0, 100, 100);
+        assert!(close(ray.origin, Vec3(0.0, 0.0, 4.9)), "{:?}", ray);
+        assert!(close(ray.direction, Vec3(0.0, 0.0, -1.0)), "{:?}", ray);
+
+        // A ray through any pixel should land back on that pixel
+        let screen = Mat4::viewport(100, 100) * camera.view_projection();
+        let ray = camera.ray(20.0, 70.0, 100, 100);
+        let hit = (screen * ray.at(3.0).augment()).retro_project();
+        assert!((hit.0 - 20.0).abs() < 1e-3 && (hit.1 - 70.0).abs() < 1e-3, "{:?}", hit);
+
+        let orthographic = Camera::orthographic(2.0, 1.0, 0.1, 100.0)
+            .look_at(Vec3(0.0, 0.0, 5.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+        let ray = orthographic.ray(0.0, 0.0, 100, 100);
+        assert!(close(ray.origin, Vec3(-1.0, 1.0, 4.9)), "{:?}", ray);
+        assert!(close(ray.direction, Vec3(0.0, 0.0, -1.0)), "{:?}", ray);
+    }
+
+    #[test]
+    fn offsets_move_the_image() {
+        let eye = Vec3(1.0, 2.0, 5.0);
+        for &camera in &[Camera::perspective(1.0, 1.5, 0.1, 100.0),
+                         Camera::orthographic(2.0, 1.5, 0.1, 100.0)] {
+            let camera = camera.look_at(eye, Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+            let shifted = camera.offset_pixels(0.25, -0.5, 150, 100);
+            let (a, b) = (camera.ray(40.25, 29.5, 150, 100), shifted.ray(40.0, 30.0, 150, 100));
+            assert!(close(a.origin, b.origin), "{:?} {:?}", a, b);
+            assert!(close(a.direction, b.direction), "{:?} {:?}", a, b);
+        }
+
+        // Moving across the lens keeps the focal plane still, and moves
+        // everything else
+        let camera = Camera::perspective(1.0, 1.0, 0.1, 100.0)
+            .look_at(eye, Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+        let moved = camera.offset_lens(Vec2(0.1, -0.2), 4.0);
+        assert!(!close(moved.position(), eye));
+        let screen = |camera: &Camera, point: Vec3<f32>| {
+            (camera.view_projection() * point.augment()).retro_project()
+        };
+        let ray = camera.ray(20.0, 70.0, 100, 100);
+        let forward = (Vec3(0.0, 0.0, 0.0) - eye).normalized();
+        let focused = ray.at((4.0 - camera.near) / ray.direction.dot(forward));
+        let (a, b) = (screen(&camera, focused), screen(&moved, focused));
+        assert!((a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4, "{:?} {:?}", a, b);
+        let (a, b) = (screen(&camera, ray.at(0.5)), screen(&moved, ray.at(0.5)));
+        assert!((a.0 - b.0).abs() > 1e-2, "{:?} {:?}", a, b);
+    }
+
+    #[test]
+    fn frustum_culls_spheres() {
+        let camera = Camera::perspective(90f32.to_radians(), 1.0, 1.0, 10.0);
+        let frustum = camera.frustum();
+        assert!(frustum.contains_point(Vec3(0.0, 0.0, -5.0)));
+        assert!(!frustum.contains_point(Vec3(0.0, 0.0, 5.0)));
+        assert!(!frustum.contains_point(Vec3(0.0, 0.0, -11.0)));
+        assert!(!frustum.contains_point(Vec3(6.0, 0.0, -5.0)));
+
+        assert!(frustum.intersects_sphere(Vec3(0.0, 0.0, -0.5), 1.0));
+        assert!(frustum.intersects_sphere(Vec3(6.0, 0.0, -5.0), 1.0));
+        assert!(!frustum.intersects_sphere(Vec3(8.0, 0.0, -5.0), 1.0));
+        assert!(!frustum.intersects_sphere(Vec3(0.0, 0.0, 2.0), 1.0));
+    }
+}
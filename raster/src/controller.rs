@@ -0,0 +1,242 @@
+//! Camera controllers, which move a camera according to a few parameters or
+//! the time instead of input events.
+//!
+//! They're meant for rendering previews and animations in batches: set up a
+//! controller once, then ask it where the camera is for each frame.
+//!
+//! ```rust,no_run
+//! use cgl::Camera;
+//! use cgl::controller::{Controller, Orbit, Turntable};
+//! # let model: cgl::Model<cgl::Vert> = unimplemented!();
+//!
+//! let camera = Camera::default();
+//! let turntable = Turntable::new(Orbit::framing(&model, &camera), 4.0);
+//! for frame in 0..96 {
+//!     let camera = turntable.place(frame as f32 / 24.0, camera);
+//!     // Render the frame with `camera.view_projection()`...
+//! }
+//! ```
+
+use std::f32::consts::PI;
+
+use camera::Camera;
+use cgl_math::Vec3;
+use model::{MeshVertex, Model};
+
+/// Something that decides where a camera is and where it's looking
+pub trait Controller {
+    /// `camera`, moved to where the controller puts it `time` seconds in
+    fn place(&self, time: f32, camera: Camera) -> Camera;
+}
+
+/// Keeps the camera looking at a target from a fixed direction and distance.
+///
+/// The camera is always upright. With a yaw and pitch of 0 it looks at the
+/// target along -z, a positive yaw swings it around counter-clockwise when
+/// seen from above, and a positive pitch raises it up to look down. The pitch
+/// has to stay strictly between -90° and 90°.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    pub target: Vec3<f32>,
+    /// The angle around the vertical axis, in radians
+    pub yaw: f32,
+    /// The angle above the horizon, in radians
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Orbit {
+    pub fn new(target: Vec3<f32>, yaw: f32, pitch: f32, distance: f32) -> Self {
+        Orbit { target, yaw, pitch, distance }
+    }
+
+    /// An orbit around the center of `model`, far enough away that all of it
+    /// stays in `camera`'s view from any direction.
+    pub fn framing<V: MeshVertex>(model: &Model<V>, camera: &Camera) -> Self {
+        let (center, radius) = model.bounding_sphere();
+        Orbit::new(center, 0.0, 0.0, camera.framing_distance(radius))
+    }
+
+    /// The position of the camera
+    pub fn eye(&self) -> Vec3<f32> {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        self.target + Vec3(pitch_cos * yaw_sin, pitch_sin, pitch_cos * yaw_cos) * self.distance
+    }
+}
+
+impl Controller for Orbit {
+    fn place(&self, _: f32, camera: Camera) -> Camera {
+        camera.look_at(self.eye(), self.target, Vec3(0.0, 1.0, 0.0))
+    }
+}
+
+/// Orbits around the target at a steady speed, like the object is sitting on
+/// a turntable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Turntable {
+    /// Where the camera is at time 0
+    pub orbit: Orbit,
+    /// How many seconds it takes to go all the way around
+    pub period: f32,
+}
+
+impl Turntable {
+    pub fn new(orbit: Orbit, period: f32) -> Self {
+        Turntable { orbit, period }
+    }
+}
+
+impl Controller for Turntable {
+    fn place(&self, time: f32, camera: Camera) -> Camera {
+        let yaw = self.orbit.yaw + 2.0 * PI * time / self.period;
+        Orbit { yaw, ..self.orbit }.place(time, camera)
+    }
+}
+
+/// Flies the camera smoothly through a list of points, looking either where
+/// it's going or at a fixed target.
+///
+/// The path is a Catmull-Rom spline, so it passes through every point. The
+/// points are spread evenly over the duration, whatever the distance between
+/// them, and the camera stays at the ends outside of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyPath {
+    pub points: Vec<Vec3<f32>>,
+    /// How many seconds it takes to get from the first point to the last
+    pub duration: f32,
+    /// Where to look, or `None` to look along the path
+    pub target: Option<Vec3<f32>>,
+}
+
+impl FlyPath {
+    /// # Panics
+    ///
+    /// Panics if there are fewer than two points.
+    pub fn new(points: Vec<Vec3<f32>>, duration: f32) -> Self {
+        assert!(points.len() >= 2, "A fly path needs at least two points");
+        FlyPath { points, duration, target: None }
+    }
+
+    pub fn looking_at(self, target: Vec3<f32>) -> Self {
+        FlyPath { target: Some(target), ..self }
+    }
+
+    /// The four points that control the segment the camera is on at `time`,
+    /// and how far along that segment it is
+    fn segment(&self, time: f32) -> ([Vec3<f32>; 4], f32) {
+        let last = self.points.len() - 1;
+        let s = (time / self.duration).clamp(0.0, 1.0) * last as f32;
+        let i = (s.floor() as usize).min(last - 1);
+        let p = |i: usize| self.points[i.min(last)];
+        ([p(i.max(1) - 1), p(i), p(i + 1), p(i + 2)], s - i as f32)
+    }
+
+    /// The position of the camera at `time`
+    pub fn position(&self, time: f32) -> Vec3<f32> {
+        let ([p0, p1, p2, p3], u) = self.segment(time);
+        let a = p1 * 2.0;
+        let b = p2 - p0;
+        let c = p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3;
+        let d = (p1 - p2) * 3.0 + p3 - p0;
+        (a + b * u + c * (u * u) + d * (u * u * u)) * 0.5
+    }
+
+    /// The direction the camera is moving at `time`, normalized
+    pub fn direction(&self, time: f32) -> Vec3<f32> {
+        let ([p0, p1, p2, p3], u) = self.segment(time);
+        let b = p2 - p0;
+        let c = p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3;
+        let d = (p1 - p2) * 3.0 + p3 - p0;
+        let tangent = (b + c * (2.0 * u) + d * (3.0 * u * u)) * 0.5;
+        // The spline stops dead at repeated points, so fall back to the
+        // direction of the segment there
+        if tangent.len2() > 1e-12 { tangent.normalized() } else { (p2 - p1).normalized() }
+    }
+}
+
+impl Controller for FlyPath {
+    fn place(&self, time: f32, camera: Camera) -> Camera {
+        let eye = self.position(time);
+        let target = self.target.unwrap_or_else(|| eye + self.direction(time));
+        camera.look_at(eye, target, Vec3(0.0, 1.0, 0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Controller, FlyPath, Orbit, Turntable};
+    use camera::Camera;
+    use cgl_math::{Vec2, Vec3};
+    use model::{Model, Vert};
+    use std::f32::consts::PI;
+
+    fn close(a: Vec3<f32>, b: Vec3<f32>) -> bool {
+        (a - b).len() < 1e-4
+    }
+
+    /// Where a camera is looking, in world space
+    fn forward(camera: &Camera) -> Vec3<f32> {
+        let t = camera.transform;
+        Vec3(-t[(0, 2)], -t[(1, 2)], -t[(2, 2)])
+    }
+
+    #[test]
+    fn orbits_look_at_target() {
+        let target = Vec3(1.0, 2.0, 3.0);
+        let orbit = Orbit::new(target, 0.0, 0.0, 2.0);
+        assert!(close(orbit.eye(), Vec3(1.0, 2.0, 5.0)));
+        let orbit = Orbit { yaw: PI / 2.0, ..orbit };
+        assert!(close(orbit.eye(), Vec3(3.0, 2.0, 3.0)));
+        let orbit = Orbit { pitch: PI / 4.0, ..orbit };
+        let camera = orbit.place(0.0, Camera::default());
+        assert!(close(camera.position(), orbit.eye()));
+        assert!(close(forward(&camera), (target - orbit.eye()).normalized()));
+
+        let turntable = Turntable::new(orbit, 4.0);
+        assert!(close(turntable.place(4.0, Camera::default()).position(), orbit.eye()));
+        let quarter = Orbit { yaw: PI, ..orbit }.eye();
+        assert!(close(turntable.place(1.0, Camera::default()).position(), quarter));
+    }
+
+    #[test]
+    fn framing_fits_the_model() {
+        let model = Model {
+            vertices: vec![vert(-1.0, 0.0, 0.0), vert(3.0, 0.0, 0.0), vert(1.0, 2.0, 1.0)],
+            triangles: vec![[0, 1, 2]],
+        };
+        let (center, radius) = model.bounding_sphere();
+        assert!(close(center, Vec3(1.0, 1.0, 0.5)));
+        assert!((radius - 5.25f32.sqrt()).abs() < 1e-4);
+
+        for &camera in &[Camera::perspective(1.0, 2.0, 0.1, 100.0),
+                         Camera::perspective(1.0, 0.5, 0.1, 100.0)] {
+            let orbit = Orbit { yaw: 1.0, pitch: 0.3, ..Orbit::framing(&model, &camera) };
+            let frustum = orbit.place(0.0, camera).frustum();
+            for plane in &frustum.planes[..4] {
+                let distance = plane.dot(center.augment());
+                assert!(distance >= radius - 1e-3, "{} < {}", distance, radius);
+            }
+        }
+    }
+
+    fn vert(x: f32, y: f32, z: f32) -> Vert {
+        Vert { pos: Vec3(x, y, z), tex: Vec2(0.0, 0.0), norm: Vec3(0.0, 0.0, 1.0) }
+    }
+
+    #[test]
+    fn fly_paths_pass_through_points() {
+        let points = vec![Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, -1.0),
+                          Vec3(1.0, 0.0, -2.0), Vec3(2.0, 1.0, -2.0)];
+        let path = FlyPath::new(points.clone(), 3.0);
+        for (i, &point) in points.iter().enumerate() {
+            assert!(close(path.position(i as f32), point), "{:?}", path.position(i as f32));
+        }
+        assert!(close(path.position(10.0), points[3]));
+
+        let camera = path.place(0.0, Camera::default());
+        assert!(close(forward(&camera), Vec3(0.0, 0.0, -1.0)), "{:?}", forward(&camera));
+        let camera = path.clone().looking_at(Vec3(5.0, 0.0, 0.0)).place(0.0, Camera::default());
+        assert!(close(forward(&camera), Vec3(1.0, 0.0, 0.0)));
+    }
+}
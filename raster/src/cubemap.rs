@@ -0,0 +1,419 @@
+//! Cube maps, which are textures looked up by direction instead of by UV
+//! coordinates.
+//!
+//! A [`CubeMap`] is six square images on the faces of a cube around the
+//! origin. Sampling it with a direction gives whatever the cube shows in that
+//! direction, which makes it a good fit for storing environments, either to
+//! draw in the background or for shaders to reflect and refract.
+//!
+//! The faces are laid out the same way as in OpenGL, so cube maps made for it
+//! can be loaded directly.
+//!
+//! [`CubeMap`]: struct.CubeMap.html
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::ops::{Add, Mul};
+use std::path::Path;
+
+use bmp::read_bmp;
+use cgl_math::Vec3;
+use image::{Color, HdrColor, Image};
+
+/// One of the faces of a cube map, named by the axis pointing through its
+/// middle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl Face {
+    /// Every face, in the order they're stored in a `CubeMap`
+    pub const ALL: [Face; 6] = [Face::PositiveX, Face::NegativeX,
+                                Face::PositiveY, Face::NegativeY,
+                                Face::PositiveZ, Face::NegativeZ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The direction through the point `(s, t)` of the face, where both go
+    /// from -1 to 1 and `t` increases down the face's image
+    pub fn direction(self, s: f32, t: f32) -> Vec3<f32> {
+        match self {
+            Face::PositiveX => Vec3(1.0, -t, -s),
+            Face::NegativeX => Vec3(-1.0, -t, s),
+            Face::PositiveY => Vec3(s, 1.0, t),
+            Face::NegativeY => Vec3(s, -1.0, -t),
+            Face::PositiveZ => Vec3(s, -t, 1.0),
+            Face::NegativeZ => Vec3(-s, -t, -1.0),
+        }
+    }
+
+    /// The face that `dir` points through, and where it goes through it. This
+    /// is the inverse of `Face::direction`.
+    pub fn locate(dir: Vec3<f32>) -> (Face, f32, f32) {
+        let Vec3(x, y, z) = dir;
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        if ax >= ay && ax >= az {
+            if x > 0.0 {
+                (Face::PositiveX, -z / ax, -y / ax)
+            } else {
+                (Face::NegativeX, z / ax, -y / ax)
+            }
+        } else if ay >= az {
+            if y > 0.0 {
+                (Face::PositiveY, x / ay, z / ay)
+            } else {
+                (Face::NegativeY, x / ay, -z / ay)
+            }
+        } else if z > 0.0 {
+            (Face::PositiveZ, x / az, -y / az)
+        } else {
+            (Face::NegativeZ, -x / az, -y / az)
+        }
+    }
+}
+
+/// Six square images on the faces of a cube, sampled by direction
+#[derive(Clone)]
+pub struct CubeMap<P> {
+    faces: Vec<Image<P>>,
+    size: usize,
+}
+
+impl<P: Copy + Default> CubeMap<P> {
+    /// A cube map with the given faces, in the order of `Face::ALL`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the faces aren't all squares of the same size.
+    pub fn new(faces: [Image<P>; 6]) -> Self {
+        let size = faces[0].width;
+        for face in &faces {
+            assert!(face.width == size && face.height == size,
+                    "Cube map faces should all be {0}x{0}, not {1}x{2}",
+                    size, face.width, face.height);
+        }
+        CubeMap { faces: faces.to_vec(), size }
+    }
+
+    /// A cube map with `size` by `size` faces, where each texel is `f` of the
+    /// direction through its middle.
+    pub fn from_fn<F>(size: usize, mut f: F) -> Self
+        where F: FnMut(Vec3<f32>) -> P
+    {
+        let faces = Face::ALL.iter().map(|&face| {
+            let mut image = Image::with_dimensions(size, size);
+            for y in 0..size {
+                for x in 0..size {
+                    image[(x, y)] = f(texel_direction(face, x as isize, y as isize, size));
+                }
+            }
+            image
+        }).collect();
+        CubeMap { faces, size }
+    }
+
+    /// Split a cross-shaped image into the faces of a cube map.
+    ///
+    /// The cross can either be horizontal, four faces wide and three tall:
+    ///
+    /// ```text
+    ///      +Y
+    /// -X   +Z   +X   -Z
+    ///      -Y
+    /// ```
+    ///
+    /// or vertical, three faces wide and four tall, with -Z upside down:
+    ///
+    /// ```text
+    ///      +Y
+    /// -X   +Z   +X
+    ///      -Y
+    ///      -Z
+    /// ```
+    ///
+    /// Returns `None` if the image isn't one of those shapes.
+    pub fn from_cross(image: &Image<P>) -> Option<Self> {
+        let (w, h) = (image.width, image.height);
+        let (size, cells, flip_back) = if w * 3 == h * 4 && w % 4 == 0 {
+            (w / 4, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)], false)
+        } else if w * 4 == h * 3 && w % 3 == 0 {
+            (w / 3, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)], true)
+        } else {
+            return None;
+        };
+        let faces = Face::ALL.iter().zip(&cells).map(|(&face, &(col, row))| {
+            let mut out = Image::with_dimensions(size, size);
+            let flip = flip_back && face == Face::NegativeZ;
+            for y in 0..size {
+                for x in 0..size {
+                    let (fx, fy) = if flip { (size - 1 - x, size - 1 - y) } else { (x, y) };
+                    out[(x, y)] = image[(col * size + fx, row * size + fy)];
+                }
+            }
+            out
+        }).collect();
+        Some(CubeMap { faces, size })
+    }
+
+    /// The width and height of each face
+    pub fn size(&self) -> usize { self.size }
+    pub fn face(&self, face: Face) -> &Image<P> { &self.faces[face.index()] }
+    pub fn face_mut(&mut self, face: Face) -> &mut Image<P> { &mut self.faces[face.index()] }
+
+    /// Create a new cube map of the same size by applying `f` to every texel.
+    pub fn map<F, Out>(&self, mut f: F) -> CubeMap<Out>
+        where F: FnMut(P) -> Out
+    {
+        CubeMap {
+            faces: self.faces.iter().map(|face| face.map(&mut f)).collect(),
+            size: self.size,
+        }
+    }
+
+    /// The texel at `(x, y)` of `face`, where coordinates one texel past the
+    /// edge of the face are taken from the neighboring face instead
+    fn texel(&self, face: Face, x: isize, y: isize) -> P {
+        let size = self.size as isize;
+        if x >= 0 && x < size && y >= 0 && y < size {
+            return self.faces[face.index()][(x as usize, y as usize)];
+        }
+        let (face, s, t) = Face::locate(texel_direction(face, x, y, self.size));
+        let to_texel = |s: f32| {
+            let x = ((s + 1.0) / 2.0 * self.size as f32) as isize;
+            x.max(0).min(size - 1) as usize
+        };
+        self.faces[face.index()][(to_texel(s), to_texel(t))]
+    }
+}
+
+impl<P> CubeMap<P> where P: Mul<f32, Output=P> + Add<Output=P> + Copy + Default {
+    /// Sample the cube map in the direction `dir`, which doesn't need to be
+    /// normalized.
+    ///
+    /// Samples are bilinearly interpolated, including across the edges between
+    /// faces, so there are no seams. Like `Image::sample_clamp`, this happens
+    /// on the stored values, so `Color` cube maps should be converted with
+    /// `CubeMap::to_linear` first.
+    pub fn sample(&self, dir: Vec3<f32>) -> P {
+        let (face, s, t) = Face::locate(dir);
+        let x = (s + 1.0) / 2.0 * self.size as f32 - 0.5;
+        let y = (t + 1.0) / 2.0 * self.size as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let top = self.texel(face, x0, y0) * (1.0 - fx) + self.texel(face, x0 + 1, y0) * fx;
+        let bot = self.texel(face, x0, y0 + 1) * (1.0 - fx) + self.texel(face, x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bot * fy
+    }
+
+    /// Wrap an equirectangular image, with longitude going across and latitude
+    /// going down, around a cube map with `size` by `size` faces. The middle of
+    /// the image ends up in the -z direction.
+    pub fn from_equirectangular(image: &Image<P>, size: usize) -> Self {
+        CubeMap::from_fn(size, |dir| sample_equirectangular(image, dir))
+    }
+
+    /// Unwrap the cube map into a `width` by `height` equirectangular image.
+    /// This is the inverse of `CubeMap::from_equirectangular`.
+    pub fn to_equirectangular(&self, width: usize, height: usize) -> Image<P> {
+        let mut image = Image::with_dimensions(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                let v = (y as f32 + 0.5) / height as f32;
+                image[(x, y)] = self.sample(equirectangular_direction(u, v));
+            }
+        }
+        image
+    }
+}
+
+impl CubeMap<Color> {
+    /// Load a cube map from six BMP files, in the order of `Face::ALL`
+    pub fn open_bmps<Q: AsRef<Path>>(paths: &[Q; 6]) -> io::Result<Self> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths {
+            let mut reader = BufReader::new(File::open(path)?);
+            faces.push(read_bmp(&mut reader)?);
+        }
+        let size = faces[0].width;
+        if faces.iter().any(|face| face.width != size || face.height != size) {
+            return Err(io::Error::other("Cube map faces should be squares of the same size"));
+        }
+        Ok(CubeMap { faces, size })
+    }
+
+    /// Load a cube map from a single BMP laid out as a cross, as described in
+    /// `CubeMap::from_cross`
+    pub fn read_cross_bmp<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let image = read_bmp(reader)?;
+        CubeMap::from_cross(&image).ok_or_else(|| {
+            io::Error::other("Cube map should be laid out as a cross")
+        })
+    }
+
+    /// Decode the sRGB texels into linear RGB, ready for filtering and
+    /// lighting
+    pub fn to_linear(&self) -> CubeMap<HdrColor> {
+        self.map(Color::to_linear)
+    }
+}
+
+/// The direction through the middle of the texel at `(x, y)` of `face`
+fn texel_direction(face: Face, x: isize, y: isize, size: usize) -> Vec3<f32> {
+    let to_st = |x: isize| (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    face.direction(to_st(x), to_st(y))
+}
+
+/// The direction through the point `(u, v)` of an equirectangular image,
+/// where both go from 0 to 1 and `v` increases downwards
+fn equirectangular_direction(u: f32, v: f32) -> Vec3<f32> {
+    let longitude = (u - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - v) * PI;
+    Vec3(latitude.cos() * longitude.sin(), latitude.sin(), -latitude.cos() * longitude.cos())
+}
+
+/// Bilinearly sample an equirectangular image in the direction `dir`,
+/// wrapping around horizontally
+pub fn sample_equirectangular<P>(image: &Image<P>, dir: Vec3<f32>) -> P
+    where P: Mul<f32, Output=P> + Add<Output=P> + Copy
+{
+    let dir = dir.normalized();
+    let longitude = dir.0.atan2(-dir.2);
+    let latitude = dir.1.clamp(-1.0, 1.0).asin();
+    let x = (longitude / (2.0 * PI) + 0.5) * image.width as f32 - 0.5;
+    let y = (0.5 - latitude / PI) * image.height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (w, h) = (image.width as isize, image.height as isize);
+    let texel = |x: isize, y: isize| {
+        image[(((x % w + w) % w) as usize, y.max(0).min(h - 1) as usize)]
+    };
+    let (x0, y0) = (x0 as isize, y0 as isize);
+    let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+    let bot = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+    top * (1.0 - fy) + bot * fy
+}
+
+/// The direction `incident` bounces off in from a mirror facing `normal`. The
+/// normal should be normalized.
+pub fn reflect(incident: Vec3<f32>, normal: Vec3<f32>) -> Vec3<f32> {
+    incident - normal * (2.0 * normal.dot(incident))
+}
+
+/// The direction `incident` bends to when it goes through a surface facing
+/// `normal`, where `eta` is the ratio of the refractive indices on the
+/// incident side and the other side. Both directions should be normalized.
+/// Returns `None` when all of the light is reflected instead.
+pub fn refract(incident: Vec3<f32>, normal: Vec3<f32>, eta: f32) -> Option<Vec3<f32>> {
+    let cos_i = -normal.dot(incident);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        None
+    } else {
+        Some(incident * eta + normal * (eta * cos_i - k.sqrt()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CubeMap, Face, reflect, refract};
+    use cgl_math::Vec3;
+    use image::{Color, Image};
+
+    fn close(a: Vec3<f32>, b: Vec3<f32>) -> bool {
+        (a - b).len() < 1e-4
+    }
+
+    /// A cube map where each face is filled with its index
+    fn numbered(size: usize) -> CubeMap<f32> {
+        CubeMap::from_fn(size, |dir| Face::locate(dir).0 as usize as f32)
+    }
+
+    #[test]
+    fn directions_round_trip() {
+        for &face in &Face::ALL {
+            for &(s, t) in &[(0.0, 0.0), (0.5, -0.25), (-0.9, 0.9)] {
+                let (found, s2, t2) = Face::locate(face.direction(s, t) * 3.0);
+                assert_eq!(found, face);
+                assert!((s - s2).abs() < 1e-6 && (t - t2).abs() < 1e-6);
+            }
+        }
+        assert_eq!(Face::locate(Vec3(0.1, -2.0, 0.3)).0, Face::NegativeY);
+    }
+
+    #[test]
+    fn samples_blend_across_edges() {
+        let cube = numbered(4);
+        assert_eq!(cube.sample(Vec3(0.0, 0.0, -1.0)), Face::NegativeZ as usize as f32);
+        assert_eq!(cube.sample(Vec3(2.0, 0.1, 0.2)), Face::PositiveX as usize as f32);
+
+        // Exactly on the edge between +X and +Z, half of each
+        let edge = cube.sample(Vec3(1.0, 0.1, 1.0));
+        let expected = (Face::PositiveX as usize + Face::PositiveZ as usize) as f32 / 2.0;
+        assert!((edge - expected).abs() < 1e-5, "{}", edge);
+    }
+
+    #[test]
+    fn crosses_are_unfolded() {
+        // Each face of the cross is filled with its position
+        let size = 2;
+        let horizontal = {
+            let mut image = Image::with_dimensions(4 * size, 3 * size);
+            for y in 0..3 * size {
+                for x in 0..4 * size {
+                    image[(x, y)] = Color::rgb((x / size) as u8, (y / size) as u8, 0);
+                }
+            }
+            image
+        };
+        let cube = CubeMap::from_cross(&horizontal).expect("Should be a cross");
+        assert_eq!(cube.size(), size);
+        assert_eq!(cube.face(Face::PositiveY)[(0, 0)], Color::rgb(1, 0, 0));
+        assert_eq!(cube.face(Face::NegativeZ)[(1, 1)], Color::rgb(3, 1, 0));
+
+        let mut vertical = Image::with_dimensions(3 * size, 4 * size);
+        vertical[(size, 3 * size)] = Color::white();
+        let cube = CubeMap::from_cross(&vertical).expect("Should be a cross");
+        assert_eq!(cube.face(Face::NegativeZ)[(size - 1, size - 1)], Color::white());
+
+        assert!(CubeMap::from_cross(&Image::<Color>::with_dimensions(5, 5)).is_none());
+    }
+
+    #[test]
+    fn equirectangular_round_trip() {
+        let smooth = |dir: Vec3<f32>| {
+            let dir = dir.normalized();
+            dir.0 * 0.5 + dir.1 * 0.3 - dir.2 * 0.2
+        };
+        let cube = CubeMap::from_fn(32, smooth);
+        let flat = cube.to_equirectangular(128, 64);
+        let back = CubeMap::from_equirectangular(&flat, 32);
+        for &dir in &[Vec3(0.0, 0.0, -1.0), Vec3(0.3, 0.8, 0.2), Vec3(-1.0, -0.2, 0.5)] {
+            assert!((back.sample(dir) - smooth(dir)).abs() < 0.02,
+                    "{} != {}", back.sample(dir), smooth(dir));
+        }
+        // The middle of the image looks along -z
+        assert!((flat[(64, 32)] - smooth(Vec3(0.0, 0.0, -1.0))).abs() < 0.05);
+    }
+
+    #[test]
+    fn reflects_and_refracts() {
+        let normal = Vec3(0.0, 1.0, 0.0);
+        let incident = Vec3(1.0f32, -1.0, 0.0).normalized();
+        assert!(close(reflect(incident, normal), Vec3(1.0f32, 1.0, 0.0).normalized()));
+        assert!(close(refract(incident, normal, 1.0).unwrap(), incident));
+        let bent = refract(incident, normal, 1.0 / 1.5).unwrap();
+        assert!(bent.0 < incident.0 && bent.1 < 0.0);
+        assert!(refract(Vec3(1.0f32, -0.1, 0.0).normalized(), normal, 1.5).is_none());
+    }
+}
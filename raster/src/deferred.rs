@@ -0,0 +1,309 @@
+//! A deferred shading pipeline for scenes with many lights.
+//!
+//! Forward rendering with [`Renderer`] evaluates every light in
+//! `Shader::fragment`, once for every fragment that passes the depth test, even
+//! if it's overwritten later. Deferred shading splits the work into two passes
+//! instead:
+//!
+//! 1. The *geometry pass* rasterizes the scene with a shader that outputs a
+//!    [`Surface`] instead of a `Color`. The nearest surface at each pixel is
+//!    recorded into the attachments of a [`GBuffer`].
+//! 2. The *lighting pass* goes through the lights one at a time, and shades
+//!    only the pixels inside each light's screen-space bounds, accumulating the
+//!    results into a floating point buffer.
+//!
+//! Finally, the accumulated light is [tone mapped](../tonemap/index.html) into
+//! an `Image<Color>`.
+//!
+//! [`Renderer`]: ../renderer/struct.Renderer.html
+//! [`Surface`]: struct.Surface.html
+//! [`GBuffer`]: struct.GBuffer.html
+
+use cgl_math::{Vec3, Mat4};
+use image::{Image, Color, HdrColor, Pixel};
+use light::PointLight;
+use model::{Model, Vertex};
+use renderer::{Rasterizer, draw};
+use shader::Shader;
+use tessellation::{Tessellator, tessellate};
+use tonemap::{self, ToneMap};
+
+/// How a surface reflects specular light, as stored in the G-buffer
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GBufferMaterial {
+    /// How strong the specular highlight is, from 0 to 1
+    pub specular: f32,
+    /// The Blinn-Phong exponent, larger values give tighter highlights
+    pub shininess: f32,
+}
+
+/// The output of a geometry pass shader for a single pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    pub albedo: Color,
+    /// The world-space surface normal
+    pub normal: Vec3<f32>,
+    pub material: GBufferMaterial,
+}
+
+/// The attachments written by the geometry pass
+pub struct GBuffer {
+    pub albedo: Image<Color>,
+    pub normal: Image<Vec3<f32>>,
+    /// Screen-space depth, `f32::MIN` where no geometry was drawn
+    pub depth: Image<f32>,
+    pub material: Image<GBufferMaterial>,
+}
+
+impl GBuffer {
+    pub fn with_dimensions(w: usize, h: usize) -> Self {
+        GBuffer {
+            albedo: Image::with_dimensions(w, h),
+            normal: Image::with_dimensions(w, h),
+            depth: Image::filled(w, h, f32::MIN),
+            material: Image::with_dimensions(w, h),
+        }
+    }
+
+    /// Whether any geometry was drawn at the pixel `(x, y)`
+    pub fn is_covered(&self, x: usize, y: usize) -> bool {
+        self.depth[(x, y)] != f32::MIN
+    }
+}
+
+pub struct DeferredRenderer {
+    gbuf: GBuffer,
+    hdr: Image<HdrColor>,
+    rasterizer: Rasterizer,
+}
+
+impl DeferredRenderer {
+    pub fn with_dimensions(w: usize, h: usize) -> Self {
+        DeferredRenderer {
+            gbuf: GBuffer::with_dimensions(w, h),
+            hdr: Image::with_dimensions(w, h),
+            rasterizer: Rasterizer::default(),
+        }
+    }
+
+    /// Choose how the triangles of the geometry pass are rasterized.
+    pub fn set_rasterizer(&mut self, rasterizer: Rasterizer) {
+        self.rasterizer = rasterizer;
+    }
+
+    pub fn width(&self) -> usize { self.hdr.width }
+    pub fn height(&self) -> usize { self.hdr.height }
+    pub fn gbuffer(&self) -> &GBuffer { &self.gbuf }
+    /// The light accumulated so far, in linear RGB
+    pub fn hdr(&self) -> &Image<HdrColor> { &self.hdr }
+
+    /// Draw a triangle into the G-buffer.
+    pub fn tri<S, V, U>(&mut self, shader: &S, uniform: &U, t0: V, t1: V, t2: V)
+        where V: Vertex, S: Shader<V, U, Surface> + ?Sized
+    {
+        let GBuffer { ref mut albedo, ref mut normal, ref mut depth, ref mut material } =
+            self.gbuf;
+        draw(depth, self.rasterizer, shader, uniform, t0, t1, t2, |x, y, vert| {
+            let surface = shader.fragment(vert, uniform);
+            albedo[(x, y)] = surface.albedo;
+            normal[(x, y)] = surface.normal;
+            material[(x, y)] = surface.material;
+        });
+    }
+
+    /// Draw every triangle of `model` into the G-buffer.
+    pub fn model<S, V, U>(&mut self, shader: &S, uniform: &U, model: &Model<V>)
+        where V: Vertex + Copy, S: Shader<V, U, Surface> + ?Sized
+    {
+        for tri in &model.triangles {
+            self.tri(shader, uniform,
+                     model.vertices[tri[0]],
+                     model.vertices[tri[1]],
+                     model.vertices[tri[2]]);
+        }
+    }
+
+    /// Draw every triangle of `model` into the G-buffer, after splitting it up
+    /// with `tessellator`.
+    pub fn tessellated_model<S, T, V, U>(&mut self, shader: &S, tessellator: &T,
+                                         uniform: &U, model: &Model<V>)
+        where V: Vertex + Copy, T: Tessellator<V>, S: Shader<V, U, Surface> + ?Sized
+    {
+        for tri in &model.triangles {
+            tessellate(tessellator,
+                       model.vertices[tri[0]],
+                       model.vertices[tri[1]],
+                       model.vertices[tri[2]],
+                       |t0, t1, t2| self.tri(shader, uniform, t0, t1, t2));
+        }
+    }
+
+    /// Add a constant amount of light to every covered pixel.
+    pub fn ambient(&mut self, color: HdrColor) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if self.gbuf.is_covered(x, y) {
+                    let albedo = HdrColor::from_rgb(self.gbuf.albedo[(x, y)].to_rgb());
+                    self.hdr[(x, y)] += albedo * color;
+                }
+            }
+        }
+    }
+
+    /// Accumulate the contribution of each of `lights`.
+    ///
+    /// `matrix` must be the same world to screen transformation that the
+    /// geometry pass vertex shader used, since it's used to reconstruct the
+    /// world-space position of each pixel from its depth. `eye` is the
+    /// world-space position of the camera, for computing specular highlights.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `matrix` isn't invertible.
+    pub fn lights(&mut self, lights: &[PointLight], matrix: &Mat4<f32>, eye: Vec3<f32>) {
+        let inverse = matrix.inverted()
+            .expect("The view matrix should be invertible");
+        for light in lights {
+            let ((x0, y0), (x1, y1)) = match self.light_bounds(light, matrix) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    if !self.gbuf.is_covered(x, y) {
+                        continue;
+                    }
+                    let screen = Vec3(x as f32, y as f32, self.gbuf.depth[(x, y)]);
+                    let pos = (inverse * screen.augment()).retro_project();
+                    let color = self.shade(x, y, pos, light, eye);
+                    self.hdr[(x, y)] += color;
+                }
+            }
+        }
+    }
+
+    /// Resolve the accumulated light into displayable colors using the tone
+    /// mapping operator `op`.
+    pub fn resolve<T: ToneMap>(&self, op: &T) -> Image<Color> {
+        tonemap::resolve(&self.hdr, op)
+    }
+
+    fn shade(&self, x: usize, y: usize, pos: Vec3<f32>, light: &PointLight,
+             eye: Vec3<f32>)
+             -> HdrColor
+    {
+        let to_light = light.position - pos;
+        let distance = to_light.len() as f32;
+        let attenuation = light.attenuation(distance);
+        if attenuation <= 0.0 {
+            return HdrColor::black();
+        }
+
+        let normal = self.gbuf.normal[(x, y)].normalized();
+        let material = self.gbuf.material[(x, y)];
+        let l = to_light / distance;
+        let h = (l + (eye - pos).normalized()).normalized();
+        let diffuse = normal.dot(l).max(0.0);
+        let specular = if diffuse > 0.0 {
+            normal.dot(h).max(0.0).powf(material.shininess) * material.specular
+        } else {
+            0.0
+        };
+
+        let reflected = self.gbuf.albedo[(x, y)].to_rgb() * diffuse +
+            Vec3(specular, specular, specular);
+        HdrColor::from_rgb(reflected) * light.color * (light.intensity * attenuation)
+    }
+
+    /// The screen-space rectangle `[x0, x1) × [y0, y1)` that a light can
+    /// affect, found by projecting the corners of the box around its sphere of
+    /// influence. Returns `None` if the light is entirely off-screen.
+    fn light_bounds(&self, light: &PointLight, matrix: &Mat4<f32>)
+                    -> Option<((usize, usize), (usize, usize))>
+    {
+        let (w, h) = (self.width() as f32, self.height() as f32);
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for i in 0..8 {
+            let corner = Vec3(if i & 1 == 0 { -1.0 } else { 1.0 },
+                              if i & 2 == 0 { -1.0 } else { 1.0 },
+                              if i & 4 == 0 { -1.0 } else { 1.0 });
+            let p = *matrix * (light.position + corner * light.radius).augment();
+            if p.3 <= 0.0 {
+                // A corner is behind the camera, so the projected box isn't
+                // meaningful and the light could reach anywhere.
+                return Some(((0, 0), (self.width(), self.height())));
+            }
+            let p = p.retro_project();
+            min_x = min_x.min(p.0);
+            min_y = min_y.min(p.1);
+            max_x = max_x.max(p.0);
+            max_y = max_y.max(p.1);
+        }
+
+        if max_x < 0.0 || max_y < 0.0 || min_x >= w || min_y >= h {
+            return None;
+        }
+        let clamp = |v: f32, max: f32| v.max(0.0).min(max) as usize;
+        Some(((clamp(min_x.floor(), w), clamp(min_y.floor(), h)),
+              (clamp(max_x.ceil() + 1.0, w), clamp(max_y.ceil() + 1.0, h))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeferredRenderer, GBufferMaterial, Surface};
+    use cgl_math::{Vec3, Vec4, Mat4};
+    use image::{Color, HdrColor};
+    use light::PointLight;
+    use shader::Shader;
+    use tonemap::Clamp;
+
+    struct Flat;
+
+    impl Shader<Vec3<f32>, Mat4<f32>, Surface> for Flat {
+        type VOut = Vec3<f32>;
+
+        fn vertex(&self, vert: Vec3<f32>, mat: &Mat4<f32>, pos: &mut Vec4<f32>)
+                  -> Vec3<f32>
+        {
+            *pos = *mat * vert.augment();
+            vert
+        }
+
+        fn fragment(&self, _: Vec3<f32>, _: &Mat4<f32>) -> Surface {
+            Surface {
+                albedo: Color::white(),
+                normal: Vec3(0.0, 0.0, 1.0),
+                material: GBufferMaterial::default(),
+            }
+        }
+    }
+
+    fn render(light: PointLight) -> DeferredRenderer {
+        let matrix = Mat4::viewport(16, 16);
+        let mut renderer = DeferredRenderer::with_dimensions(16, 16);
+        renderer.tri(&Flat, &matrix,
+                     Vec3(-0.5, -0.5, 0.0), Vec3(0.5, -0.5, 0.0), Vec3(0.0, 0.5, 0.0));
+        renderer.lights(&[light], &matrix, Vec3(0.0, 0.0, 1.0));
+        renderer
+    }
+
+    #[test]
+    fn lights_covered_pixels() {
+        let renderer = render(PointLight::new(Vec3(0.0, 0.0, 1.0),
+                                              HdrColor::gray(1.0), 2.0, 4.0));
+        let image = renderer.resolve(&Clamp { stops: 0.0 });
+        assert!(renderer.gbuffer().is_covered(8, 8));
+        assert!(image[(8, 8)].r > 0);
+        assert!(!renderer.gbuffer().is_covered(0, 0));
+        assert_eq!(image[(0, 0)], Color::black());
+    }
+
+    #[test]
+    fn light_out_of_range() {
+        let renderer = render(PointLight::new(Vec3(0.0, 0.0, 3.0),
+                                              HdrColor::gray(1.0), 2.0, 1.0));
+        assert_eq!(renderer.resolve(&Clamp { stops: 0.0 })[(8, 8)], Color::black());
+    }
+}
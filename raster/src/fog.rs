@@ -0,0 +1,273 @@
+//! Fog, which fades whatever is far from the camera into a fog color.
+//!
+//! A [`Fog`] can be applied in two places:
+//!
+//! - In a shader, by passing the fragment's color through `Fog::blend` along
+//!   with the eye and the fragment's world-space position.
+//! - After rendering, with `Renderer::fog` or a [`FogPass`], which work out
+//!   each pixel's position from the depth buffer. Pixels where nothing was
+//!   drawn are treated as infinitely far away, so the background is fogged
+//!   too.
+//!
+//! Fog can also get thinner with height, so that it settles into valleys and
+//! leaves the sky clear. Its density is then integrated along the line of
+//! sight, so looking down through the fog from above it is thinner than
+//! looking across through it.
+//!
+//! [`Fog`]: struct.Fog.html
+//! [`FogPass`]: struct.FogPass.html
+
+use camera::Camera;
+use cgl_math::{Vec3, Vec4, Mat4, saturate};
+use image::{HdrColor, Image, Pixel};
+use post::Pass;
+
+/// How the amount of fog grows with distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    /// No fog before `start`, growing evenly up to solid fog at `end`. If
+    /// `end` isn't after `start`, the fog goes straight from clear to solid.
+    Linear { start: f32, end: f32 },
+    /// `1 - e^(-density * distance)`, which is how light is actually absorbed.
+    /// A density of 0 or less means no fog.
+    Exponential { density: f32 },
+    /// `1 - e^(-(density * distance)²)`, which stays clear for longer before
+    /// closing in. A density of 0 or less means no fog.
+    ExponentialSquared { density: f32 },
+}
+
+impl Falloff {
+    /// How much of the color at `distance` is replaced by fog, from 0 to 1
+    pub fn amount(&self, distance: f32) -> f32 {
+        // Checked first, so that an infinite distance never meets a zero
+        match *self {
+            Falloff::Linear { start, end } if end <= start => {
+                if distance > start { 1.0 } else { 0.0 }
+            }
+            Falloff::Linear { start, end } => saturate((distance - start) / (end - start)),
+            Falloff::Exponential { density } | Falloff::ExponentialSquared { density }
+                if density <= 0.0 => 0.0,
+            Falloff::Exponential { density } => 1.0 - (-density * distance).exp(),
+            Falloff::ExponentialSquared { density } => {
+                1.0 - (-(density * distance).powi(2)).exp()
+            }
+        }
+    }
+}
+
+/// Fog that gets thinner with height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightFalloff {
+    /// The height at which the fog is as thick as its `Falloff` says
+    pub base: f32,
+    /// How quickly the fog thins out above `base`, and thickens below it. The
+    /// fog is `e` times thinner every `1 / rate` units up.
+    pub rate: f32,
+}
+
+/// Fades colors into `color` with distance from the eye
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    /// The linear RGB color of the fog
+    pub color: Vec3<f32>,
+    pub falloff: Falloff,
+    pub height: Option<HeightFalloff>,
+}
+
+impl Fog {
+    /// Fog that's the same thickness everywhere
+    pub fn new(color: HdrColor, falloff: Falloff) -> Self {
+        Fog { color: color.to_rgb(), falloff, height: None }
+    }
+
+    /// The same fog, thinning out above `base` at `rate`, as described in
+    /// `HeightFalloff`
+    pub fn with_height(self, base: f32, rate: f32) -> Self {
+        Fog { height: Some(HeightFalloff { base, rate }), ..self }
+    }
+
+    /// How much of the color at `point` is replaced by fog when seen from
+    /// `eye`, from 0 to 1
+    pub fn amount(&self, eye: Vec3<f32>, point: Vec3<f32>) -> f32 {
+        let offset = point - eye;
+        let distance = offset.len() as f32;
+        if distance == 0.0 {
+            return 0.0;
+        }
+        self.amount_along(eye, offset / distance, distance)
+    }
+
+    /// How much fog there is looking from `eye` along the normalized
+    /// direction `dir` for `distance`, which may be infinite
+    fn amount_along(&self, eye: Vec3<f32>, dir: Vec3<f32>, distance: f32) -> f32 {
+        let distance = match self.height {
+            // Integrating e^(-rate * (y - base)) along the ray gives the
+            // distance through fog as thick as it is at the base height
+            Some(HeightFalloff { base, rate }) => {
+                let k = rate * dir.1;
+                let thickness = (-rate * (eye.1 - base)).exp();
+                if thickness == 0.0 {
+                    // So far above the fog that there's none to see, even
+                    // looking out to an infinite distance
+                    0.0
+                } else if k.abs() < 1e-6 {
+                    distance * thickness
+                } else {
+                    -(-k * distance).exp_m1() / k * thickness
+                }
+            }
+            None => distance,
+        };
+        self.falloff.amount(distance)
+    }
+
+    /// Fade `color`, seen at `point` from `eye`, into the fog
+    pub fn blend<P: Pixel>(&self, color: P, eye: Vec3<f32>, point: Vec3<f32>) -> P {
+        let t = self.amount(eye, point);
+        P::from_rgb(color.to_rgb() * (1.0 - t) + self.color * t)
+    }
+
+    /// Fog every pixel of `image`, working out where each one is from `depth`
+    /// as seen through `camera`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the images aren't the same size, or if the camera's
+    /// transformation isn't invertible.
+    pub fn apply<P: Pixel>(&self, camera: &Camera, image: &mut Image<P>, depth: &Image<f32>) {
+        assert_eq!((image.width, image.height), (depth.width, depth.height));
+        let viewport = Mat4::viewport(image.width as i32, image.height as i32);
+        let screen = viewport * camera.view_projection();
+        // The depths that the near and far planes end up at on screen
+        let (near_z, far_z) = ((viewport * Vec4(0.0, 0.0, 1.0, 1.0)).2,
+                               (viewport * Vec4(0.0, 0.0, -1.0, 1.0)).2);
+        let inverse = screen.inverted()
+            .expect("The camera's transformation should be invertible");
+        let eye = camera.position();
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (sx, sy) = (x as f32 + 0.5, y as f32 + 0.5);
+                let z = depth[(x, y)];
+                let t = if z == f32::MIN {
+                    let near = (inverse * Vec4(sx, sy, near_z, 1.0)).retro_project();
+                    let far = (inverse * Vec4(sx, sy, far_z, 1.0)).retro_project();
+                    self.amount_along(eye, (far - near).normalized(), f32::INFINITY)
+                } else {
+                    self.amount(eye, (inverse * Vec4(sx, sy, z, 1.0)).retro_project())
+                };
+                let color = image[(x, y)].to_rgb();
+                image[(x, y)] = P::from_rgb(color * (1.0 - t) + self.color * t);
+            }
+        }
+    }
+}
+
+/// Applies a `Fog` as a post-processing pass, seen through a camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogPass {
+    pub fog: Fog,
+    /// The camera the image was rendered through
+    pub camera: Camera,
+}
+
+impl<P> Pass<P> for FogPass where P: Pixel {
+    fn apply(&self, image: &Image<P>, depth: &Image<f32>) -> Image<P> {
+        let mut out = image.clone();
+        self.fog.apply(&self.camera, &mut out, depth);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Falloff, Fog, FogPass};
+    use camera::Camera;
+    use cgl_math::{Vec3, Mat4};
+    use image::{HdrColor, Image};
+    use post::Pass;
+
+    const EYE: Vec3<f32> = Vec3(0.0, 0.0, 0.0);
+
+    #[test]
+    fn falloffs_grow_with_distance() {
+        let linear = Falloff::Linear { start: 2.0, end: 6.0 };
+        assert_eq!(linear.amount(1.0), 0.0);
+        assert_eq!(linear.amount(4.0), 0.5);
+        assert_eq!(linear.amount(10.0), 1.0);
+
+        let exp = Falloff::Exponential { density: 0.5 };
+        let exp2 = Falloff::ExponentialSquared { density: 0.5 };
+        assert_eq!(exp.amount(0.0), 0.0);
+        assert!((exp.amount(2.0) - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+        // Squaring keeps the near distance clearer and the far distance
+        // thicker, crossing over where density * distance is 1
+        assert!(exp2.amount(1.0) < exp.amount(1.0));
+        assert!(exp2.amount(4.0) > exp.amount(4.0));
+        assert!(exp.amount(1000.0) > 0.999);
+    }
+
+    #[test]
+    fn degenerate_falloffs_stay_finite() {
+        for &density in &[0.0, -1.0] {
+            assert_eq!(Falloff::Exponential { density }.amount(f32::INFINITY), 0.0);
+            assert_eq!(Falloff::ExponentialSquared { density }.amount(f32::INFINITY), 0.0);
+        }
+        let step = Falloff::Linear { start: 3.0, end: 3.0 };
+        assert_eq!(step.amount(3.0), 0.0);
+        assert_eq!(step.amount(3.5), 1.0);
+        assert_eq!(step.amount(f32::INFINITY), 1.0);
+        assert_eq!(Falloff::Linear { start: 3.0, end: 1.0 }.amount(2.0), 0.0);
+    }
+
+    #[test]
+    fn fog_thins_with_height() {
+        let flat = Fog::new(HdrColor::gray(1.0), Falloff::Exponential { density: 0.2 });
+        let fog = flat.with_height(0.0, 1.0);
+        let across = fog.amount(EYE, Vec3(5.0, 0.0, 0.0));
+        // Level with the base, the height makes no difference
+        assert!((across - flat.amount(EYE, Vec3(5.0, 0.0, 0.0))).abs() < 1e-5);
+        // The same distance upwards goes through less fog, and downwards
+        // through more
+        assert!(fog.amount(EYE, Vec3(3.0, 4.0, 0.0)) < across);
+        assert!(fog.amount(EYE, Vec3(3.0, -4.0, 0.0)) > across);
+        // Looking up from above the base, there's only so much fog in the way
+        assert!(fog.amount_along(Vec3(0.0, 2.0, 0.0), Vec3(0.0, 1.0, 0.0), f32::INFINITY) < 0.1);
+        assert_eq!(fog.amount_along(EYE, Vec3(0.0, -1.0, 0.0), f32::INFINITY), 1.0);
+        // High enough that the fog underflows to nothing, looking out to the
+        // horizon
+        let horizon = fog.amount_along(Vec3(0.0, 200.0, 0.0), Vec3(1.0, 0.0, 0.0), f32::INFINITY);
+        assert_eq!(horizon, 0.0);
+    }
+
+    #[test]
+    fn blends_toward_color() {
+        let fog = Fog::new(HdrColor::rgb(1.0, 0.0, 0.0),
+                           Falloff::Linear { start: 0.0, end: 4.0 });
+        let color = fog.blend(HdrColor::rgb(0.0, 0.0, 1.0), EYE, Vec3(0.0, 0.0, -1.0));
+        assert_eq!(color, HdrColor::rgb(0.25, 0.0, 0.75));
+    }
+
+    #[test]
+    fn pass_reads_distance_from_depth() {
+        let camera = Camera::perspective(60f32.to_radians(), 1.0, 0.1, 100.0);
+        let fog = Fog::new(HdrColor::gray(1.0), Falloff::Exponential { density: 0.1 });
+        let size = 9;
+
+        // Everything in the image is a wall 5 units in front of the camera,
+        // except the bottom row which is empty
+        let screen = Mat4::viewport(size as i32, size as i32) * camera.view_projection();
+        let wall = (screen * Vec3(0.0f32, 0.0, -5.0).augment()).retro_project().2;
+        let mut depth = Image::filled(size, size, wall);
+        for x in 0..size {
+            depth[(x, size - 1)] = f32::MIN;
+        }
+        let image = Image::filled(size, size, HdrColor::black());
+        let fogged = FogPass { fog, camera }.apply(&image, &depth);
+
+        let center = fogged[(size / 2, size / 2)].r;
+        assert!((center - fog.amount(EYE, Vec3(0.0, 0.0, -5.0))).abs() < 1e-3, "{}", center);
+        // The wall is further away towards the corners
+        assert!(fogged[(0, 0)].r > center);
+        assert!(fogged[(0, size - 1)].r > 0.999);
+    }
+}
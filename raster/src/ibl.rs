@@ -0,0 +1,399 @@
+//! Image-based lighting, where a whole environment map lights a scene instead
+//! of a handful of lights.
+//!
+//! Integrating the environment for every fragment would be far too slow, so
+//! an [`Environment`] precomputes everything ahead of time:
+//!
+//! - [`Irradiance`]: the diffuse light arriving at a surface facing each
+//!   direction, stored as nine spherical harmonics coefficients.
+//! - [`Specular`]: the environment blurred by the GGX distribution for a range
+//!   of roughnesses, with a smaller cube map for each one.
+//! - [`BrdfLut`]: the rest of the specular integral, which only depends on the
+//!   viewing angle and roughness. This is the "split sum" approximation from
+//!   Karis' *Real Shading in Unreal Engine 4*.
+//!
+//! [`Environment`]: struct.Environment.html
+//! [`Irradiance`]: struct.Irradiance.html
+//! [`Specular`]: struct.Specular.html
+//! [`BrdfLut`]: struct.BrdfLut.html
+
+use std::f32::consts::PI;
+
+use cgl_math::{Vec2, Vec3, saturate};
+use cubemap::{CubeMap, Face, reflect};
+use image::{HdrColor, Image, Pixel};
+
+/// Precomputed lighting from an environment map, ready for shaders to sample
+#[derive(Clone)]
+pub struct Environment {
+    pub irradiance: Irradiance,
+    pub specular: Specular,
+    pub brdf: BrdfLut,
+}
+
+impl Environment {
+    /// Precompute the lighting from `environment` with reasonable quality
+    /// settings: six specular levels, 64 samples per texel, and a 32x32 BRDF
+    /// lookup table
+    pub fn new(environment: &CubeMap<HdrColor>) -> Self {
+        Environment {
+            irradiance: Irradiance::new(environment),
+            specular: Specular::new(environment, 6, 64),
+            brdf: BrdfLut::new(32, 128),
+        }
+    }
+
+    /// The light diffusely reflected by a white Lambertian surface facing
+    /// `normal`
+    pub fn diffuse(&self, normal: Vec3<f32>) -> HdrColor {
+        self.irradiance.sample(normal)
+    }
+
+    /// The light specularly reflected towards `view` by a surface facing
+    /// `normal`, with a head-on reflectance of `f0`. Both directions point
+    /// away from the surface and should be normalized.
+    pub fn specular(&self, normal: Vec3<f32>, view: Vec3<f32>, f0: Vec3<f32>, roughness: f32)
+                    -> HdrColor
+    {
+        let n_dot_v = normal.dot(view).max(0.0);
+        let (scale, bias) = self.brdf.sample(n_dot_v, roughness);
+        let prefiltered = self.specular.sample(reflect(-view, normal), roughness);
+        prefiltered * HdrColor::from_rgb(f0 * scale + Vec3(bias, bias, bias))
+    }
+}
+
+/// The diffuse irradiance from an environment, projected onto the first nine
+/// spherical harmonics.
+///
+/// Irradiance changes so slowly with direction that nine coefficients are
+/// enough to get within a few percent of the real thing, as shown by
+/// Ramamoorthi and Hanrahan in *An Efficient Representation for Irradiance
+/// Environment Maps*.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Irradiance {
+    /// Already convolved with the cosine lobe and divided by π, so they can be
+    /// summed up and used as is
+    pub coefficients: [HdrColor; 9],
+}
+
+/// The first nine real spherical harmonics in the direction `dir`, which
+/// should be normalized
+fn harmonics(dir: Vec3<f32>) -> [f32; 9] {
+    let Vec3(x, y, z) = dir;
+    [0.282095,
+     0.488603 * y, 0.488603 * z, 0.488603 * x,
+     1.092548 * x * y, 1.092548 * y * z, 0.315392 * (3.0 * z * z - 1.0),
+     1.092548 * x * z, 0.546274 * (x * x - y * y)]
+}
+
+impl Irradiance {
+    /// Project `environment` onto spherical harmonics, weighing each texel by
+    /// how much of the sphere it covers
+    pub fn new(environment: &CubeMap<HdrColor>) -> Self {
+        let size = environment.size();
+        let mut coefficients = [HdrColor::black(); 9];
+        let mut total = 0.0;
+        for &face in &Face::ALL {
+            let image = environment.face(face);
+            for y in 0..size {
+                for x in 0..size {
+                    let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let dir = face.direction(s, t);
+                    let weight = 1.0 / dir.len2().powf(1.5);
+                    total += weight;
+                    let texel = image[(x, y)];
+                    for (c, basis) in coefficients.iter_mut().zip(&harmonics(dir.normalized())) {
+                        *c += texel * (basis * weight);
+                    }
+                }
+            }
+        }
+
+        // Normalize the weights to cover exactly 4π, and convolve with the
+        // cosine lobe, which is π, 2π/3 and π/4 for each band. Dividing by π
+        // leaves these factors.
+        let bands = [1.0, 2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 0.25, 0.25, 0.25, 0.25, 0.25];
+        for (c, band) in coefficients.iter_mut().zip(&bands) {
+            *c = *c * (4.0 * PI / total * band);
+        }
+        Irradiance { coefficients }
+    }
+
+    /// The light diffusely reflected by a white Lambertian surface facing
+    /// `normal`. Multiplying it by the surface's albedo gives its color.
+    pub fn sample(&self, normal: Vec3<f32>) -> HdrColor {
+        let mut color = HdrColor::black();
+        for (c, basis) in self.coefficients.iter().zip(&harmonics(normal.normalized())) {
+            color += *c * *basis;
+        }
+        // Nine coefficients can't represent a really bright sun exactly, and
+        // ring slightly negative on the far side
+        HdrColor::rgb(color.r.max(0.0), color.g.max(0.0), color.b.max(0.0))
+    }
+}
+
+/// An environment blurred by the GGX distribution for a range of roughnesses.
+///
+/// Each level is half the size of the one before it, since rougher
+/// reflections don't need as much detail. The first level is the environment
+/// itself, for a roughness of 0, and the last is for a roughness of 1.
+#[derive(Clone)]
+pub struct Specular {
+    levels: Vec<CubeMap<HdrColor>>,
+}
+
+impl Specular {
+    /// Prefilter `environment` into `levels` levels, taking `samples` samples
+    /// of it for every texel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are less than two levels.
+    pub fn new(environment: &CubeMap<HdrColor>, levels: usize, samples: usize) -> Self {
+        assert!(levels >= 2, "Should have at least two levels, not {}", levels);
+        let mips = mip_chain(environment);
+        let mut out = vec![environment.clone()];
+        for level in 1..levels {
+            let roughness = level as f32 / (levels - 1) as f32;
+            let size = (environment.size() >> level).max(1);
+            out.push(CubeMap::from_fn(size, |dir| {
+                prefilter(&mips, dir.normalized(), roughness, samples)
+            }));
+        }
+        Specular { levels: out }
+    }
+
+    pub fn levels(&self) -> &[CubeMap<HdrColor>] { &self.levels }
+
+    /// The environment reflected by a surface with the given roughness in the
+    /// direction `dir`, blending between the two nearest levels
+    pub fn sample(&self, dir: Vec3<f32>, roughness: f32) -> HdrColor {
+        sample_lod(&self.levels, dir, saturate(roughness) * (self.levels.len() - 1) as f32)
+    }
+}
+
+/// The environment halved in size over and over until it's a single texel
+/// per face
+fn mip_chain(environment: &CubeMap<HdrColor>) -> Vec<CubeMap<HdrColor>> {
+    let mut mips = vec![environment.clone()];
+    while mips[mips.len() - 1].size() > 1 {
+        let last = &mips[mips.len() - 1];
+        // The middle of each texel of the smaller map is on the corner between
+        // four texels of the larger one, so bilinear sampling averages them
+        let smaller = CubeMap::from_fn(last.size() / 2, |dir| last.sample(dir));
+        mips.push(smaller);
+    }
+    mips
+}
+
+/// Sample between the levels of a mip chain, where `lod` 0 is the first level
+fn sample_lod(levels: &[CubeMap<HdrColor>], dir: Vec3<f32>, lod: f32) -> HdrColor {
+    let lod = lod.clamp(0.0, (levels.len() - 1) as f32);
+    let lower = lod.floor() as usize;
+    let upper = (lower + 1).min(levels.len() - 1);
+    let t = lod - lower as f32;
+    levels[lower].sample(dir) * (1.0 - t) + levels[upper].sample(dir) * t
+}
+
+/// The `i`th of `n` points of the Hammersley set, which covers the unit square
+/// more evenly than random points
+fn hammersley(i: usize, n: usize) -> Vec2<f32> {
+    Vec2(i as f32 / n as f32, (i as u32).reverse_bits() as f32 / 4294967296.0)
+}
+
+/// A half vector around `normal`, distributed like the GGX distribution with
+/// `a = roughness²`
+fn importance_sample_ggx(xi: Vec2<f32>, normal: Vec3<f32>, a: f32) -> Vec3<f32> {
+    let phi = 2.0 * PI * xi.0;
+    let cos_theta = ((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let up = if normal.2.abs() < 0.999 { Vec3(0.0, 0.0, 1.0) } else { Vec3(1.0, 0.0, 0.0) };
+    let tangent = up.cross(normal).normalized();
+    let bitangent = normal.cross(tangent);
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normal * cos_theta
+}
+
+/// The environment convolved with the GGX distribution around `dir`, assuming
+/// the surface is looked at head-on so the normal, view and reflection are all
+/// the same direction.
+///
+/// Each sample is taken from a blurrier mip when it stands in for a bigger
+/// part of the sphere, so that a few samples don't pick out individual bright
+/// texels. This is the filtered importance sampling from GPU Gems 3, chapter
+/// 20.
+fn prefilter(mips: &[CubeMap<HdrColor>], dir: Vec3<f32>, roughness: f32, samples: usize)
+             -> HdrColor
+{
+    let a = roughness * roughness;
+    let texel_solid_angle = 4.0 * PI / (6.0 * (mips[0].size() * mips[0].size()) as f32);
+    let mut color = HdrColor::black();
+    let mut total = 0.0;
+    for i in 0..samples {
+        let h = importance_sample_ggx(hammersley(i, samples), dir, a);
+        let l = reflect(-dir, h);
+        let n_dot_l = dir.dot(l);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        // With the normal and view the same, the pdf of l is D / 4
+        let n_dot_h = dir.dot(h).max(0.0);
+        let d = n_dot_h * n_dot_h * (a * a - 1.0) + 1.0;
+        let pdf = a * a / (PI * d * d) / 4.0;
+        let sample_solid_angle = 1.0 / (samples as f32 * pdf + 1e-4);
+        let lod = 0.5 * (sample_solid_angle / texel_solid_angle).log2() + 1.0;
+        color += sample_lod(mips, l, lod.max(0.0)) * n_dot_l;
+        total += n_dot_l;
+    }
+    color * (1.0 / total)
+}
+
+/// The specular reflectance of a GGX surface lit evenly from every direction,
+/// as a function of the viewing angle and roughness.
+///
+/// The reflectance of a surface with a head-on reflectance of `f0` is `f0 *
+/// scale + bias`. Each is stored in its own image, with the cosine of the
+/// viewing angle going from 0 to 1 left to right, and the roughness going
+/// from 0 to 1 bottom to top, so they can be looked up with
+/// `Image::sample_clamp`.
+#[derive(Clone)]
+pub struct BrdfLut {
+    pub scale: Image<f32>,
+    pub bias: Image<f32>,
+}
+
+impl BrdfLut {
+    /// Integrate a `size` by `size` table with `samples` samples per entry
+    pub fn new(size: usize, samples: usize) -> Self {
+        let mut scale = Image::with_dimensions(size, size);
+        let mut bias = Image::with_dimensions(size, size);
+        let step = 1.0 / (size - 1).max(1) as f32;
+        for y in 0..size {
+            let roughness = 1.0 - y as f32 * step;
+            for x in 0..size {
+                let n_dot_v = (x as f32 * step).max(1e-3);
+                let (s, b) = integrate_brdf(n_dot_v, roughness, samples);
+                scale[(x, y)] = s;
+                bias[(x, y)] = b;
+            }
+        }
+        BrdfLut { scale, bias }
+    }
+
+    /// The scale and bias to apply to `f0` for the given viewing angle and
+    /// roughness
+    pub fn sample(&self, n_dot_v: f32, roughness: f32) -> (f32, f32) {
+        (self.scale.sample_clamp(n_dot_v, roughness), self.bias.sample_clamp(n_dot_v, roughness))
+    }
+}
+
+fn integrate_brdf(n_dot_v: f32, roughness: f32, samples: usize) -> (f32, f32) {
+    let normal = Vec3(0.0, 0.0, 1.0);
+    let view = Vec3((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+    let a = roughness * roughness;
+    // Image-based lighting remaps k differently from analytic lights
+    let k = a / 2.0;
+    let g1 = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+    let (mut scale, mut bias) = (0.0, 0.0);
+    for i in 0..samples {
+        let h = importance_sample_ggx(hammersley(i, samples), normal, a);
+        let l = reflect(-view, h);
+        let n_dot_l = l.2;
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        let n_dot_h = h.2.max(0.0);
+        let v_dot_h = view.dot(h).max(0.0);
+        let visibility = g1(n_dot_l) * g1(n_dot_v) * v_dot_h / (n_dot_h * n_dot_v);
+        let fresnel = (1.0 - v_dot_h).powi(5);
+        scale += (1.0 - fresnel) * visibility;
+        bias += fresnel * visibility;
+    }
+    (scale / samples as f32, bias / samples as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BrdfLut, Environment, Irradiance, Specular};
+    use cgl_math::Vec3;
+    use cubemap::CubeMap;
+    use image::HdrColor;
+
+    fn close(a: HdrColor, b: HdrColor, tolerance: f32) -> bool {
+        (a.r - b.r).abs() < tolerance && (a.g - b.g).abs() < tolerance &&
+            (a.b - b.b).abs() < tolerance
+    }
+
+    #[test]
+    fn uniform_environment_stays_uniform() {
+        // In a furnace, everything comes out the color of the furnace
+        let color = HdrColor::rgb(0.5, 1.0, 2.0);
+        let furnace = CubeMap::from_fn(8, |_| color);
+        let irradiance = Irradiance::new(&furnace);
+        let specular = Specular::new(&furnace, 4, 16);
+        for &dir in &[Vec3(0.0, 1.0, 0.0), Vec3(0.3, -0.2, 0.9), Vec3(-1.0, 0.0, 0.0)] {
+            assert!(close(irradiance.sample(dir), color, 1e-3), "{:?}", irradiance.sample(dir));
+            for &roughness in &[0.0, 0.4, 1.0] {
+                let sample = specular.sample(dir, roughness);
+                assert!(close(sample, color, 1e-3), "{:?} at {}", sample, roughness);
+            }
+        }
+        assert_eq!(specular.levels().iter().map(|l| l.size()).collect::<Vec<_>>(),
+                   vec![8, 4, 2, 1]);
+    }
+
+    #[test]
+    fn irradiance_follows_the_light() {
+        // Light only coming from above
+        let sky = CubeMap::from_fn(16, |dir| {
+            HdrColor::gray(if dir.1 > 0.0 { 1.0 } else { 0.0 })
+        });
+        let irradiance = Irradiance::new(&sky);
+        // Facing straight up sees the whole sky, and facing sideways sees half
+        // of it
+        assert!((irradiance.sample(Vec3(0.0, 1.0, 0.0)).r - 1.0).abs() < 0.05);
+        assert!((irradiance.sample(Vec3(1.0, 0.0, 0.0)).r - 0.5).abs() < 0.05);
+        assert!(irradiance.sample(Vec3(0.0, -1.0, 0.0)).r < 0.05);
+    }
+
+    #[test]
+    fn rougher_levels_are_blurrier() {
+        // A single bright spot straight ahead
+        let spot = CubeMap::from_fn(16, |dir| {
+            HdrColor::gray(if dir.normalized().2 > 0.99 { 100.0 } else { 0.0 })
+        });
+        let specular = Specular::new(&spot, 5, 64);
+        let ahead = Vec3(0.0, 0.0, 1.0);
+        let aside = Vec3(0.5, 0.0, 1.0);
+        assert!(specular.sample(ahead, 0.25).r > specular.sample(ahead, 1.0).r);
+        assert!(specular.sample(aside, 0.0).r < specular.sample(aside, 0.75).r);
+    }
+
+    #[test]
+    fn brdf_never_adds_energy() {
+        let lut = BrdfLut::new(8, 64);
+        for y in 0..8 {
+            for x in 0..8 {
+                let total = lut.scale[(x, y)] + lut.bias[(x, y)];
+                assert!(total > 0.0 && total <= 1.01, "{} at ({}, {})", total, x, y);
+            }
+        }
+        // Smooth surfaces looked at head-on reflect everything they're given
+        let (scale, bias) = lut.sample(1.0, 0.0);
+        assert!(scale > 0.95 && bias < 0.05, "{} {}", scale, bias);
+        // And everything is reflected at grazing angles
+        let (scale, bias) = lut.sample(0.05, 0.1);
+        assert!(bias > scale, "{} {}", scale, bias);
+    }
+
+    #[test]
+    fn environment_combines_the_parts() {
+        let furnace = CubeMap::from_fn(4, |_| HdrColor::gray(1.0));
+        let environment = Environment::new(&furnace);
+        let normal = Vec3(0.0, 1.0, 0.0);
+        assert!(close(environment.diffuse(normal), HdrColor::gray(1.0), 1e-3));
+        // A smooth mirror looked at head-on reflects the whole furnace
+        let mirror = environment.specular(normal, normal, Vec3(1.0, 1.0, 1.0), 0.0);
+        assert!(close(mirror, HdrColor::gray(1.0), 0.05), "{:?}", mirror);
+    }
+}
@@ -2,9 +2,9 @@
 
 //! Data structures for representing images
 
-use cgl_math::{Vec2, Vec3, barycentric, saturate};
+use cgl_math::{Vec2, Vec3, Edges, saturate};
 
-use std::ops::{Add, Mul, Index, IndexMut};
+use std::ops::{Add, AddAssign, Mul, Index, IndexMut};
 use std;
 
 /// A type representing a 24 bit pixel
@@ -41,6 +41,22 @@ impl Color {
         }
         Color::rgb(map(r), map(g), map(b))
     }
+
+    /// Decode this sRGB-encoded color into linear light
+    pub fn to_linear(self) -> HdrColor {
+        HdrColor::rgb(srgb_to_linear(self.r as f32 / 255.0),
+                      srgb_to_linear(self.g as f32 / 255.0),
+                      srgb_to_linear(self.b as f32 / 255.0))
+    }
+
+    /// Encode a linear color with the sRGB transfer curve, clamping values
+    /// outside of [0, 1].
+    pub fn from_linear(color: HdrColor) -> Self {
+        fn encode(c: f32) -> u8 {
+            (linear_to_srgb(saturate(c)) * 255.0).round() as u8
+        }
+        Color::rgb(encode(color.r), encode(color.g), encode(color.b))
+    }
 }
 
 impl Add for Color {
@@ -92,6 +108,130 @@ impl Mul for Color {
     }
 }
 
+/// A linear floating point RGB pixel.
+///
+/// Unlike `Color`, components aren't limited to [0, 1], so lighting can be
+/// accumulated without clipping. Render into an `Image<HdrColor>` and then use
+/// one of the operators in the [`tonemap`] module to turn the result into an
+/// `Image<Color>` for display.
+///
+/// [`tonemap`]: ../tonemap/index.html
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+#[allow(missing_docs)]
+pub struct HdrColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl HdrColor {
+    /// Initializes a color with the given RGB values
+    pub fn rgb(r: f32, g: f32, b: f32) -> Self { HdrColor { r, g, b } }
+    /// A color with all components equal to `value`
+    pub fn gray(value: f32) -> Self { HdrColor::rgb(value, value, value) }
+    /// No light at all
+    pub fn black() -> Self { Default::default() }
+}
+
+impl Add for HdrColor {
+    type Output = Self;
+    fn add(self, other: HdrColor) -> HdrColor {
+        HdrColor::rgb(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for HdrColor {
+    fn add_assign(&mut self, other: HdrColor) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<f32> for HdrColor {
+    type Output = Self;
+    fn mul(self, other: f32) -> HdrColor {
+        HdrColor::rgb(self.r * other, self.g * other, self.b * other)
+    }
+}
+
+impl Mul for HdrColor {
+    type Output = Self;
+    /// Multiplies each component separately, e.g. for tinting a surface by
+    /// the color of a light.
+    fn mul(self, other: HdrColor) -> HdrColor {
+        HdrColor::rgb(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+/// A pixel type that can be converted to and from floating point linear RGB.
+///
+/// This lets image processing code do its math in floating point without
+/// caring how the pixels are actually stored. `Color` is treated as being
+/// sRGB-encoded, so it's decoded on the way in and re-encoded on the way out,
+/// clamping any values outside of [0, 1].
+pub trait Pixel: Copy + Default {
+    /// Convert the pixel into RGB components
+    fn to_rgb(self) -> Vec3<f32>;
+    /// Convert RGB components into a pixel
+    fn from_rgb(rgb: Vec3<f32>) -> Self;
+}
+
+impl Pixel for Color {
+    fn to_rgb(self) -> Vec3<f32> {
+        let c = self.to_linear();
+        Vec3(c.r, c.g, c.b)
+    }
+
+    fn from_rgb(rgb: Vec3<f32>) -> Self {
+        Color::from_linear(HdrColor::rgb(rgb.0, rgb.1, rgb.2))
+    }
+}
+
+impl Pixel for HdrColor {
+    fn to_rgb(self) -> Vec3<f32> { Vec3(self.r, self.g, self.b) }
+    fn from_rgb(rgb: Vec3<f32>) -> Self { HdrColor::rgb(rgb.0, rgb.1, rgb.2) }
+}
+
+impl Pixel for Vec3<f32> {
+    fn to_rgb(self) -> Vec3<f32> { self }
+    fn from_rgb(rgb: Vec3<f32>) -> Self { rgb }
+}
+
+/// A single `f32` is treated as a grayscale value
+impl Pixel for f32 {
+    fn to_rgb(self) -> Vec3<f32> { Vec3(self, self, self) }
+    fn from_rgb(rgb: Vec3<f32>) -> Self { luminance(rgb) }
+}
+
+/// The perceived brightness of a linear RGB color, using the Rec. 709
+/// coefficients.
+pub fn luminance(rgb: Vec3<f32>) -> f32 {
+    rgb.dot(Vec3(0.2126, 0.7152, 0.0722))
+}
+
+/// Decode a single sRGB-encoded component in [0, 1] into linear light.
+///
+/// Image files and displays use the sRGB transfer curve so that more of the
+/// 256 levels of a byte go to dark values, where our eyes are more sensitive.
+/// Lighting and filtering math is only correct on linear values, though, so
+/// colors should be decoded before doing any math on them.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a single linear component in [0, 1] with the sRGB transfer curve.
+/// This is the inverse of `srgb_to_linear`.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 /// A mutable buffer for storing and editing pixel data
 #[derive(Clone)]
 #[allow(missing_docs)]
@@ -141,6 +281,13 @@ impl<Pix> Image<Pix> where Pix: Clone + Copy + Default {
         }
     }
 
+    /// Set every pixel to `value`, keeping the same allocation
+    pub fn fill(&mut self, value: Pix) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = value;
+        }
+    }
+
     /// A view of the image data as bytes
     pub fn bytes(&self) -> &[u8] {
         let start = &self.pixels[0] as *const _ as *const u8;
@@ -209,14 +356,20 @@ impl<Pix> Image<Pix> where Pix: Clone + Copy + Default {
         let x1 = max(0, min(max(t0.0, max(t1.0, t2.0)), (self.width - 1) as isize));
         let y0 = max(0, min(min(t0.1, min(t1.1, t2.1)), (self.height - 1) as isize));
         let y1 = max(0, min(max(t0.1, max(t1.1, t2.1)), (self.height - 1) as isize));
-        for x in (x0..x1).chain(Some(x1)) {
-            for y in (y0..y1).chain(Some(y1)) {
-                let bc_screen = barycentric((t0, t1, t2), Vec2(x, y));
-                if bc_screen.0 < 0.0 || bc_screen.1 < 0.0 || bc_screen.2 < 0.0 {
-                    continue;
+        let edges = match Edges::new(t0, t1, t2) {
+            Some(edges) => edges,
+            None => return,
+        };
+        let mut row = edges.at(Vec2(x0, y0));
+        for y in y0..y1 + 1 {
+            let mut e = row;
+            for x in x0..x1 + 1 {
+                if e.0 >= 0 && e.1 >= 0 && e.2 >= 0 {
+                    self[(x as usize, y as usize)] = color;
                 }
-                self[(x as usize, y as usize)] = color;
+                e = e + edges.step_x();
             }
+            row = row + edges.step_y();
         }
     }
 
@@ -244,18 +397,47 @@ impl<Pix> Image<Pix> where Pix: Clone + Copy + Default {
         let y0 = max(0, min(min(s0.1, min(s1.1, s2.1)), (self.height - 1) as isize));
         let y1 = max(0, min(max(s0.1, max(s1.1, s2.1)), (self.height - 1) as isize));
 
-        for x in (x0..x1).chain(Some(x1)) {
-            for y in (y0..y1).chain(Some(y1)) {
-                let bc_screen = barycentric((s0, s1, s2), Vec2(x, y));
-                if bc_screen.0 < 0.0 || bc_screen.1 < 0.0 || bc_screen.2 < 0.0 {
-                    continue;
-                }
-                let z = t0.2 * bc_screen.0 + t1.2 * bc_screen.1 + t2.2 * bc_screen.2;
-                if zbuf[(x as usize, y as usize)] < z {
-                    zbuf[(x as usize, y as usize)] = z;
-                    self[(x as usize, y as usize)] = color;
+        let edges = match Edges::new(s0, s1, s2) {
+            Some(edges) => edges,
+            None => return,
+        };
+        let zs = Vec3(t0.2, t1.2, t2.2) / edges.area as f32;
+        let mut row = edges.at(Vec2(x0, y0));
+        for y in y0..y1 + 1 {
+            let mut e = row;
+            for x in x0..x1 + 1 {
+                if e.0 >= 0 && e.1 >= 0 && e.2 >= 0 {
+                    let z = Vec3(e.0 as f32, e.1 as f32, e.2 as f32).dot(zs);
+                    if zbuf[(x as usize, y as usize)] < z {
+                        zbuf[(x as usize, y as usize)] = z;
+                        self[(x as usize, y as usize)] = color;
+                    }
                 }
+                e = e + edges.step_x();
             }
+            row = row + edges.step_y();
+        }
+    }
+}
+
+impl<Pix> Image<Pix> where Pix: Copy {
+    /// Create a new image of the same size by applying `f` to every pixel.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use cgl::{Color, Image};
+    /// let zbuf = Image::filled(4, 4, 0.5f32);
+    /// let gray = zbuf.map(|z| Color::float_rgb(z, z, z));
+    /// ```
+    pub fn map<F, Out>(&self, f: F) -> Image<Out>
+        where F: FnMut(Pix) -> Out
+    {
+        Image {
+            pixels: self.pixels.iter().cloned().map(f)
+                .collect::<Vec<_>>().into_boxed_slice(),
+            width: self.width,
+            height: self.height,
         }
     }
 }
@@ -267,6 +449,12 @@ impl<Pix> Image<Pix> where Pix: Mul<f32, Output=Pix> + Add<Output=Pix> + Copy {
     /// and (1, 1) at the upper right corner. Points outside this range will be
     /// sampled as if they were taken from the nearest point on the texture.
     /// Samples are bilinearly interpolated.
+    ///
+    /// The interpolation happens directly on the stored values, which is wrong
+    /// for sRGB-encoded `Color`s. Use a [`Texture`] to filter those in linear
+    /// space instead.
+    ///
+    /// [`Texture`]: ../texture/struct.Texture.html
     pub fn sample_clamp(&self, u: f32, v: f32) -> Pix {
         let u = saturate(u);
         let v = 1.0 - saturate(v);
@@ -278,9 +466,9 @@ impl<Pix> Image<Pix> where Pix: Mul<f32, Output=Pix> + Add<Output=Pix> + Copy {
         let y0 = y.floor() as usize;
         let y1 = y.ceil() as usize;
         let y = y.fract();
-        let top = self[(x0, y0)] * x + self[(x1, y0)] * (1.0 - x);
-        let bot = self[(x0, y1)] * x + self[(x1, y1)] * (1.0 - x);
-        top * y + bot * (1.0 - y)
+        let top = self[(x0, y0)] * (1.0 - x) + self[(x1, y0)] * x;
+        let bot = self[(x0, y1)] * (1.0 - x) + self[(x1, y1)] * x;
+        top * (1.0 - y) + bot * y
     }
 }
 
@@ -330,6 +518,15 @@ mod tests {
         assert_eq!(im[(0, 0)], Color::white());
     }
 
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..256 {
+            let c = Color::rgb(i as u8, 0, 255 - i as u8);
+            assert_eq!(Color::from_linear(c.to_linear()), c);
+        }
+        assert!((Color::rgb(128, 128, 128).to_linear().r - 0.2158).abs() < 1e-3);
+    }
+
     #[test]
     fn draw_line() {
         let mut im = Image::with_dimensions(4, 4);
@@ -354,4 +551,16 @@ mod tests {
             assert_eq!(im[(1, y)], Color::black())
         }
     }
+
+    #[test]
+    fn sample_clamp_weights_nearest_texel() {
+        let im = Image::with_pixels(2, 1, &[1.0f32, 0.0]);
+        assert_eq!(im.sample_clamp(0.0, 0.5), 1.0);
+        assert_eq!(im.sample_clamp(0.25, 0.5), 0.75);
+        assert_eq!(im.sample_clamp(1.0, 0.5), 0.0);
+
+        let im = Image::with_pixels(1, 2, &[1.0f32, 0.0]);
+        // v runs upwards, so the bottom row is at v = 0
+        assert_eq!(im.sample_clamp(0.5, 0.25), 0.25);
+    }
 }
@@ -26,6 +26,12 @@
 //!   will make an effort to support are features that I actually end up using
 //!   in my demos.
 //!
+//! # Command line
+//!
+//! The `cgl-render` binary renders an OBJ to a BMP without writing any code,
+//! e.g. `cgl-render head.obj --diffuse diffuse.bmp --shading textured -o
+//! head.bmp`. Run it with `--help` for the options.
+//!
 //! # Examples
 //!
 //! ```rust,no_run
@@ -69,11 +75,38 @@ pub mod bmp;
 pub mod image;
 pub mod renderer;
 pub mod shader;
+pub mod light;
+pub mod phong;
+pub mod pbr;
+pub mod deferred;
+pub mod post;
+pub mod accumulate;
+pub mod fog;
+pub mod tonemap;
+pub mod texture;
+pub mod cubemap;
+pub mod background;
+pub mod ibl;
+pub mod tessellation;
+pub mod camera;
+pub mod stereo;
+pub mod controller;
+pub mod scene;
+pub mod sequence;
+pub mod skeleton;
+pub mod morph;
 
 pub use obj::Obj;
-pub use model::{Model, Vertex, Vert, TanVert};
+pub use model::{Model, Vertex, MeshVertex, Vert, TanVert};
 pub use bmp::{read_bmp, write_bmp};
-pub use cgl_math::{Vec2, Vec3, Vec4, Mat2, Mat3, Mat4};
-pub use image::{Image, Color};
-pub use renderer::Renderer;
-pub use shader::Shader;
+pub use cgl_math::{Vec2, Vec3, Vec4, Mat2, Mat3, Mat4, Quat};
+pub use image::{Image, Color, HdrColor, Pixel};
+pub use texture::{Texture, Encoding};
+pub use cubemap::CubeMap;
+pub use background::Background;
+pub use renderer::{Renderer, Rasterizer, Pick};
+pub use shader::{Shader, Primitive};
+pub use light::{Light, DirectionalLight, PointLight, SpotLight};
+pub use camera::{Camera, Lens, Ray, Frustum};
+pub use scene::{Scene, Node, NodeId, SceneUniform};
+pub use deferred::{DeferredRenderer, GBuffer, GBufferMaterial, Surface};
@@ -0,0 +1,183 @@
+//! Light sources that can be shared between shading passes.
+//!
+//! Each kind of light has its own type, and [`Light`] can hold any of them so
+//! that a shader can take a list of mixed lights and ask each one how much
+//! light reaches a point.
+//!
+//! [`Light`]: enum.Light.html
+
+use cgl_math::{Vec3, Vec4, Mat4, saturate};
+use image::HdrColor;
+
+/// A light so far away that it shines in the same direction everywhere, like
+/// the sun.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    /// The direction the light is traveling in
+    pub direction: Vec3<f32>,
+    pub color: HdrColor,
+    pub intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3<f32>, color: HdrColor, intensity: f32) -> Self {
+        DirectionalLight { direction: direction.normalized(), color, intensity }
+    }
+}
+
+/// A light that shines in every direction from a single point, and which has
+/// no effect beyond `radius`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3<f32>,
+    pub color: HdrColor,
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3<f32>, color: HdrColor, intensity: f32, radius: f32)
+               -> Self
+    {
+        PointLight { position, color, intensity, radius }
+    }
+
+    /// How much of the light's intensity reaches a point `distance` away.
+    ///
+    /// This is inverse-square falloff, windowed so that it smoothly reaches
+    /// zero at `radius` instead of continuing forever. Cutting the light off
+    /// is what lets a renderer skip every pixel outside of the light's bounds.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        falloff(distance, self.radius)
+    }
+}
+
+/// Inverse-square falloff, windowed to reach zero at `radius`
+fn falloff(distance: f32, radius: f32) -> f32 {
+    if distance >= radius {
+        return 0.0;
+    }
+    let window = 1.0 - (distance / radius).powi(4);
+    window * window / (distance * distance + 1.0)
+}
+
+/// A point light that only shines in a cone around `direction`.
+///
+/// The light is at full strength within `inner` radians of the middle of the
+/// cone, and fades out smoothly to nothing at `outer` radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Vec3<f32>,
+    /// The direction the middle of the cone points in
+    pub direction: Vec3<f32>,
+    pub color: HdrColor,
+    pub intensity: f32,
+    pub radius: f32,
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl SpotLight {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(position: Vec3<f32>, direction: Vec3<f32>, color: HdrColor, intensity: f32,
+               radius: f32, inner: f32, outer: f32)
+               -> Self
+    {
+        SpotLight { position, direction: direction.normalized(), color, intensity, radius,
+                    inner, outer }
+    }
+
+    /// How much of the light's intensity reaches `point`, falling off with
+    /// distance like a `PointLight` and with the angle from the middle of the
+    /// cone.
+    pub fn attenuation(&self, point: Vec3<f32>) -> f32 {
+        let offset = point - self.position;
+        let distance = offset.len() as f32;
+        let falloff = falloff(distance, self.radius);
+        if falloff <= 0.0 || distance == 0.0 {
+            return falloff;
+        }
+        let cos_angle = (offset / distance).dot(self.direction);
+        let (cos_inner, cos_outer) = (self.inner.cos(), self.outer.cos());
+        let t = saturate((cos_angle - cos_outer) / (cos_inner - cos_outer).max(1e-6));
+        falloff * t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Any kind of light
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+/// The light arriving at a point from a single light
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Incident {
+    /// The normalized direction from the point towards the light
+    pub direction: Vec3<f32>,
+    /// The color and strength of the light, including its attenuation
+    pub radiance: HdrColor,
+}
+
+impl Light {
+    /// The light arriving at `point`, or `None` if none of it gets there.
+    pub fn incident(&self, point: Vec3<f32>) -> Option<Incident> {
+        let (direction, radiance) = match *self {
+            Light::Directional(light) => {
+                (-light.direction, light.color * light.intensity)
+            }
+            Light::Point(light) => {
+                let to_light = light.position - point;
+                let attenuation = light.attenuation(to_light.len() as f32);
+                (to_light, light.color * (light.intensity * attenuation))
+            }
+            Light::Spot(light) => {
+                let attenuation = light.attenuation(point);
+                (light.position - point, light.color * (light.intensity * attenuation))
+            }
+        };
+        if radiance == HdrColor::black() || direction.len2() == 0.0 {
+            None
+        } else {
+            Some(Incident { direction: direction.normalized(), radiance })
+        }
+    }
+
+    /// The same light, moved by `matrix`
+    pub fn transformed(&self, matrix: &Mat4<f32>) -> Light {
+        let point = |p: Vec3<f32>| (*matrix * p.augment()).retro_project();
+        let direction = |d: Vec3<f32>| {
+            let d = *matrix * Vec4(d.0, d.1, d.2, 0.0);
+            Vec3(d.0, d.1, d.2).normalized()
+        };
+        match *self {
+            Light::Directional(light) => Light::Directional(DirectionalLight {
+                direction: direction(light.direction),
+                ..light
+            }),
+            Light::Point(light) => Light::Point(PointLight {
+                position: point(light.position),
+                ..light
+            }),
+            Light::Spot(light) => Light::Spot(SpotLight {
+                position: point(light.position),
+                direction: direction(light.direction),
+                ..light
+            }),
+        }
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Light { Light::Directional(light) }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Light { Light::Point(light) }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Light { Light::Spot(light) }
+}
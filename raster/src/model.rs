@@ -19,6 +19,15 @@ impl Vertex for Vec2<f32> {
     }
 }
 
+/// Pairs of vertices are interpolated component-wise, which allows extra
+/// attributes to be carried along with a vertex through rasterization.
+impl<A: Vertex, B: Vertex> Vertex for (A, B) {
+    fn interpolate(x: Vec3<f32>, t0: &Self, t1: &Self, t2: &Self) -> Self {
+        (Vertex::interpolate(x, &t0.0, &t1.0, &t2.0),
+         Vertex::interpolate(x, &t0.1, &t1.1, &t2.1))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Vert {
     pub pos: Vec3<f32>,
@@ -58,6 +67,36 @@ impl Vertex for TanVert {
 }
 
 
+/// A vertex with a position, normal, and texture coordinates, like the ones
+/// loaded from an OBJ file.
+///
+/// This lets code that moves vertices around, like the evaluation stage of a
+/// tessellator, work on any kind of model vertex without having to know about
+/// its other attributes.
+pub trait MeshVertex: Vertex + Copy {
+    fn pos(&self) -> Vec3<f32>;
+    fn pos_mut(&mut self) -> &mut Vec3<f32>;
+    fn norm(&self) -> Vec3<f32>;
+    fn norm_mut(&mut self) -> &mut Vec3<f32>;
+    fn tex(&self) -> Vec2<f32>;
+}
+
+impl MeshVertex for Vert {
+    fn pos(&self) -> Vec3<f32> { self.pos }
+    fn pos_mut(&mut self) -> &mut Vec3<f32> { &mut self.pos }
+    fn norm(&self) -> Vec3<f32> { self.norm }
+    fn norm_mut(&mut self) -> &mut Vec3<f32> { &mut self.norm }
+    fn tex(&self) -> Vec2<f32> { self.tex }
+}
+
+impl MeshVertex for TanVert {
+    fn pos(&self) -> Vec3<f32> { self.pos }
+    fn pos_mut(&mut self) -> &mut Vec3<f32> { &mut self.pos }
+    fn norm(&self) -> Vec3<f32> { self.norm }
+    fn norm_mut(&mut self) -> &mut Vec3<f32> { &mut self.norm }
+    fn tex(&self) -> Vec2<f32> { self.tex }
+}
+
 // Model ///////////////////////////////////////////////////////////////////////
 
 #[derive(Debug)]
@@ -66,6 +105,30 @@ pub struct Model<V: Vertex> {
     pub triangles: Vec<[usize; 3]>,
 }
 
+impl<V: MeshVertex> Model<V> {
+    /// A sphere containing every vertex of the model, as its center and
+    /// radius.
+    ///
+    /// The sphere is centered on the model's bounding box, so it isn't always
+    /// the smallest one possible, but it's never far off.
+    pub fn bounding_sphere(&self) -> (Vec3<f32>, f32) {
+        let mut positions = self.vertices.iter().map(MeshVertex::pos);
+        let first = match positions.next() {
+            Some(pos) => pos,
+            None => return (Vec3(0.0, 0.0, 0.0), 0.0),
+        };
+        let (min, max) = positions.fold((first, first), |(min, max), p| {
+            (Vec3(min.0.min(p.0), min.1.min(p.1), min.2.min(p.2)),
+             Vec3(max.0.max(p.0), max.1.max(p.1), max.2.max(p.2)))
+        });
+        let center = (min + max) * 0.5;
+        let radius = self.vertices.iter()
+            .map(|vert| (vert.pos() - center).len() as f32)
+            .fold(0.0, f32::max);
+        (center, radius)
+    }
+}
+
 pub fn compute_tangent_space(model: Model<Vert>) -> Model<TanVert> {
     let mut triangle_tangent_bundles = vec![vec![]; model.vertices.len()];
     let mut triangle_bitangent_bundles = vec![vec![]; model.vertices.len()];
@@ -0,0 +1,237 @@
+//! Morph targets, also known as blend shapes, for animating a mesh by blending
+//! between versions of it.
+//!
+//! A [`MorphModel`] holds a base model along with any number of targets, each
+//! of which is the same mesh with its vertices moved, like a face smiling or
+//! blinking. Each target is stored as how far every vertex moved from the
+//! base, so they can be mixed together in any amounts with `blend`.
+//!
+//! Targets are usually modeled by editing a copy of the base mesh and saving
+//! it as a separate OBJ file, so they can be loaded with
+//! `MorphModel::open_objs` as long as every file has the same faces.
+//!
+//! [`MorphModel`]: struct.MorphModel.html
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+use cgl_math::Vec3;
+use model::{Model, Vert};
+use obj::{self, Obj};
+
+/// How each vertex of a model moves to get to a target
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphTarget {
+    pub name: String,
+    pub positions: Vec<Vec3<f32>>,
+    pub normals: Vec<Vec3<f32>>,
+}
+
+impl MorphTarget {
+    /// The target that moves `base` to `target`.
+    ///
+    /// Returns an error unless both models have the same triangles.
+    pub fn between(name: &str, base: &Model<Vert>, target: &Model<Vert>) -> Result<Self, Error> {
+        if base.vertices.len() != target.vertices.len() || base.triangles != target.triangles {
+            return Err(Error::Topology(name.to_owned()));
+        }
+        let (positions, normals) = base.vertices.iter().zip(&target.vertices)
+            .map(|(b, t)| (t.pos - b.pos, t.norm - b.norm))
+            .unzip();
+        Ok(MorphTarget { name: name.to_owned(), positions, normals })
+    }
+}
+
+/// A model along with targets it can be morphed towards
+#[derive(Debug)]
+pub struct MorphModel {
+    pub base: Model<Vert>,
+    pub targets: Vec<MorphTarget>,
+}
+
+impl MorphModel {
+    /// A model with no targets yet
+    pub fn new(base: Model<Vert>) -> Self {
+        MorphModel { base, targets: Vec::new() }
+    }
+
+    /// Add a target that morphs the base model into `target`, and return its
+    /// index.
+    ///
+    /// Returns an error unless `target` has the same triangles as the base.
+    pub fn add_target(&mut self, name: &str, target: &Model<Vert>) -> Result<usize, Error> {
+        self.targets.push(MorphTarget::between(name, &self.base, target)?);
+        Ok(self.targets.len() - 1)
+    }
+
+    /// Build a model from a base OBJ and a target OBJ for each name.
+    ///
+    /// Returns an error if any OBJ can't be turned into a model, or if a
+    /// target doesn't have exactly the same faces as the base.
+    pub fn from_objs(base: &Obj, targets: &[(&str, &Obj)]) -> Result<Self, Error> {
+        let mut model = MorphModel::new(base.model().map_err(Error::Model)?);
+        for &(name, target) in targets {
+            if target.components != base.components {
+                return Err(Error::Topology(name.to_owned()));
+            }
+            model.add_target(name, &target.model().map_err(Error::Model)?)?;
+        }
+        Ok(model)
+    }
+
+    /// Load a model from a base OBJ file and a series of target OBJ files, as
+    /// in `from_objs`. Each target is named after its file, without the
+    /// extension.
+    pub fn open_objs<P: AsRef<Path>>(base: P, targets: &[P]) -> Result<Self, Error> {
+        let base = Obj::from_file(base)?;
+        let mut objs = Vec::with_capacity(targets.len());
+        for path in targets {
+            let path = path.as_ref();
+            let name = path.file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+            objs.push((name, Obj::from_file(path)?));
+        }
+        let named: Vec<_> = objs.iter().map(|(name, obj)| (&name[..], obj)).collect();
+        MorphModel::from_objs(&base, &named)
+    }
+
+    /// The index of the first target called `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.targets.iter().position(|target| target.name == name)
+    }
+
+    /// The base model with each target mixed in by the weight at the same
+    /// index. A weight of 1 moves all the way to a target, and targets without
+    /// a weight aren't used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more weights than targets.
+    pub fn blend(&self, weights: &[f32]) -> Model<Vert> {
+        let mut vertices = Vec::with_capacity(self.base.vertices.len());
+        self.blend_into(weights, &mut vertices);
+        Model { vertices, triangles: self.base.triangles.clone() }
+    }
+
+    /// The same as `blend`, but replacing the contents of `vertices` instead
+    /// of making a new model, so the buffer can be reused from frame to frame.
+    /// The triangles are the same as the base model's.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more weights than targets.
+    pub fn blend_into(&self, weights: &[f32], vertices: &mut Vec<Vert>) {
+        assert!(weights.len() <= self.targets.len(),
+                "Got {} weights for {} targets", weights.len(), self.targets.len());
+        vertices.clear();
+        vertices.extend_from_slice(&self.base.vertices);
+        for (target, &weight) in self.targets.iter().zip(weights) {
+            if weight == 0.0 {
+                continue;
+            }
+            for (i, vert) in vertices.iter_mut().enumerate() {
+                vert.pos = vert.pos + target.positions[i] * weight;
+                vert.norm = vert.norm + target.normals[i] * weight;
+            }
+        }
+        for vert in vertices.iter_mut() {
+            if vert.norm.len2() > 0.0 {
+                vert.norm = vert.norm.normalized();
+            }
+        }
+    }
+}
+
+/// Errors from building a `MorphModel`
+#[derive(Debug)]
+pub enum Error {
+    /// An OBJ file couldn't be read
+    Obj(obj::Error),
+    /// An OBJ couldn't be turned into a model
+    Model(String),
+    /// The named target doesn't have the same faces as the base model
+    Topology(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Obj(ref err) => write!(fmt, "{}", err),
+            Error::Model(ref err) => write!(fmt, "Model error: {}", err),
+            Error::Topology(ref name) => {
+                write!(fmt, "Morph target {:?} doesn't have the same faces as the base", name)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Obj(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<obj::Error> for Error {
+    fn from(error: obj::Error) -> Self { Error::Obj(error) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, MorphModel};
+    use cgl_math::Vec3;
+    use obj::Obj;
+
+    const FACES: &str = "vn 0 0 1\nf 1//1 2//1 3//1\n";
+
+    fn triangle(top: f32) -> Obj {
+        Obj::from_str(&format!("v 0 0 0\nv 1 0 0\nv 0 {} 0\n{}", top, FACES)).unwrap()
+    }
+
+    #[test]
+    fn blends_targets() {
+        let base = triangle(1.0);
+        let tall = triangle(3.0);
+        let morph = MorphModel::from_objs(&base, &[("tall", &tall)]).unwrap();
+        assert_eq!(morph.find("tall"), Some(0));
+        assert_eq!(morph.targets[0].positions[2], Vec3(0.0, 2.0, 0.0));
+
+        let half = morph.blend(&[0.5]);
+        assert_eq!(half.vertices[2].pos, Vec3(0.0, 2.0, 0.0));
+        assert_eq!(half.vertices[0].pos, Vec3(0.0, 0.0, 0.0));
+        assert_eq!(half.triangles, morph.base.triangles);
+
+        // No weights is the base model
+        let mut vertices = Vec::new();
+        morph.blend_into(&[], &mut vertices);
+        assert_eq!(vertices[2].pos, Vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn weights_add_up() {
+        let base = triangle(1.0);
+        let tilted = Obj::from_str("v 0 0 0\nv 1 0 1\nv 0 1 0\nvn 0 0 1\n\
+                                    f 1//1 2//1 3//1\n").unwrap();
+        let morph = MorphModel::from_objs(&base, &[("tall", &triangle(2.0)),
+                                                   ("tilted", &tilted)]).unwrap();
+        let both = morph.blend(&[1.0, 0.5]);
+        assert_eq!(both.vertices[2].pos, Vec3(0.0, 2.0, 0.0));
+        assert_eq!(both.vertices[1].pos, Vec3(1.0, 0.0, 0.5));
+        // Going past a target exaggerates it
+        assert_eq!(morph.blend(&[2.0]).vertices[2].pos, Vec3(0.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn targets_need_the_same_faces() {
+        let base = triangle(1.0);
+        let flipped = Obj::from_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\n\
+                                     f 1//1 3//1 2//1\n").unwrap();
+        match MorphModel::from_objs(&base, &[("flipped", &flipped)]) {
+            Err(Error::Topology(ref name)) if name == "flipped" => {}
+            other => panic!("Should be a topology error, not {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -252,7 +252,7 @@ pub fn normalize_indices(i: VertexIndex, vert: usize, tex: usize, norm: usize)
 #[cfg(test)]
 mod test {
     use super::{Obj, VertexIndex, normalize_indices};
-    use math::Vec3;
+    use cgl_math::Vec3;
 
     #[test]
     fn read_vertex() {
@@ -0,0 +1,385 @@
+//! A physically based shader for metallic-roughness materials, like the ones
+//! exported by most PBR asset pipelines.
+//!
+//! Light is reflected with the Cook-Torrance model: the GGX (Trowbridge-Reitz)
+//! normal distribution, Smith's shadowing with the Schlick-GGX approximation,
+//! and Schlick's approximation of the Fresnel effect. Whatever light the
+//! specular reflection doesn't take is left for Lambertian diffuse, so a
+//! surface never reflects more light than arrives at it.
+//!
+//! Because the diffuse term is divided by π, a white surface facing a light of
+//! intensity 1 head-on comes out at about 1/π, rather than the 1 you'd get from
+//! the [Blinn-Phong shaders](../phong/index.html). Lights need to be around π
+//! times brighter to look the same.
+//!
+//! Besides the lights in the uniform, a material can be lit by a whole
+//! environment through [image-based lighting](../ibl/index.html).
+
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use cgl_math::{Vec3, Vec4, saturate};
+use ibl::Environment;
+use image::{HdrColor, Pixel};
+use model::TanVert;
+use scene::SceneUniform;
+use shader::Shader;
+use texture::Texture;
+
+/// Roughness is kept above this, since a perfectly smooth surface would only
+/// reflect point lights from a single direction, which no pixel would hit
+const MIN_ROUGHNESS: f32 = 0.045;
+
+/// How much light a dielectric reflects when looked at head-on
+const DIELECTRIC_REFLECTANCE: f32 = 0.04;
+
+/// A metallic-roughness material.
+///
+/// Each property is a constant factor, multiplied by a texture if there is
+/// one. The textures use the same channels as glTF: roughness is in the green
+/// channel and metalness in the blue channel of `metallic_roughness_texture`,
+/// and occlusion is in the red channel of `occlusion_texture`. Only the base
+/// color and emissive textures should have sRGB encoding.
+#[derive(Clone)]
+pub struct Material {
+    pub base_color: HdrColor,
+    pub base_color_texture: Option<Texture>,
+    /// 0 for dielectrics like plastic or wood, and 1 for metals
+    pub metallic: f32,
+    /// 0 for a perfect mirror, up to 1 for something completely matte
+    pub roughness: f32,
+    pub metallic_roughness_texture: Option<Texture>,
+    /// A tangent-space normal map, with each channel going from -1 at 0 to 1
+    /// at 255
+    pub normal_texture: Option<Texture>,
+    /// How much of the ambient light reaches each point, from 0 to 1
+    pub occlusion_texture: Option<Texture>,
+    /// Light given off by the surface itself
+    pub emissive: HdrColor,
+    pub emissive_texture: Option<Texture>,
+    /// The light arriving from every direction at once, which is reflected
+    /// by the base color and darkened by the occlusion
+    pub ambient: HdrColor,
+    /// An environment lighting the surface, which replaces `ambient` when
+    /// there is one
+    pub environment: Option<Rc<Environment>>,
+}
+
+impl Material {
+    /// An untextured material
+    pub fn new(base_color: HdrColor, metallic: f32, roughness: f32) -> Self {
+        Material {
+            base_color,
+            base_color_texture: None,
+            metallic,
+            roughness,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive: HdrColor::black(),
+            emissive_texture: None,
+            ambient: HdrColor::gray(0.03),
+            environment: None,
+        }
+    }
+}
+
+impl Default for Material {
+    /// A white, fairly rough dielectric
+    fn default() -> Self {
+        Material::new(HdrColor::gray(1.0), 0.0, 0.5)
+    }
+}
+
+/// The properties of a material at a single point, after sampling its
+/// textures
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    base_color: Vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion: f32,
+    emissive: Vec3<f32>,
+}
+
+impl Material {
+    fn sample(&self, u: f32, v: f32) -> Sample {
+        let texture = |texture: &Option<Texture>| {
+            texture.as_ref().map_or(Vec3(1.0, 1.0, 1.0), |t| t.sample_clamp(u, v).to_rgb())
+        };
+        let metallic_roughness = texture(&self.metallic_roughness_texture);
+        Sample {
+            base_color: self.base_color.to_rgb() * texture(&self.base_color_texture),
+            metallic: saturate(self.metallic * metallic_roughness.2),
+            roughness: saturate(self.roughness * metallic_roughness.1).max(MIN_ROUGHNESS),
+            occlusion: texture(&self.occlusion_texture).0,
+            emissive: self.emissive.to_rgb() * texture(&self.emissive_texture),
+        }
+    }
+}
+
+/// The GGX normal distribution: how many microfacets face along the half
+/// vector, for a squared roughness of `a2`
+fn distribution(n_dot_h: f32, a2: f32) -> f32 {
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// Smith's shadowing and masking with the Schlick-GGX approximation: how many
+/// of those microfacets can see both the light and the eye
+fn geometry(n_dot_l: f32, n_dot_v: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let g1 = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+    g1(n_dot_l) * g1(n_dot_v)
+}
+
+/// Schlick's approximation of Fresnel reflectance, starting at `f0` head-on
+fn fresnel(h_dot_v: f32, f0: Vec3<f32>) -> Vec3<f32> {
+    let t = (1.0 - h_dot_v).max(0.0).powi(5);
+    f0 + (Vec3(1.0, 1.0, 1.0) - f0) * t
+}
+
+/// Schlick's approximation for light arriving from every direction at once,
+/// which doesn't get as bright at grazing angles on rough surfaces
+fn fresnel_roughness(n_dot_v: f32, f0: Vec3<f32>, roughness: f32) -> Vec3<f32> {
+    let t = (1.0 - n_dot_v).max(0.0).powi(5);
+    let max = Vec3(1.0 - roughness, 1.0 - roughness, 1.0 - roughness);
+    let grazing = Vec3(max.0.max(f0.0), max.1.max(f0.1), max.2.max(f0.2));
+    f0 + (grazing - f0) * t
+}
+
+/// The light reflected towards the eye from a point at `pos` facing `normal`
+fn reflect(sample: &Sample, material: &Material, pos: Vec3<f32>, normal: Vec3<f32>,
+           uniform: &SceneUniform)
+           -> Vec3<f32>
+{
+    let normal = normal.normalized();
+    let view = (uniform.eye - pos).normalized();
+    let n_dot_v = normal.dot(view).max(1e-4);
+    let dielectric = Vec3(DIELECTRIC_REFLECTANCE, DIELECTRIC_REFLECTANCE, DIELECTRIC_REFLECTANCE);
+    let f0 = dielectric * (1.0 - sample.metallic) + sample.base_color * sample.metallic;
+    let a = sample.roughness * sample.roughness;
+
+    let ambient = match material.environment {
+        Some(ref environment) => {
+            let f = fresnel_roughness(n_dot_v, f0, sample.roughness);
+            let diffuse = (Vec3(1.0, 1.0, 1.0) - f) * (1.0 - sample.metallic) *
+                sample.base_color * environment.diffuse(normal).to_rgb();
+            diffuse + environment.specular(normal, view, f0, sample.roughness).to_rgb()
+        }
+        None => material.ambient.to_rgb() * sample.base_color,
+    };
+    let mut color = ambient * sample.occlusion + sample.emissive;
+    for light in &uniform.lights {
+        let incident = match light.incident(pos) {
+            Some(incident) => incident,
+            None => continue,
+        };
+        let l = incident.direction;
+        let n_dot_l = normal.dot(l);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        let h = (l + view).normalized();
+        let f = fresnel(h.dot(view), f0);
+        let specular = f * (distribution(normal.dot(h).max(0.0), a * a) *
+                            geometry(n_dot_l, n_dot_v, sample.roughness) /
+                            (4.0 * n_dot_l * n_dot_v));
+        // Metals don't have any diffuse reflection, and what the specular
+        // reflection takes can't be diffusely reflected as well
+        let diffuse = (Vec3(1.0, 1.0, 1.0) - f) * (1.0 - sample.metallic) *
+            sample.base_color / PI;
+        color = color + (diffuse + specular) * incident.radiance.to_rgb() * n_dot_l;
+    }
+    color
+}
+
+/// Shades a `Material` with Cook-Torrance lighting from the lights in the
+/// uniform.
+///
+/// It needs a tangent space for the normal map, which can be added to a model
+/// with `model::compute_tangent_space`.
+#[derive(Clone, Default)]
+pub struct CookTorrance(pub Material);
+
+impl<P: Pixel> Shader<TanVert, SceneUniform, P> for CookTorrance {
+    type VOut = TanVert;
+
+    fn vertex(&self, vert: TanVert, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> TanVert {
+        *pos = uniform.project(vert.pos);
+        TanVert {
+            pos: uniform.world_position(vert.pos),
+            tex: vert.tex,
+            norm: uniform.world_normal(vert.norm),
+            tan: uniform.world_direction(vert.tan),
+            bitan: uniform.world_direction(vert.bitan),
+        }
+    }
+
+    fn fragment(&self, vert: TanVert, uniform: &SceneUniform) -> P {
+        let material = &self.0;
+        let (u, v) = (vert.tex.0, vert.tex.1);
+        let normal = match material.normal_texture {
+            Some(ref texture) => {
+                let t = texture.sample_clamp(u, v).to_rgb() * 2.0 - Vec3(1.0, 1.0, 1.0);
+                vert.tan * t.0 + vert.bitan * t.1 + vert.norm * t.2
+            }
+            None => vert.norm,
+        };
+        let sample = material.sample(u, v);
+        P::from_rgb(reflect(&sample, material, vert.pos, normal, uniform))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CookTorrance, Material};
+    use cgl_math::{Vec2, Vec3, Vec4, Mat4};
+    use cubemap::CubeMap;
+    use ibl::Environment;
+    use image::{Color, HdrColor, Image};
+    use light::DirectionalLight;
+    use model::TanVert;
+    use scene::SceneUniform;
+    use shader::Shader;
+    use texture::Texture;
+    use std::f32::consts::PI;
+    use std::rc::Rc;
+
+    /// The color of a surface at the origin facing the eye, lit by a white
+    /// light shining along `direction`
+    fn shade_from(material: &Material, direction: Vec3<f32>) -> HdrColor {
+        let light = DirectionalLight::new(direction, HdrColor::gray(1.0), 1.0);
+        let view = Mat4::translate(Vec3(0.0, 0.0, -2.0));
+        let uniform = SceneUniform::new(Mat4::identity(), view, Mat4::identity(),
+                                        vec![light.into()]);
+        let vert = TanVert {
+            pos: Vec3(0.0, 0.0, 0.0),
+            tex: Vec2(0.5, 0.5),
+            norm: Vec3(0.0, 0.0, 1.0),
+            tan: Vec3(1.0, 0.0, 0.0),
+            bitan: Vec3(0.0, 1.0, 0.0),
+        };
+        let shader: &dyn Shader<TanVert, SceneUniform, HdrColor, VOut = TanVert> =
+            &CookTorrance(material.clone());
+        let mut pos = Vec4(0.0, 0.0, 0.0, 0.0);
+        let out = shader.vertex(vert, &uniform, &mut pos);
+        shader.fragment(out, &uniform)
+    }
+
+    /// The color of a surface lit head-on
+    fn shade(material: Material) -> HdrColor {
+        shade_from(&material, Vec3(0.0, 0.0, -1.0))
+    }
+
+    fn unlit(mut material: Material) -> Material {
+        material.ambient = HdrColor::black();
+        material
+    }
+
+    fn flat(color: Color) -> Texture {
+        Texture::linear(Image::filled(1, 1, color))
+    }
+
+    #[test]
+    fn conserves_energy() {
+        // Add up the light reflected towards the eye from lights all over the
+        // hemisphere, which can't be more than what arrives
+        let steps = 64;
+        let d = PI / 2.0 / steps as f32;
+        for &roughness in &[0.5, 1.0] {
+            for &metallic in &[0.0, 1.0] {
+                let material = unlit(Material::new(HdrColor::gray(1.0), metallic, roughness));
+                let mut total = 0.0;
+                for i in 0..steps {
+                    let theta = (i as f32 + 0.5) * d;
+                    for j in 0..steps * 4 {
+                        let phi = (j as f32 + 0.5) * d;
+                        let to_light = Vec3(theta.sin() * phi.cos(), theta.sin() * phi.sin(),
+                                            theta.cos());
+                        let color = shade_from(&material, Vec3(0.0, 0.0, 0.0) - to_light);
+                        total += color.r * theta.sin() * d * d;
+                    }
+                }
+                assert!(total > 0.0 && total <= 1.01,
+                        "{} reflected at roughness {} and metalness {}", total, roughness,
+                        metallic);
+            }
+        }
+
+        // A white matte surface is mostly diffuse, at about 1/π
+        let color = shade(unlit(Material::new(HdrColor::gray(1.0), 0.0, 1.0)));
+        assert!((color.r - 1.0 / PI).abs() < 0.05, "{:?}", color);
+    }
+
+    #[test]
+    fn metals_tint_reflections() {
+        // Metals only reflect specularly, tinted by their base color
+        let gold = shade(unlit(Material::new(HdrColor::rgb(1.0, 0.8, 0.3), 1.0, 0.5)));
+        assert!(gold.r > gold.g && gold.g > gold.b, "{:?}", gold);
+        let black = shade(unlit(Material::new(HdrColor::black(), 1.0, 0.5)));
+        assert_eq!(black, HdrColor::black());
+
+        // Smoother surfaces concentrate the highlight
+        let smooth = shade(unlit(Material::new(HdrColor::gray(1.0), 1.0, 0.2)));
+        let rough = shade(unlit(Material::new(HdrColor::gray(1.0), 1.0, 0.8)));
+        assert!(smooth.r > rough.r);
+    }
+
+    #[test]
+    fn samples_textures() {
+        let base = Material::new(HdrColor::gray(1.0), 1.0, 1.0);
+        // Turning the metalness down in the texture brings the diffuse back
+        let dielectric = Material {
+            metallic_roughness_texture: Some(flat(Color::rgb(0, 255, 0))),
+            ..unlit(base.clone())
+        };
+        assert!(shade(dielectric).r > shade(unlit(base.clone())).r);
+
+        let emissive = Material {
+            emissive: HdrColor::gray(1.0),
+            emissive_texture: Some(flat(Color::rgb(0, 0, 255))),
+            ..Material::new(HdrColor::black(), 1.0, 1.0)
+        };
+        let glow = shade(unlit(emissive));
+        assert_eq!((glow.r, glow.g), (0.0, 0.0));
+        assert!(glow.b > 0.99);
+
+        let ambient_only = Material::new(HdrColor::gray(1.0), 1.0, 1.0);
+        let lit = shade(Material { base_color: HdrColor::black(), ..ambient_only.clone() });
+        assert_eq!(lit, HdrColor::black());
+        let open = shade(ambient_only.clone());
+        let occluded = shade(Material {
+            occlusion_texture: Some(flat(Color::rgb(0, 0, 0))),
+            ..ambient_only
+        });
+        assert!(occluded.r < open.r);
+    }
+
+    #[test]
+    fn lit_by_environment() {
+        // A white furnace lights everything evenly, so nothing can come out
+        // brighter than it. Rough metals lose quite a bit, since light that
+        // bounces between microfacets more than once isn't accounted for.
+        let furnace = CubeMap::from_fn(8, |_| HdrColor::gray(1.0));
+        let environment = Rc::new(Environment::new(&furnace));
+        let lit = |base_color, metallic, roughness| {
+            let material = Material {
+                environment: Some(environment.clone()),
+                ..Material::new(base_color, metallic, roughness)
+            };
+            shade_from(&material, Vec3(0.0, 0.0, 1.0))
+        };
+        for &roughness in &[0.1, 0.5, 1.0] {
+            for &metallic in &[0.0, 1.0] {
+                let color = lit(HdrColor::gray(1.0), metallic, roughness);
+                assert!(color.r > 0.25 && color.r <= 1.01,
+                        "{:?} at roughness {} and metalness {}", color, roughness, metallic);
+            }
+        }
+        // The environment replaces the ambient color, and a black dielectric
+        // only has its specular reflection left
+        let black = lit(HdrColor::black(), 0.0, 0.5);
+        assert!(black.r > 0.0 && black.r < 0.1, "{:?}", black);
+    }
+}
@@ -0,0 +1,351 @@
+//! Ready-made shaders for the classic lighting models, lit by a list of
+//! [lights](../light/enum.Light.html).
+//!
+//! Every shader here takes a [`SceneUniform`], so they can be used on meshes
+//! in a [`Scene`] or on their own with `SceneUniform::new`, and they can draw
+//! into any kind of [`Pixel`].
+//!
+//! - [`Gouraud`] lights each vertex and blends the colors in between, which is
+//!   cheap but loses highlights in the middle of large triangles.
+//! - [`Phong`] lights each pixel, with highlights around the reflection of the
+//!   light.
+//! - [`BlinnPhong`] lights each pixel, with highlights from the half vector
+//!   between the light and the eye. It's usually what you want.
+//! - [`Textured`] is Blinn-Phong with diffuse and specular textures.
+//! - [`NormalMapped`] is `Textured` with a normal map as well, for models with
+//!   a tangent space.
+//!
+//! [`SceneUniform`]: ../scene/struct.SceneUniform.html
+//! [`Scene`]: ../scene/struct.Scene.html
+//! [`Pixel`]: ../image/trait.Pixel.html
+//! [`Gouraud`]: struct.Gouraud.html
+//! [`Phong`]: struct.Phong.html
+//! [`BlinnPhong`]: struct.BlinnPhong.html
+//! [`Textured`]: struct.Textured.html
+//! [`NormalMapped`]: struct.NormalMapped.html
+
+use cgl_math::{Vec3, Vec4};
+use image::{HdrColor, Pixel};
+use model::{MeshVertex, TanVert, Vert};
+use scene::SceneUniform;
+use shader::Shader;
+use texture::Texture;
+
+/// The colors a surface reflects, in linear RGB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// The light arriving from every direction at once, standing in for light
+    /// that has bounced around the scene. It's reflected by the diffuse color.
+    pub ambient: HdrColor,
+    pub diffuse: HdrColor,
+    pub specular: HdrColor,
+    /// The specular exponent, larger values give tighter highlights
+    pub shininess: f32,
+}
+
+impl Material {
+    pub fn new(diffuse: HdrColor, specular: HdrColor, shininess: f32) -> Self {
+        Material { ambient: HdrColor::gray(0.05), diffuse, specular, shininess }
+    }
+}
+
+impl Default for Material {
+    /// A light gray plastic
+    fn default() -> Self {
+        Material::new(HdrColor::gray(0.8), HdrColor::gray(0.3), 32.0)
+    }
+}
+
+/// How the specular highlight is worked out
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Highlight {
+    Phong,
+    Blinn,
+}
+
+/// The light reflected towards `eye` from a surface at `pos` facing `normal`,
+/// with the diffuse color replaced by `albedo` and the specular color scaled
+/// by `specular`.
+fn reflect(highlight: Highlight, material: &Material, albedo: HdrColor, specular: f32,
+           pos: Vec3<f32>, normal: Vec3<f32>, uniform: &SceneUniform)
+           -> HdrColor
+{
+    let normal = normal.normalized();
+    let view = (uniform.eye - pos).normalized();
+    let mut color = material.ambient * albedo;
+    for light in &uniform.lights {
+        let incident = match light.incident(pos) {
+            Some(incident) => incident,
+            None => continue,
+        };
+        let l = incident.direction;
+        let diffuse = normal.dot(l);
+        if diffuse <= 0.0 {
+            continue;
+        }
+        let highlight = match highlight {
+            Highlight::Phong => (normal * (2.0 * diffuse) - l).dot(view),
+            Highlight::Blinn => normal.dot((l + view).normalized()),
+        };
+        let highlight = highlight.max(0.0).powf(material.shininess) * specular;
+        color += (albedo * diffuse + material.specular * highlight) * incident.radiance;
+    }
+    color
+}
+
+/// The vertex stage shared by the per-pixel shaders, which moves the
+/// position and normal into world space
+fn world_vertex<V: MeshVertex>(vert: V, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vert {
+    *pos = uniform.project(vert.pos());
+    Vert {
+        pos: uniform.world_position(vert.pos()),
+        tex: vert.tex(),
+        norm: uniform.world_normal(vert.norm()),
+    }
+}
+
+/// Lights each vertex with Blinn-Phong, and blends the colors across the
+/// triangle
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Gouraud(pub Material);
+
+impl<V: MeshVertex, P: Pixel> Shader<V, SceneUniform, P> for Gouraud {
+    type VOut = Vec3<f32>;
+
+    fn vertex(&self, vert: V, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vec3<f32> {
+        let world = world_vertex(vert, uniform, pos);
+        let color = reflect(Highlight::Blinn, &self.0, self.0.diffuse, 1.0,
+                            world.pos, world.norm, uniform);
+        color.to_rgb()
+    }
+
+    fn fragment(&self, color: Vec3<f32>, _: &SceneUniform) -> P {
+        P::from_rgb(color)
+    }
+}
+
+/// Lights each pixel, with highlights around the mirror reflection of each
+/// light
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Phong(pub Material);
+
+impl<V: MeshVertex, P: Pixel> Shader<V, SceneUniform, P> for Phong {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: V, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vert {
+        world_vertex(vert, uniform, pos)
+    }
+
+    fn fragment(&self, vert: Vert, uniform: &SceneUniform) -> P {
+        P::from_rgb(reflect(Highlight::Phong, &self.0, self.0.diffuse, 1.0,
+                            vert.pos, vert.norm, uniform).to_rgb())
+    }
+}
+
+/// Lights each pixel, with highlights where the surface faces halfway between
+/// each light and the eye
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BlinnPhong(pub Material);
+
+impl<V: MeshVertex, P: Pixel> Shader<V, SceneUniform, P> for BlinnPhong {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: V, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vert {
+        world_vertex(vert, uniform, pos)
+    }
+
+    fn fragment(&self, vert: Vert, uniform: &SceneUniform) -> P {
+        P::from_rgb(reflect(Highlight::Blinn, &self.0, self.0.diffuse, 1.0,
+                            vert.pos, vert.norm, uniform).to_rgb())
+    }
+}
+
+/// Blinn-Phong with the surface's colors read from textures.
+///
+/// The diffuse texture is multiplied by the material's diffuse color, and the
+/// specular texture's red channel scales the material's specular color.
+pub struct Textured {
+    pub material: Material,
+    pub diffuse: Texture,
+    pub specular: Option<Texture>,
+}
+
+impl Textured {
+    pub fn new(material: Material, diffuse: Texture) -> Self {
+        Textured { material, diffuse, specular: None }
+    }
+
+    pub fn with_specular(self, specular: Texture) -> Self {
+        Textured { specular: Some(specular), ..self }
+    }
+}
+
+impl<V: MeshVertex, P: Pixel> Shader<V, SceneUniform, P> for Textured {
+    type VOut = Vert;
+
+    fn vertex(&self, vert: V, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> Vert {
+        world_vertex(vert, uniform, pos)
+    }
+
+    fn fragment(&self, vert: Vert, uniform: &SceneUniform) -> P {
+        let (u, v) = (vert.tex.0, vert.tex.1);
+        let albedo = self.diffuse.sample_clamp(u, v) * self.material.diffuse;
+        let specular = self.specular.as_ref().map_or(1.0, |s| s.sample_clamp(u, v).r);
+        P::from_rgb(reflect(Highlight::Blinn, &self.material, albedo, specular,
+                            vert.pos, vert.norm, uniform).to_rgb())
+    }
+}
+
+/// Blinn-Phong with the surface's details read from textures.
+///
+/// The diffuse texture is multiplied by the material's diffuse color, and the
+/// specular texture's red channel scales the material's specular color. The
+/// normal map is in tangent space, with each channel going from -1 at 0 to 1
+/// at 255, so it should be a `Texture` with linear encoding.
+pub struct NormalMapped {
+    pub material: Material,
+    pub diffuse: Texture,
+    pub normal: Texture,
+    pub specular: Option<Texture>,
+}
+
+impl NormalMapped {
+    pub fn new(material: Material, diffuse: Texture, normal: Texture) -> Self {
+        NormalMapped { material, diffuse, normal, specular: None }
+    }
+
+    pub fn with_specular(self, specular: Texture) -> Self {
+        NormalMapped { specular: Some(specular), ..self }
+    }
+}
+
+impl<P: Pixel> Shader<TanVert, SceneUniform, P> for NormalMapped {
+    type VOut = TanVert;
+
+    fn vertex(&self, vert: TanVert, uniform: &SceneUniform, pos: &mut Vec4<f32>) -> TanVert {
+        *pos = uniform.project(vert.pos);
+        TanVert {
+            pos: uniform.world_position(vert.pos),
+            tex: vert.tex,
+            norm: uniform.world_normal(vert.norm),
+            tan: uniform.world_direction(vert.tan),
+            bitan: uniform.world_direction(vert.bitan),
+        }
+    }
+
+    fn fragment(&self, vert: TanVert, uniform: &SceneUniform) -> P {
+        let (u, v) = (vert.tex.0, vert.tex.1);
+        let t = self.normal.sample_clamp(u, v).to_rgb() * 2.0 - Vec3(1.0, 1.0, 1.0);
+        let normal = vert.tan * t.0 + vert.bitan * t.1 + vert.norm * t.2;
+        let albedo = self.diffuse.sample_clamp(u, v) * self.material.diffuse;
+        let specular = self.specular.as_ref().map_or(1.0, |s| s.sample_clamp(u, v).r);
+        P::from_rgb(reflect(Highlight::Blinn, &self.material, albedo, specular,
+                            vert.pos, normal, uniform).to_rgb())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlinnPhong, Gouraud, Material, Phong, Textured};
+    use cgl_math::{Vec2, Vec3, Vec4, Mat4};
+    use image::{Color, HdrColor, Image};
+    use light::{DirectionalLight, Light, PointLight, SpotLight};
+    use model::Vert;
+    use scene::SceneUniform;
+    use shader::Shader;
+    use texture::Texture;
+
+    fn uniform(lights: Vec<Light>) -> SceneUniform {
+        let view = Mat4::translate(Vec3(0.0, 0.0, -2.0));
+        SceneUniform::new(Mat4::identity(), view, Mat4::identity(), lights)
+    }
+
+    /// The color of a surface at the origin facing the eye, through `shader`
+    fn shade<S>(shader: &S, lights: Vec<Light>) -> HdrColor
+        where S: Shader<Vert, SceneUniform, HdrColor>
+    {
+        let uniform = uniform(lights);
+        let vert = Vert {
+            pos: Vec3(0.0, 0.0, 0.0),
+            tex: Vec2(0.0, 0.0),
+            norm: Vec3(0.0, 0.0, 1.0),
+        };
+        let mut pos = Vec4(0.0, 0.0, 0.0, 0.0);
+        let out = shader.vertex(vert, &uniform, &mut pos);
+        shader.fragment(out, &uniform)
+    }
+
+    #[test]
+    fn lights_face_the_surface() {
+        let material = Material {
+            ambient: HdrColor::black(),
+            specular: HdrColor::black(),
+            ..Material::default()
+        };
+        let shader = BlinnPhong(material);
+        let head_on = DirectionalLight::new(Vec3(0.0, 0.0, -1.0), HdrColor::gray(1.0), 1.0);
+        assert_eq!(shade(&shader, vec![head_on.into()]), HdrColor::gray(0.8));
+
+        let behind = DirectionalLight::new(Vec3(0.0, 0.0, 1.0), HdrColor::gray(1.0), 1.0);
+        assert_eq!(shade(&shader, vec![behind.into()]), HdrColor::black());
+
+        let both = vec![head_on.into(), head_on.into()];
+        assert_eq!(shade(&shader, both), HdrColor::gray(1.6));
+
+        let far = PointLight::new(Vec3(0.0, 0.0, 5.0), HdrColor::gray(1.0), 1.0, 4.0);
+        assert_eq!(shade(&shader, vec![far.into()]), HdrColor::black());
+
+        let spot = |direction| SpotLight::new(Vec3(0.0, 0.0, 1.0), direction,
+                                              HdrColor::gray(1.0), 1.0, 10.0,
+                                              0.2, 0.4);
+        let lit = shade(&shader, vec![spot(Vec3(0.0, 0.0, -1.0)).into()]);
+        assert!(lit.r > 0.0);
+        let missed = shade(&shader, vec![spot(Vec3(1.0, 0.0, -1.0)).into()]);
+        assert_eq!(missed, HdrColor::black());
+    }
+
+    #[test]
+    fn highlights_match_head_on() {
+        // With the light behind the eye, all three models agree
+        let light = DirectionalLight::new(Vec3(0.0, 0.0, -1.0), HdrColor::gray(1.0), 1.0);
+        let material = Material::default();
+        let phong = shade(&Phong(material), vec![light.into()]);
+        let blinn = shade(&BlinnPhong(material), vec![light.into()]);
+        let gouraud = shade(&Gouraud(material), vec![light.into()]);
+        let expected = material.ambient * material.diffuse + material.diffuse +
+            material.specular;
+        for color in &[phong, blinn, gouraud] {
+            assert!((color.r - expected.r).abs() < 1e-5, "{:?} != {:?}", color, expected);
+        }
+
+        // Off to the side, Blinn-Phong's highlight is wider
+        let light = DirectionalLight::new(Vec3(-1.0, 0.0, -1.0), HdrColor::gray(1.0), 1.0);
+        let phong = shade(&Phong(material), vec![light.into()]);
+        let blinn = shade(&BlinnPhong(material), vec![light.into()]);
+        assert!(blinn.r > phong.r);
+    }
+
+    #[test]
+    fn textures_color_the_surface() {
+        let material = Material {
+            ambient: HdrColor::black(),
+            specular: HdrColor::black(),
+            ..Material::default()
+        };
+        let light = DirectionalLight::new(Vec3(0.0, 0.0, -1.0), HdrColor::gray(1.0), 1.0);
+        let texture = |color| Texture::linear(Image::filled(1, 1, color));
+        let plain = shade(&BlinnPhong(material), vec![light.into()]);
+        let white = shade(&Textured::new(material, texture(Color::white())),
+                          vec![light.into()]);
+        assert_eq!(white, plain);
+        let red = shade(&Textured::new(material, texture(Color::red())),
+                        vec![light.into()]);
+        assert_eq!(red, HdrColor::rgb(0.8, 0.0, 0.0));
+
+        // The specular texture scales the highlight
+        let material = Material { specular: HdrColor::gray(1.0), ..material };
+        let dull = Textured::new(material, texture(Color::white()))
+            .with_specular(texture(Color::black()));
+        assert_eq!(shade(&dull, vec![light.into()]), HdrColor::gray(0.8));
+    }
+}
@@ -0,0 +1,520 @@
+//! Full-screen post-processing passes.
+//!
+//! A post-processing [`Pass`] takes a finished image along with its depth
+//! buffer and produces a new image. Since passes only need an `Image` and an
+//! `Image<f32>`, they can be run on the output of [`Renderer`] as well as
+//! [`DeferredRenderer`], or on any other image. Passes can be strung together
+//! into a [`Chain`], which is itself a pass.
+//!
+//! All the math happens in floating point through the [`Pixel`] trait, so the
+//! same passes work for any pixel type.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! # use cgl::Renderer;
+//! use cgl::post::{Chain, Pass, Bloom, Vignette, Fxaa};
+//!
+//! # let renderer: Renderer = Renderer::with_dimensions(512, 512);
+//! let post = Chain::new()
+//!     .then(Bloom::new(0.8, 0.6))
+//!     .then(Fxaa::default())
+//!     .then(Vignette::new(0.5));
+//! let image = post.apply(renderer.image(), renderer.depth());
+//! ```
+//!
+//! [`Pass`]: trait.Pass.html
+//! [`Chain`]: struct.Chain.html
+//! [`Renderer`]: ../renderer/struct.Renderer.html
+//! [`DeferredRenderer`]: ../deferred/struct.DeferredRenderer.html
+//! [`Pixel`]: ../image/trait.Pixel.html
+
+use cgl_math::{Vec3, saturate};
+use image::{Image, Color, Pixel, luminance};
+
+/// A full-screen image operation
+pub trait Pass<P> {
+    /// Run the pass over `image`, whose depth buffer is `depth`
+    fn apply(&self, image: &Image<P>, depth: &Image<f32>) -> Image<P>;
+}
+
+/// A sequence of passes that are applied one after the other
+pub struct Chain<P> {
+    passes: Vec<Box<dyn Pass<P>>>,
+}
+
+impl<P> Chain<P> {
+    pub fn new() -> Self {
+        Chain { passes: Vec::new() }
+    }
+
+    /// Add `pass` to the end of the chain
+    pub fn then<T>(mut self, pass: T) -> Self
+        where T: Pass<P> + 'static
+    {
+        self.passes.push(Box::new(pass));
+        self
+    }
+}
+
+impl<P> Default for Chain<P> {
+    fn default() -> Self { Chain::new() }
+}
+
+impl<P> Pass<P> for Chain<P> where P: Clone {
+    fn apply(&self, image: &Image<P>, depth: &Image<f32>) -> Image<P> {
+        self.passes.iter().fold(image.clone(), |image, pass| pass.apply(&image, depth))
+    }
+}
+
+
+// Blur ////////////////////////////////////////////////////////////////////////
+
+/// A separable gaussian blur
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaussianBlur {
+    weights: Vec<f32>,
+}
+
+impl GaussianBlur {
+    /// A blur with standard deviation `sigma` in pixels. The kernel extends
+    /// out to three standard deviations from the center.
+    pub fn new(sigma: f32) -> Self {
+        let radius = (sigma * 3.0).ceil().max(1.0) as isize;
+        let weights = (-radius..radius + 1)
+            .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect::<Vec<_>>();
+        let total: f32 = weights.iter().sum();
+        GaussianBlur { weights: weights.into_iter().map(|w| w / total).collect() }
+    }
+
+    fn radius(&self) -> isize { (self.weights.len() / 2) as isize }
+
+    fn blur(&self, image: &Image<Vec3<f32>>) -> Image<Vec3<f32>> {
+        let horizontal = self.blur_1d(image, (1, 0));
+        self.blur_1d(&horizontal, (0, 1))
+    }
+
+    fn blur_1d(&self, image: &Image<Vec3<f32>>, (dx, dy): (isize, isize))
+               -> Image<Vec3<f32>>
+    {
+        let mut out = Image::with_dimensions(image.width, image.height);
+        let radius = self.radius();
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let mut sum = Vec3(0.0, 0.0, 0.0);
+                for (i, &w) in (-radius..radius + 1).zip(&self.weights) {
+                    sum = sum + fetch(image, x as isize + i * dx, y as isize + i * dy) * w;
+                }
+                out[(x, y)] = sum;
+            }
+        }
+        out
+    }
+}
+
+impl<P> Pass<P> for GaussianBlur where P: Pixel {
+    fn apply(&self, image: &Image<P>, _: &Image<f32>) -> Image<P> {
+        self.blur(&image.map(Pixel::to_rgb)).map(Pixel::from_rgb)
+    }
+}
+
+
+// Bloom ///////////////////////////////////////////////////////////////////////
+
+/// Makes bright parts of the image glow by blurring them and adding them back
+/// on top of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bloom {
+    /// The luminance above which pixels start to glow
+    pub threshold: f32,
+    /// How much of the glow is added back to the image
+    pub intensity: f32,
+    pub blur: GaussianBlur,
+}
+
+impl Bloom {
+    pub fn new(threshold: f32, intensity: f32) -> Self {
+        Bloom { threshold, intensity, blur: GaussianBlur::new(4.0) }
+    }
+}
+
+impl<P> Pass<P> for Bloom where P: Pixel {
+    fn apply(&self, image: &Image<P>, _: &Image<f32>) -> Image<P> {
+        let image = image.map(Pixel::to_rgb);
+        let bright = image.map(|c| {
+            let luma = luminance(c);
+            if luma > self.threshold {
+                c * ((luma - self.threshold) / luma)
+            } else {
+                Vec3(0.0, 0.0, 0.0)
+            }
+        });
+        let glow = self.blur.blur(&bright);
+
+        let mut out = Image::with_dimensions(image.width, image.height);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                out[(x, y)] = P::from_rgb(image[(x, y)] + glow[(x, y)] * self.intensity);
+            }
+        }
+        out
+    }
+}
+
+
+// Vignette ////////////////////////////////////////////////////////////////////
+
+/// Darkens the corners of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    /// How dark the corners get, from 0 to 1
+    pub strength: f32,
+    /// How far from the center the darkening starts, where 1 is the corners
+    pub radius: f32,
+    /// How far the darkening takes to reach full strength
+    pub softness: f32,
+}
+
+impl Vignette {
+    pub fn new(strength: f32) -> Self {
+        Vignette { strength, radius: 0.75, softness: 0.5 }
+    }
+}
+
+impl<P> Pass<P> for Vignette where P: Pixel {
+    fn apply(&self, image: &Image<P>, _: &Image<f32>) -> Image<P> {
+        let (cx, cy) = (image.width as f32 / 2.0, image.height as f32 / 2.0);
+        let mut out = Image::with_dimensions(image.width, image.height);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let dx = (x as f32 + 0.5 - cx) / cx;
+                let dy = (y as f32 + 0.5 - cy) / cy;
+                let dist = ((dx * dx + dy * dy) / 2.0).sqrt();
+                let edge = smoothstep(self.radius - self.softness, self.radius, dist);
+                out[(x, y)] = P::from_rgb(image[(x, y)].to_rgb() *
+                                          (1.0 - self.strength * edge));
+            }
+        }
+        out
+    }
+}
+
+
+// Color Grading ///////////////////////////////////////////////////////////////
+
+/// A 3D color lookup table, mapping each input color to an output color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    table: Vec<Vec3<f32>>,
+}
+
+impl Lut3d {
+    /// A `size`×`size`×`size` table that maps every color to itself
+    pub fn identity(size: usize) -> Self {
+        Lut3d::from_fn(size, |rgb| rgb)
+    }
+
+    /// A `size`×`size`×`size` table built by evaluating `f` at each entry.
+    pub fn from_fn<F>(size: usize, mut f: F) -> Self
+        where F: FnMut(Vec3<f32>) -> Vec3<f32>
+    {
+        assert!(size >= 2, "A LUT needs at least two entries per axis");
+        let scale = 1.0 / (size - 1) as f32;
+        let mut table = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push(f(Vec3(r as f32, g as f32, b as f32) * scale));
+                }
+            }
+        }
+        Lut3d { size, table }
+    }
+
+    /// Load a table from an image in the common "strip" layout.
+    ///
+    /// The image is `size` pixels tall and `size * size` pixels wide, made of
+    /// `size` square slices side by side. Blue increases from one slice to the
+    /// next, red increases to the right within a slice, and green increases
+    /// downwards. This means that a LUT can be made by color grading a
+    /// screenshot of `Lut3d::identity(size).to_image()` in an image editor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image isn't in the strip layout.
+    pub fn from_image<P: Pixel>(image: &Image<P>) -> Self {
+        let size = image.height;
+        assert_eq!(image.width, size * size, "A LUT image should be size² by size");
+        let mut table = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push(image[(b * size + r, g)].to_rgb());
+                }
+            }
+        }
+        Lut3d { size, table }
+    }
+
+    /// Lay the table out as an image in the strip layout described in
+    /// `from_image`.
+    pub fn to_image<P: Pixel>(&self) -> Image<P> {
+        let size = self.size;
+        let mut image = Image::with_dimensions(size * size, size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    image[(b * size + r, g)] = P::from_rgb(self.entry(r, g, b));
+                }
+            }
+        }
+        image
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> Vec3<f32> {
+        self.table[(b * self.size + g) * self.size + r]
+    }
+
+    /// Look up a color, trilinearly interpolating between table entries.
+    pub fn sample(&self, color: Vec3<f32>) -> Vec3<f32> {
+        let max = (self.size - 1) as f32;
+        let split = |c: f32| {
+            let c = saturate(c) * max;
+            let i = (c.floor() as usize).min(self.size - 2);
+            (i, c - i as f32)
+        };
+        let (r, fr) = split(color.0);
+        let (g, fg) = split(color.1);
+        let (b, fb) = split(color.2);
+        let lerp = |a: Vec3<f32>, b: Vec3<f32>, t: f32| a * (1.0 - t) + b * t;
+        let plane = |b| {
+            lerp(lerp(self.entry(r, g, b), self.entry(r + 1, g, b), fr),
+                 lerp(self.entry(r, g + 1, b), self.entry(r + 1, g + 1, b), fr),
+                 fg)
+        };
+        lerp(plane(b), plane(b + 1), fb)
+    }
+}
+
+/// Remaps every color in the image through a 3D lookup table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGrade {
+    pub lut: Lut3d,
+}
+
+impl<P> Pass<P> for ColorGrade where P: Pixel {
+    fn apply(&self, image: &Image<P>, _: &Image<f32>) -> Image<P> {
+        image.map(|c| P::from_rgb(self.lut.sample(c.to_rgb())))
+    }
+}
+
+
+// Anti-aliasing ///////////////////////////////////////////////////////////////
+
+/// Fast approximate anti-aliasing.
+///
+/// This finds edges by looking at the contrast in luminance around each
+/// pixel, and then blurs along the direction of the edge. It's cheap and
+/// doesn't need any extra information from the renderer, but it will also
+/// soften some detail in textures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fxaa {
+    /// The longest distance in pixels that will be blurred along an edge
+    pub span_max: f32,
+    /// Limits how much the search direction can be scaled up in dark areas
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Fxaa { span_max: 8.0, reduce_mul: 1.0 / 8.0, reduce_min: 1.0 / 128.0 }
+    }
+}
+
+impl<P> Pass<P> for Fxaa where P: Pixel {
+    fn apply(&self, image: &Image<P>, _: &Image<f32>) -> Image<P> {
+        let rgb = image.map(Pixel::to_rgb);
+        let luma = |x: f32, y: f32| luminance(sample(&rgb, x, y));
+        let mut out = Image::with_dimensions(image.width, image.height);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (fx, fy) = (x as f32, y as f32);
+                let nw = luma(fx - 1.0, fy - 1.0);
+                let ne = luma(fx + 1.0, fy - 1.0);
+                let sw = luma(fx - 1.0, fy + 1.0);
+                let se = luma(fx + 1.0, fy + 1.0);
+                let m = luminance(rgb[(x, y)]);
+                let luma_min = m.min(nw.min(ne).min(sw.min(se)));
+                let luma_max = m.max(nw.max(ne).max(sw.max(se)));
+
+                // The direction along the edge is perpendicular to the
+                // gradient in luminance.
+                let dir_x = -((nw + ne) - (sw + se));
+                let dir_y = (nw + sw) - (ne + se);
+                let reduce = ((nw + ne + sw + se) * 0.25 * self.reduce_mul)
+                    .max(self.reduce_min);
+                let scale = 1.0 / (dir_x.abs().min(dir_y.abs()) + reduce);
+                let clamp = |d: f32| (d * scale).max(-self.span_max).min(self.span_max);
+                let (dir_x, dir_y) = (clamp(dir_x), clamp(dir_y));
+
+                let along = |t: f32| sample(&rgb, fx + dir_x * t, fy + dir_y * t);
+                let near = (along(1.0 / 3.0 - 0.5) + along(2.0 / 3.0 - 0.5)) * 0.5;
+                let far = near * 0.5 + (along(-0.5) + along(0.5)) * 0.25;
+                let far_luma = luminance(far);
+                out[(x, y)] = P::from_rgb(if far_luma < luma_min || far_luma > luma_max {
+                    near
+                } else {
+                    far
+                });
+            }
+        }
+        out
+    }
+}
+
+
+// Outlines ////////////////////////////////////////////////////////////////////
+
+/// Draws lines along edges in the depth buffer, like silhouettes and places
+/// where one part of a model passes in front of another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    pub color: Vec3<f32>,
+    /// How large of a jump in depth counts as an edge
+    pub threshold: f32,
+}
+
+impl Outline {
+    pub fn new(color: Color, threshold: f32) -> Self {
+        Outline { color: color.to_rgb(), threshold }
+    }
+
+    /// Whether there is a depth edge at the pixel `(x, y)`.
+    ///
+    /// Pixels where nothing was drawn have a depth of `f32::MIN`, which would
+    /// overwhelm the filter, so the boundary between drawn and empty pixels
+    /// is always an edge, and empty neighbors are otherwise ignored.
+    pub fn is_edge(&self, depth: &Image<f32>, x: usize, y: usize) -> bool {
+        let center = depth[(x, y)];
+        let covered = center != f32::MIN;
+        let mut samples = [0.0; 9];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let sx = x as isize + (i % 3) as isize - 1;
+            let sy = y as isize + (i / 3) as isize - 1;
+            let d = fetch(depth, sx, sy);
+            if (d != f32::MIN) != covered {
+                return covered;
+            }
+            *sample = if covered { d } else { 0.0 };
+        }
+
+        // A Laplacian filter responds to sudden jumps in depth, but not to
+        // surfaces that are merely steep, since a constant slope cancels out.
+        let neighbors: f32 = samples.iter().sum::<f32>() - samples[4];
+        (samples[4] * 8.0 - neighbors).abs() > self.threshold
+    }
+}
+
+impl<P> Pass<P> for Outline where P: Pixel {
+    fn apply(&self, image: &Image<P>, depth: &Image<f32>) -> Image<P> {
+        assert_eq!((image.width, image.height), (depth.width, depth.height));
+        let mut out = image.clone();
+        for y in 0..image.height {
+            for x in 0..image.width {
+                if self.is_edge(depth, x, y) {
+                    out[(x, y)] = P::from_rgb(self.color);
+                }
+            }
+        }
+        out
+    }
+}
+
+
+// Helpers /////////////////////////////////////////////////////////////////////
+
+/// Read a pixel, clamping the coordinates to the edges of the image
+fn fetch<P: Copy>(image: &Image<P>, x: isize, y: isize) -> P {
+    let x = x.max(0).min(image.width as isize - 1) as usize;
+    let y = y.max(0).min(image.height as isize - 1) as usize;
+    image[(x, y)]
+}
+
+/// Bilinearly sample an image in pixel coordinates
+fn sample(image: &Image<Vec3<f32>>, x: f32, y: f32) -> Vec3<f32> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as isize, y0 as isize);
+    let top = fetch(image, x0, y0) * (1.0 - fx) + fetch(image, x0 + 1, y0) * fx;
+    let bot = fetch(image, x0, y0 + 1) * (1.0 - fx) + fetch(image, x0 + 1, y0 + 1) * fx;
+    top * (1.0 - fy) + bot * fy
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = saturate((x - edge0) / (edge1 - edge0));
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pass, Chain, GaussianBlur, Lut3d, ColorGrade, Outline, Vignette};
+    use cgl_math::Vec3;
+    use image::{Image, Color};
+
+    #[test]
+    fn blur_keeps_flat_color() {
+        let image = Image::filled(8, 8, Color::rgb(100, 150, 200));
+        let depth = Image::filled(8, 8, 0.0);
+        let blurred = GaussianBlur::new(1.5).apply(&image, &depth);
+        for y in 0..8 {
+            for x in 0..8 {
+                let c = blurred[(x, y)];
+                assert!((c.r as i32 - 100).abs() <= 1);
+                assert!((c.g as i32 - 150).abs() <= 1);
+                assert!((c.b as i32 - 200).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn identity_lut() {
+        let lut = Lut3d::identity(8);
+        for &c in &[Vec3(0.0, 0.0, 0.0), Vec3(0.3, 0.5, 0.9), Vec3(1.0, 1.0, 1.0)] {
+            let out = lut.sample(c);
+            assert!((out - c).len() < 1e-5, "{:?} mapped to {:?}", c, out);
+        }
+        assert_eq!(Lut3d::from_image(&lut.to_image::<Vec3<f32>>()), lut);
+    }
+
+    #[test]
+    fn chain_applies_in_order() {
+        let image = Image::filled(4, 4, Vec3(0.2f32, 0.2, 0.2));
+        let depth = Image::filled(4, 4, 0.0);
+        let lut = Lut3d::from_fn(2, |c| Vec3(1.0, 1.0, 1.0) - c);
+        let chain = Chain::new()
+            .then(ColorGrade { lut })
+            .then(Vignette { strength: 1.0, radius: 2.0, softness: 0.5 });
+        // The vignette is entirely outside of the image, so only the LUT
+        // has an effect.
+        let out = chain.apply(&image, &depth);
+        assert!((out[(1, 1)] - Vec3(0.8, 0.8, 0.8)).len() < 1e-5);
+    }
+
+    #[test]
+    fn outline_silhouette() {
+        let image = Image::filled(5, 5, Color::white());
+        let mut depth = Image::filled(5, 5, f32::MIN);
+        for y in 1..4 {
+            for x in 1..4 {
+                depth[(x, y)] = 10.0;
+            }
+        }
+        let out = Outline::new(Color::black(), 1.0).apply(&image, &depth);
+        assert_eq!(out[(1, 1)], Color::black());
+        assert_eq!(out[(2, 2)], Color::white());
+        assert_eq!(out[(0, 0)], Color::white());
+    }
+}
@@ -1,111 +1,760 @@
-use cgl_math::{Vec2, Vec3, Vec4, barycentric};
-use image::{Image, Color};
-use shader::Shader;
+use std::ops::{Add, Mul};
+
+use background::Background;
+use camera::Camera;
+use cgl_math::{Vec2, Vec3, Vec4, Edges};
+use fog::Fog;
+use image::{Image, Color, Pixel};
+use shader::{Shader, Primitive};
 use model::{Model, Vertex};
+use tessellation::{Tessellator, tessellate};
 
-pub struct Renderer {
-    color: Image<Color>,
+/// Draws triangles into a color buffer of `P` pixels, using a depth buffer to
+/// keep only the nearest fragment at each pixel.
+///
+/// By default the color buffer holds 8-bit `Color`s, but rendering into
+/// `HdrColor` allows lighting to be accumulated without clipping before it's
+/// [tone mapped](../tonemap/index.html) for display.
+pub struct Renderer<P = Color> {
+    color: Image<P>,
     zbuf: Image<f32>,
+    rasterizer: Rasterizer,
+    ids: Option<Image<Option<Id>>>,
+    draw_id: u32,
+}
+
+/// An entry in the ID attachment
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Id {
+    draw: u32,
+    triangle: Option<usize>,
+    bc: Vec3<f32>,
+}
+
+/// What was drawn at a pixel, as found by `Renderer::pick`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    /// The draw ID that was set with `Renderer::set_draw_id`
+    pub draw: u32,
+    /// The index of the triangle in `Model::triangles`, or `None` for
+    /// triangles drawn on their own with `Renderer::tri`
+    pub triangle: Option<usize>,
+    /// The perspective-correct barycentric coordinates of the pixel, which
+    /// weight the triangle's vertices in order. If the triangle was split by
+    /// the geometry stage or a tessellator, these are within the piece that
+    /// was drawn rather than the original triangle.
+    pub bc: Vec3<f32>,
+    /// The depth of the pixel, as in `Renderer::depth`
+    pub depth: f32,
 }
 
-impl Renderer {
+/// The algorithm used to find the pixels covered by a triangle.
+///
+/// Both cover exactly the same pixels, so this only affects speed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rasterizer {
+    /// Test every pixel in the triangle's bounding box, see `rasterize`
+    #[default]
+    BoundingBox,
+    /// Only visit the pixels inside the triangle, see `rasterize_scanline`
+    Scanline,
+}
+
+impl<P> Renderer<P> where P: Copy + Default {
     pub fn with_dimensions(w: usize, h: usize) -> Self {
         Renderer {
             color: Image::with_dimensions(w, h),
             zbuf: Image::filled(w, h, ::std::f32::MIN),
+            rasterizer: Rasterizer::default(),
+            ids: None,
+            draw_id: 0,
+        }
+    }
+
+    /// Reset the color buffer to the default pixel and the depth buffer to the
+    /// far depth, keeping their allocations so the renderer can be reused for
+    /// another frame. If picking is enabled it stays enabled, but forgets
+    /// everything that was drawn.
+    pub fn clear(&mut self) {
+        self.color.fill(P::default());
+        self.zbuf.fill(f32::MIN);
+        if let Some(ref mut ids) = self.ids {
+            ids.fill(None);
+        }
+    }
+
+    /// Start recording which triangle is drawn at each pixel, so that it can
+    /// be found with `pick`. Only pixels drawn after this are recorded.
+    pub fn enable_picking(&mut self) {
+        if self.ids.is_none() {
+            self.ids = Some(Image::with_dimensions(self.width(), self.height()));
+        }
+    }
+
+    /// Set the ID that's recorded along with the triangles drawn from now on,
+    /// to tell apart the different models in a scene.
+    pub fn set_draw_id(&mut self, id: u32) {
+        self.draw_id = id;
+    }
+
+    /// Find what was drawn at the pixel `(x, y)` by `tri`, `model`, or
+    /// `tessellated_model`.
+    ///
+    /// Returns `None` if nothing was drawn there, if `(x, y)` is outside of the
+    /// image, or if picking hasn't been enabled with `enable_picking`.
+    pub fn pick(&self, x: usize, y: usize) -> Option<Pick> {
+        if x >= self.width() || y >= self.height() {
+            return None;
         }
+        let id = self.ids.as_ref()?[(x, y)]?;
+        Some(Pick {
+            draw: id.draw,
+            triangle: id.triangle,
+            bc: id.bc,
+            depth: self.zbuf[(x, y)],
+        })
+    }
+
+    /// Choose how the triangles drawn by `tri` and `model` are rasterized.
+    pub fn set_rasterizer(&mut self, rasterizer: Rasterizer) {
+        self.rasterizer = rasterizer;
     }
 
     pub fn width(&self) -> usize { self.color.width }
     pub fn height(&self) -> usize { self.color.height }
-    pub fn image(&self) -> &Image<Color> { &self.color }
+    pub fn image(&self) -> &Image<P> { &self.color }
+    pub fn depth(&self) -> &Image<f32> { &self.zbuf }
 
-    pub fn line(&mut self, t0: Vec3<isize>, t1: Vec3<isize>, color: Color) {
+    pub fn line(&mut self, t0: Vec3<isize>, t1: Vec3<isize>, color: P) {
         self.color.line(t0.0, t0.1, t1.0, t1.1, color);
     }
 
     pub fn triangle(&mut self, t0: Vec3<isize>, t1: Vec3<isize>,
-                     t2: Vec3<isize>, color: Color)
+                     t2: Vec3<isize>, color: P)
     {
-        let ((x0, y0), (x1, y1)) = self.clip(t0, t1, t2);
-
-        for x in (x0..x1).chain(Some(x1)) {
-            for y in (y0..y1).chain(Some(y1)) {
-                let bc_screen = barycentric((t0.into(), t1.into(), t2.into()),
-                                            Vec2(x as isize, y as isize));
-                if bc_screen.0 < 0.0 || bc_screen.1 < 0.0 || bc_screen.2 < 0.0 {
-                    continue;
-                }
-                let z = t0.2 as f32 * bc_screen.0 +
-                    t1.2 as f32 * bc_screen.1 +
-                    t2.2 as f32 * bc_screen.2;
-                if self.zbuf[(x as usize, y as usize)] < z {
-                    self.zbuf[(x as usize, y as usize)] = z;
-                    self.color[(x as usize, y as usize)] = color;
+        let ((x0, y0), (x1, y1)) = clip(self.width(), self.height(), t0, t1, t2);
+        let edges = match Edges::new(t0.into(), t1.into(), t2.into()) {
+            Some(edges) => edges,
+            None => return,
+        };
+        let zs = Vec3(t0.2 as f32, t1.2 as f32, t2.2 as f32) / edges.area as f32;
+
+        let mut row = edges.at(Vec2(x0, y0));
+        for y in y0..y1 + 1 {
+            let mut e = row;
+            for x in x0..x1 + 1 {
+                if e.0 >= 0 && e.1 >= 0 && e.2 >= 0 {
+                    let z = Vec3(e.0 as f32, e.1 as f32, e.2 as f32).dot(zs);
+                    if self.zbuf[(x as usize, y as usize)] < z {
+                        self.zbuf[(x as usize, y as usize)] = z;
+                        self.color[(x as usize, y as usize)] = color;
+                        if let Some(ref mut ids) = self.ids {
+                            ids[(x as usize, y as usize)] = None;
+                        }
+                    }
                 }
+                e = e + edges.step_x();
             }
+            row = row + edges.step_y();
         }
     }
 
     pub fn tri<S, V, U>(&mut self, shader: &S, uniform: &U, t0: V, t1: V, t2: V)
-        where V: Vertex + ::std::fmt::Debug, S: Shader<V, U>, <S as Shader<V, U>>::VOut: ::std::fmt::Debug
+        where V: Vertex, S: Shader<V, U, P> + ?Sized
+    {
+        self.shade(shader, uniform, t0, t1, t2, None);
+    }
+
+    pub fn model<S, V, U>(&mut self, shader: &S, uniform: &U, model: &Model<V>)
+        where V: Vertex + Copy, S: Shader<V, U, P> + ?Sized
+    {
+        for (i, tri) in model.triangles.iter().enumerate() {
+            self.shade(shader, uniform,
+                       model.vertices[tri[0] as usize],
+                       model.vertices[tri[1] as usize],
+                       model.vertices[tri[2] as usize],
+                       Some(i));
+        }
+    }
+
+    /// Draw every triangle of `model`, after splitting it up with
+    /// `tessellator`. See the [`tessellation`](../tessellation/index.html)
+    /// module for details.
+    pub fn tessellated_model<S, T, V, U>(&mut self, shader: &S, tessellator: &T,
+                                         uniform: &U, model: &Model<V>)
+        where V: Vertex + Copy, T: Tessellator<V>, S: Shader<V, U, P> + ?Sized
+    {
+        for (i, tri) in model.triangles.iter().enumerate() {
+            tessellate(tessellator,
+                       model.vertices[tri[0]],
+                       model.vertices[tri[1]],
+                       model.vertices[tri[2]],
+                       |t0, t1, t2| self.shade(shader, uniform, t0, t1, t2, Some(i)));
+        }
+    }
+
+    /// Draw a triangle, recording it as `triangle` in the ID attachment.
+    fn shade<S, V, U>(&mut self, shader: &S, uniform: &U, t0: V, t1: V, t2: V,
+                      triangle: Option<usize>)
+        where V: Vertex, S: Shader<V, U, P> + ?Sized
     {
-        macro_rules! apply_vertex {
-            ($vin:ident => $t:ident $v:ident $w:ident) => {
-                let mut t = Vec4::default();
-                let $v = shader.vertex($vin, uniform, &mut t);
-                let t0 = t.retro_project();
-                let $t = Vec3(t0.0 as isize, t0.1 as isize, t0.2 as isize);
-                let $w = t.3;
+        let color = &mut self.color;
+        let draw_id = self.draw_id;
+        match self.ids {
+            // Interpolating the barycentric coordinates isn't free, so only do
+            // it when they're going to be recorded
+            Some(ref mut ids) => {
+                draw_barycentric(&mut self.zbuf, self.rasterizer, shader, uniform,
+                                 t0, t1, t2, |x, y, vert, bc| {
+                    color[(x, y)] = shader.fragment(vert, uniform);
+                    ids[(x, y)] = Some(Id { draw: draw_id, triangle, bc });
+                });
+            }
+            None => {
+                draw(&mut self.zbuf, self.rasterizer, shader, uniform, t0, t1, t2,
+                     |x, y, vert| color[(x, y)] = shader.fragment(vert, uniform));
             }
         }
-        apply_vertex!(t0 => t0 v0 w0);
-        apply_vertex!(t1 => t1 v1 w1);
-        apply_vertex!(t2 => t2 v2 w2);
-        let ((x0, y0), (x1, y1)) = self.clip(t0, t1, t2);
-
-        for x in (x0..x1).chain(Some(x1)) {
-            for y in (y0..y1).chain(Some(y1)) {
-                let bc_screen = barycentric((t0.into(), t1.into(), t2.into()),
-                                            Vec2(x as isize, y as isize));
-                if bc_screen.0 < 0.0 || bc_screen.1 < 0.0 || bc_screen.2 < 0.0 {
-                    continue;
+    }
+}
+
+impl<P> Renderer<P> where P: Mul<f32, Output=P> + Add<Output=P> + Copy + Default {
+    /// Fill every pixel that no geometry has been drawn to with `background`,
+    /// as seen through `camera`.
+    ///
+    /// The depth buffer is left at the far depth, so this can be called either
+    /// before or after drawing the scene without hiding any of it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the camera's transformation isn't invertible.
+    pub fn background(&mut self, background: &Background<P>, camera: &Camera) {
+        background.draw(camera, &mut self.color, &self.zbuf);
+    }
+}
+
+impl<P> Renderer<P> where P: Pixel {
+    /// Fade everything drawn so far into `fog`, as seen through `camera`. See
+    /// the [`fog`](../fog/index.html) module for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the camera's transformation isn't invertible.
+    pub fn fog(&mut self, fog: &Fog, camera: &Camera) {
+        fog.apply(camera, &mut self.color, &self.zbuf);
+    }
+}
+
+/// Run a triangle through the vertex and geometry stages of `shader`, and then
+/// rasterize each of the resulting primitives, using `rasterizer` for the
+/// triangles.
+///
+/// `fragment` is called with the pixel coordinates and the interpolated vertex
+/// for every pixel that passes the depth test against `zbuf`. It's up to the
+/// caller to run the fragment stage and store the result.
+#[allow(clippy::too_many_arguments)]
+pub fn draw<S, V, U, P, F>(zbuf: &mut Image<f32>, rasterizer: Rasterizer,
+                           shader: &S, uniform: &U,
+                           t0: V, t1: V, t2: V, mut fragment: F)
+    where V: Vertex, S: Shader<V, U, P> + ?Sized, F: FnMut(usize, usize, S::VOut)
+{
+    let mut p0 = Vec4::default();
+    let mut p1 = Vec4::default();
+    let mut p2 = Vec4::default();
+    let v0 = shader.vertex(t0, uniform, &mut p0);
+    let v1 = shader.vertex(t1, uniform, &mut p1);
+    let v2 = shader.vertex(t2, uniform, &mut p2);
+
+    shader.geometry([(p0, v0), (p1, v1), (p2, v2)], uniform, &mut |primitive| {
+        rasterize_primitive(zbuf, rasterizer, primitive, &mut fragment);
+    });
+}
+
+/// The same as `draw`, except that `fragment` is also passed the
+/// perspective-correct barycentric coordinates of the pixel within the
+/// primitive being rasterized.
+#[allow(clippy::too_many_arguments)]
+fn draw_barycentric<S, V, U, P, F>(zbuf: &mut Image<f32>, rasterizer: Rasterizer,
+                                   shader: &S, uniform: &U,
+                                   t0: V, t1: V, t2: V, mut fragment: F)
+    where V: Vertex, S: Shader<V, U, P> + ?Sized, F: FnMut(usize, usize, S::VOut, Vec3<f32>)
+{
+    let mut p0 = Vec4::default();
+    let mut p1 = Vec4::default();
+    let mut p2 = Vec4::default();
+    let v0 = shader.vertex(t0, uniform, &mut p0);
+    let v1 = shader.vertex(t1, uniform, &mut p1);
+    let v2 = shader.vertex(t2, uniform, &mut p2);
+
+    // Each vertex carries its own barycentric coordinates, so interpolating
+    // them gives the coordinates of the pixel.
+    let tag = |(p, v), bc| (p, (v, bc));
+    let (e0, e1, e2) = (Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(0.0, 0.0, 1.0));
+    let mut fragment = |x, y, (vert, bc)| fragment(x, y, vert, bc);
+    shader.geometry([(p0, v0), (p1, v1), (p2, v2)], uniform, &mut |primitive| {
+        let primitive = match primitive {
+            Primitive::Triangle([a, b, c]) => {
+                Primitive::Triangle([tag(a, e0), tag(b, e1), tag(c, e2)])
+            }
+            Primitive::Line([a, b]) => Primitive::Line([tag(a, e0), tag(b, e1)]),
+            Primitive::Point(a) => Primitive::Point(tag(a, e0)),
+        };
+        rasterize_primitive(zbuf, rasterizer, primitive, &mut fragment);
+    });
+}
+
+/// The smallest `w` a vertex can have without being behind the eye
+const NEAR_W: f32 = 1e-5;
+
+/// Rasterize a primitive emitted by the geometry stage, after clipping away
+/// the parts of it that are behind the eye.
+///
+/// Without clipping, vertices behind the eye have a negative `w`, and dividing
+/// by it flips them onto the screen upside down.
+fn rasterize_primitive<V, F>(zbuf: &mut Image<f32>, rasterizer: Rasterizer,
+                             primitive: Primitive<V>, fragment: &mut F)
+    where V: Vertex, F: FnMut(usize, usize, V)
+{
+    // Clipping is linear in homogeneous coordinates, so the attributes of new
+    // vertices can be interpolated without correcting for perspective.
+    let split = |(p, v): &(Vec4<f32>, V), (q, u): &(Vec4<f32>, V)| {
+        let t = (NEAR_W - p.3) / (q.3 - p.3);
+        (*p + (*q - *p) * t, Vertex::interpolate(Vec3(1.0 - t, t, 0.0), v, u, u))
+    };
+    let copy = |(p, v): &(Vec4<f32>, V)| (*p, Vertex::interpolate(Vec3(1.0, 0.0, 0.0), v, v, v));
+    let inside = |(p, _): &(Vec4<f32>, V)| p.3 >= NEAR_W;
+
+    let mut triangle = |a, b, c| match rasterizer {
+        Rasterizer::BoundingBox => rasterize(zbuf, a, b, c, &mut *fragment),
+        Rasterizer::Scanline => rasterize_scanline(zbuf, a, b, c, &mut *fragment),
+    };
+
+    match primitive {
+        Primitive::Triangle([a, b, c]) => {
+            if inside(&a) && inside(&b) && inside(&c) {
+                return triangle(a, b, c);
+            }
+            let corners = [a, b, c];
+            let mut polygon = Vec::with_capacity(4);
+            for i in 0..3 {
+                let (a, b) = (&corners[i], &corners[(i + 1) % 3]);
+                if inside(a) {
+                    polygon.push(copy(a));
                 }
+                if inside(a) != inside(b) {
+                    polygon.push(split(a, b));
+                }
+            }
+            for i in 2..polygon.len() {
+                triangle(copy(&polygon[0]), copy(&polygon[i - 1]), copy(&polygon[i]));
+            }
+        }
+        Primitive::Line([a, b]) => {
+            let (a, b) = match (inside(&a), inside(&b)) {
+                (true, true) => (a, b),
+                (true, false) => { let b = split(&a, &b); (a, b) }
+                (false, true) => (split(&a, &b), b),
+                (false, false) => return,
+            };
+            rasterize_line(zbuf, a, b, fragment)
+        }
+        Primitive::Point(a) => if inside(&a) {
+            rasterize_point(zbuf, a, fragment)
+        },
+    }
+}
+
+/// Rasterize a triangle whose vertices have already been through the vertex
+/// stage.
+///
+/// `p0`, `p1`, and `p2` are the homogeneous screen-space positions written out
+/// by the vertex shader, and `v0`, `v1`, and `v2` are the vertices it
+/// returned. For every pixel covered by the triangle that passes the depth
+/// test against `zbuf`, the depth is written and `fragment` is called with the
+/// pixel coordinates and the perspective-correct interpolated vertex.
+pub fn rasterize<V, F>(zbuf: &mut Image<f32>,
+                       (p0, v0): (Vec4<f32>, V),
+                       (p1, v1): (Vec4<f32>, V),
+                       (p2, v2): (Vec4<f32>, V),
+                       mut fragment: F)
+    where V: Vertex, F: FnMut(usize, usize, V)
+{
+    fn screen(p: Vec4<f32>) -> Vec3<isize> {
+        let t = p.retro_project();
+        Vec3(t.0 as isize, t.1 as isize, t.2 as isize)
+    }
+    let (t0, t1, t2) = (screen(p0), screen(p1), screen(p2));
+    // Depth isn't rounded like the pixel positions are, so that it keeps its
+    // precision when most of the scene is squeezed into a small range of it
+    let zs = Vec3(p0.retro_project().2, p1.retro_project().2, p2.retro_project().2);
+    let (w0, w1, w2) = (p0.3, p1.3, p2.3);
+    let ((x0, y0), (x1, y1)) = clip(zbuf.width, zbuf.height, t0, t1, t2);
+    let edges = match Edges::new(t0.into(), t1.into(), t2.into()) {
+        Some(edges) => edges,
+        None => return,
+    };
+    let inv_area = 1.0 / edges.area as f32;
 
+    // The edge functions are evaluated once, and then stepped across each row
+    // and down to the next one.
+    let mut row = edges.at(Vec2(x0, y0));
+    for y in y0..y1 + 1 {
+        let mut e = row;
+        for x in x0..x1 + 1 {
+            if e.0 >= 0 && e.1 >= 0 && e.2 >= 0 {
+                let bc_screen = Vec3(e.0 as f32, e.1 as f32, e.2 as f32) * inv_area;
                 let w_point = 1.0 / bc_screen.dot(Vec3(1.0/w0, 1.0/w1, 1.0/w2));
                 let bc_clip = bc_screen / Vec3(w0, w1, w2) * w_point;
 
                 // FIXME: Should this be bc_screen, or bc_clip?
-                let z = bc_screen.dot(Vec3(t0.2 as f32, t1.2 as f32, t2.2 as f32));
-                if self.zbuf[(x as usize, y as usize)] < z {
+                let z = bc_screen.dot(zs);
+                if zbuf[(x as usize, y as usize)] < z {
                     let vert = Vertex::interpolate(bc_clip, &v0, &v1, &v2);
-                    self.zbuf[(x as usize, y as usize)] = z;
-                    self.color[(x as usize, y as usize)] = shader.fragment(vert, uniform);
+                    zbuf[(x as usize, y as usize)] = z;
+                    fragment(x as usize, y as usize, vert);
                 }
             }
+            e = e + edges.step_x();
         }
+        row = row + edges.step_y();
     }
+}
 
-    pub fn model<S, V, U>(&mut self, shader: &S, uniform: &U, model: &Model<V>)
-        where V: Vertex + Copy + ::std::fmt::Debug, S: Shader<V, U>, S::VOut: ::std::fmt::Debug
-    {
-        for tri in &model.triangles {
-            self.tri(shader, uniform,
-                     model.vertices[tri[0] as usize],
-                     model.vertices[tri[1] as usize],
-                     model.vertices[tri[2] as usize]);
+/// Rasterize a triangle in the same way as `rasterize`, covering exactly the
+/// same pixels, but without visiting the ones outside of it.
+///
+/// For each row of the bounding box, the span of covered pixels is found by
+/// solving the three edge functions of the triangle for `x`, so the spans are
+/// exact. Along each span the barycentric coordinates are incremented instead
+/// of being recomputed, so long skinny triangles cost about as much as the
+/// pixels they actually cover.
+pub fn rasterize_scanline<V, F>(zbuf: &mut Image<f32>,
+                                (p0, v0): (Vec4<f32>, V),
+                                (p1, v1): (Vec4<f32>, V),
+                                (p2, v2): (Vec4<f32>, V),
+                                mut fragment: F)
+    where V: Vertex, F: FnMut(usize, usize, V)
+{
+    fn screen(p: Vec4<f32>) -> Vec3<isize> {
+        let t = p.retro_project();
+        Vec3(t.0 as isize, t.1 as isize, t.2 as isize)
+    }
+    let (t0, t1, t2) = (screen(p0), screen(p1), screen(p2));
+    // Depth isn't rounded like the pixel positions are, so that it keeps its
+    // precision when most of the scene is squeezed into a small range of it
+    let zs = Vec3(p0.retro_project().2, p1.retro_project().2, p2.retro_project().2);
+    let ((x0, y0), (x1, y1)) = clip(zbuf.width, zbuf.height, t0, t1, t2);
+
+    let edges = match Edges::new(t0.into(), t1.into(), t2.into()) {
+        Some(edges) => edges,
+        None => return,
+    };
+    let inv_area = 1.0 / edges.area as f32;
+    let step = edges.step_x();
+    let step = Vec3(step.0 as f32, step.1 as f32, step.2 as f32) * inv_area;
+    let w_inv = Vec3(1.0 / p0.3, 1.0 / p1.3, 1.0 / p2.3);
+
+    for y in y0..y1 + 1 {
+        let (start, end) = match edges.span(y, x0, x1) {
+            Some(span) => span,
+            None => continue,
+        };
+        let e = edges.at(Vec2(start, y));
+        let mut bc_screen = Vec3(e.0 as f32, e.1 as f32, e.2 as f32) * inv_area;
+        for x in start..end + 1 {
+            let w_point = 1.0 / bc_screen.dot(w_inv);
+            let bc_clip = bc_screen * w_inv * w_point;
+            let z = bc_screen.dot(zs);
+            if zbuf[(x as usize, y as usize)] < z {
+                let vert = Vertex::interpolate(bc_clip, &v0, &v1, &v2);
+                zbuf[(x as usize, y as usize)] = z;
+                fragment(x as usize, y as usize, vert);
+            }
+            bc_screen = bc_screen + step;
         }
     }
+}
 
-    fn clip(&self, t0: Vec3<isize>, t1: Vec3<isize>, t2: Vec3<isize>)
-            -> ((isize, isize), (isize, isize))
-    {
-        use std::cmp::{min, max};
-        let x0 = max(0, min(min(t0.0, min(t1.0, t2.0)), (self.width() - 1) as isize));
-        let x1 = max(0, min(max(t0.0, max(t1.0, t2.0)), (self.width() - 1) as isize));
-        let y0 = max(0, min(min(t0.1, min(t1.1, t2.1)), (self.height() - 1) as isize));
-        let y1 = max(0, min(max(t0.1, max(t1.1, t2.1)), (self.height() - 1) as isize));
-        ((x0, y0), (x1, y1))
+/// Rasterize a line whose endpoints have already been through the vertex
+/// stage, in the same way as `rasterize`.
+pub fn rasterize_line<V, F>(zbuf: &mut Image<f32>,
+                            (p0, v0): (Vec4<f32>, V),
+                            (p1, v1): (Vec4<f32>, V),
+                            mut fragment: F)
+    where V: Vertex, F: FnMut(usize, usize, V)
+{
+    let (t0, t1) = (p0.retro_project(), p1.retro_project());
+    let steps = (t1.0 - t0.0).abs().max((t1.1 - t0.1).abs()).ceil().max(1.0) as usize;
+    for i in 0..steps + 1 {
+        let t = i as f32 / steps as f32;
+        let x = (t0.0 + (t1.0 - t0.0) * t) as isize;
+        let y = (t0.1 + (t1.1 - t0.1) * t) as isize;
+        if x < 0 || y < 0 || x >= zbuf.width as isize || y >= zbuf.height as isize {
+            continue;
+        }
+        let (x, y) = (x as usize, y as usize);
+
+        let z = t0.2 + (t1.2 - t0.2) * t;
+        if zbuf[(x, y)] < z {
+            // Correct for perspective the same way as for triangles, with the
+            // third vertex given no weight.
+            let w_point = 1.0 / ((1.0 - t) / p0.3 + t / p1.3);
+            let bc_clip = Vec3((1.0 - t) / p0.3, t / p1.3, 0.0) * w_point;
+            zbuf[(x, y)] = z;
+            fragment(x, y, Vertex::interpolate(bc_clip, &v0, &v1, &v1));
+        }
     }
 }
 
+/// Rasterize a single point which has already been through the vertex stage,
+/// in the same way as `rasterize`.
+pub fn rasterize_point<V, F>(zbuf: &mut Image<f32>, (p, v): (Vec4<f32>, V), mut fragment: F)
+    where V: Vertex, F: FnMut(usize, usize, V)
+{
+    let t = p.retro_project();
+    if t.0 < 0.0 || t.1 < 0.0 || t.0 >= zbuf.width as f32 || t.1 >= zbuf.height as f32 {
+        return;
+    }
+    let (x, y) = (t.0 as usize, t.1 as usize);
+    if zbuf[(x, y)] < t.2 {
+        zbuf[(x, y)] = t.2;
+        fragment(x, y, v);
+    }
+}
+
+fn clip(width: usize, height: usize,
+        t0: Vec3<isize>, t1: Vec3<isize>, t2: Vec3<isize>)
+        -> ((isize, isize), (isize, isize))
+{
+    use std::cmp::{min, max};
+    let x0 = max(0, min(min(t0.0, min(t1.0, t2.0)), (width - 1) as isize));
+    let x1 = max(0, min(max(t0.0, max(t1.0, t2.0)), (width - 1) as isize));
+    let y0 = max(0, min(min(t0.1, min(t1.1, t2.1)), (height - 1) as isize));
+    let y1 = max(0, min(max(t0.1, max(t1.1, t2.1)), (height - 1) as isize));
+    ((x0, y0), (x1, y1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Renderer, rasterize, rasterize_scanline};
+    use model::Model;
+    use cgl_math::{Vec2, Vec3, Vec4, Mat4, barycentric};
+    use image::{Color, Image};
+    use shader::{Shader, Primitive};
+
+    /// Draws each triangle's first vertex as a point and its first edge as a
+    /// line instead of filling it in
+    struct Outline;
+
+    impl Shader<Vec3<f32>, Mat4<f32>> for Outline {
+        type VOut = Vec3<f32>;
+
+        fn vertex(&self, vert: Vec3<f32>, mat: &Mat4<f32>, pos: &mut Vec4<f32>)
+                  -> Vec3<f32>
+        {
+            *pos = *mat * vert.augment();
+            vert
+        }
+
+        fn geometry(&self, [a, b, _]: [(Vec4<f32>, Vec3<f32>); 3], _: &Mat4<f32>,
+                    emit: &mut dyn FnMut(Primitive<Vec3<f32>>))
+        {
+            emit(Primitive::Point(a));
+            emit(Primitive::Line([a, b]));
+        }
+
+        fn fragment(&self, _: Vec3<f32>, _: &Mat4<f32>) -> Color {
+            Color::white()
+        }
+    }
+
+    #[test]
+    fn geometry_stage_replaces_triangle() {
+        let mut renderer = Renderer::with_dimensions(8, 8);
+        let matrix = Mat4::viewport(8, 8);
+        renderer.tri(&Outline, &matrix,
+                     Vec3(-0.5, 0.5, 0.0), Vec3(0.5, 0.5, 0.0), Vec3(0.0, -0.5, 0.0));
+        for x in 2..7 {
+            assert_eq!(renderer.image()[(x, 2)], Color::white());
+        }
+        assert_eq!(renderer.image()[(4, 4)], Color::black());
+    }
+
+    #[test]
+    fn translation_moves_geometry() {
+        let mut renderer = Renderer::with_dimensions(8, 8);
+        let matrix = Mat4::viewport(8, 8) * Mat4::translate(Vec3(0.0, -0.5, 0.0));
+        renderer.tri(&Outline, &matrix,
+                     Vec3(-0.5, 0.5, 0.0), Vec3(0.5, 0.5, 0.0), Vec3(0.0, -0.5, 0.0));
+        for x in 2..7 {
+            assert_eq!(renderer.image()[(x, 4)], Color::white());
+            assert_eq!(renderer.image()[(x, 2)], Color::black());
+        }
+    }
+
+    /// The pixels covered by a triangle according to `barycentric`, which is
+    /// how every rasterizer used to decide coverage
+    fn reference_coverage(t: [Vec4<f32>; 3], width: isize, height: isize)
+                          -> Vec<(usize, usize)>
+    {
+        let t: Vec<Vec2<isize>> = t.iter()
+            .map(|p| Vec2(p.0 as isize, p.1 as isize))
+            .collect();
+        let mut covered = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let bc = barycentric((t[0], t[1], t[2]), Vec2(x, y));
+                if bc.0 >= 0.0 && bc.1 >= 0.0 && bc.2 >= 0.0 {
+                    covered.push((x as usize, y as usize));
+                }
+            }
+        }
+        covered
+    }
+
+    #[test]
+    fn rasterizers_match_barycentric() {
+        // A small linear congruential generator, so that the triangles are the
+        // same every time
+        let mut seed = 12345u32;
+        let mut random = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            ((seed >> 16) % 80) as f32 - 8.0
+        };
+        for _ in 0..500 {
+            let mut vertex = || (Vec4(random(), random(), random(), 1.0), Vec2(0.0, 0.0));
+            let (a, b, c) = (vertex(), vertex(), vertex());
+            let mut expected = reference_coverage([a.0, b.0, c.0], 64, 64);
+            expected.sort();
+            let mut bbox = Vec::new();
+            let mut scanline = Vec::new();
+            rasterize(&mut Image::filled(64, 64, f32::MIN), a, b, c,
+                      |x, y, _| bbox.push((x, y)));
+            rasterize_scanline(&mut Image::filled(64, 64, f32::MIN), a, b, c,
+                               |x, y, _| scanline.push((x, y)));
+            bbox.sort();
+            scanline.sort();
+            assert_eq!(expected, bbox, "Bounding box coverage differs for {:?}", (a, b, c));
+            assert_eq!(expected, scanline, "Scanline coverage differs for {:?}", (a, b, c));
+        }
+    }
+
+    #[test]
+    fn depth_keeps_fractions() {
+        let triangle = |z| {
+            let vertex = |x, y| (Vec4(x, y, z, 1.0), Vec2(0.0, 0.0));
+            (vertex(0.0, 0.0), vertex(8.0, 0.0), vertex(0.0, 8.0))
+        };
+        // The second triangle is only a fraction of a unit nearer than the
+        // first, but should still be drawn over it
+        let (mut bbox, mut scanline) = (0, 0);
+        let mut zbuf = Image::filled(8, 8, f32::MIN);
+        for &z in &[0.4, 0.6] {
+            let (a, b, c) = triangle(z);
+            rasterize(&mut zbuf, a, b, c, |x, y, _| if (x, y) == (1, 1) { bbox += 1 });
+        }
+        let mut zbuf = Image::filled(8, 8, f32::MIN);
+        for &z in &[0.4, 0.6] {
+            let (a, b, c) = triangle(z);
+            rasterize_scanline(&mut zbuf, a, b, c,
+                               |x, y, _| if (x, y) == (1, 1) { scanline += 1 });
+        }
+        assert_eq!((bbox, scanline), (2, 2));
+        assert!((zbuf[(1, 1)] - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clips_behind_eye() {
+        /// Takes `w` from the vertex's z, so that vertices can be placed
+        /// behind the eye
+        struct Homogeneous;
+
+        impl Shader<Vec3<f32>, ()> for Homogeneous {
+            type VOut = Vec3<f32>;
+
+            fn vertex(&self, vert: Vec3<f32>, _: &(), pos: &mut Vec4<f32>) -> Vec3<f32> {
+                *pos = Vec4(vert.0, vert.1, 0.0, vert.2);
+                vert
+            }
+
+            fn fragment(&self, _: Vec3<f32>, _: &()) -> Color {
+                Color::white()
+            }
+        }
+
+        let covered = |renderer: &Renderer| {
+            (0..8).flat_map(|y| (0..8).map(move |x| (x, y)))
+                .filter(|&p| renderer.image()[p] == Color::white())
+                .collect::<Vec<_>>()
+        };
+
+        // Entirely behind the eye, where dividing by w would flip it onto the
+        // screen
+        let mut renderer = Renderer::with_dimensions(8, 8);
+        renderer.tri(&Homogeneous, &(),
+                     Vec3(-1.0, -1.0, -1.0), Vec3(-6.0, -1.0, -1.0), Vec3(-1.0, -6.0, -1.0));
+        assert_eq!(covered(&renderer), vec![]);
+
+        // One corner behind the eye, which would drag the triangle up and to
+        // the left. Only the part in front of it should be drawn, below and to
+        // the right of the other two corners.
+        let mut renderer = Renderer::with_dimensions(8, 8);
+        renderer.tri(&Homogeneous, &(),
+                     Vec3(2.0, 2.0, 1.0), Vec3(6.0, 2.0, 1.0), Vec3(4.0, 6.0, -1.0));
+        let covered = covered(&renderer);
+        assert!(!covered.is_empty());
+        assert!(covered.iter().all(|&(x, y)| x >= 2 && y >= 2), "{:?}", covered);
+    }
+
+    #[test]
+    fn picks_draw_and_triangle() {
+        struct Flat;
+
+        impl Shader<Vec3<f32>, Mat4<f32>> for Flat {
+            type VOut = Vec3<f32>;
+
+            fn vertex(&self, vert: Vec3<f32>, mat: &Mat4<f32>, pos: &mut Vec4<f32>)
+                      -> Vec3<f32>
+            {
+                *pos = *mat * vert.augment();
+                vert
+            }
+
+            fn fragment(&self, _: Vec3<f32>, _: &Mat4<f32>) -> Color {
+                Color::white()
+            }
+        }
+
+        // Two triangles covering the left and right halves of the screen
+        let model = Model {
+            vertices: vec![Vec3(-1.0, -1.0, 0.0), Vec3(0.0, -1.0, 0.0),
+                           Vec3(0.0, 1.0, 0.0), Vec3(-1.0, 1.0, 0.0),
+                           Vec3(1.0, -1.0, 0.0), Vec3(1.0, 1.0, 0.0)],
+            triangles: vec![[0, 1, 3], [1, 4, 5]],
+        };
+        let matrix = Mat4::viewport(16, 16);
+        let mut renderer = Renderer::with_dimensions(16, 16);
+        renderer.enable_picking();
+        renderer.set_draw_id(7);
+        renderer.model(&Flat, &matrix, &model);
+        let left = renderer.pick(1, 8).expect("Should hit the left triangle");
+        let right = renderer.pick(14, 8).expect("Should hit the right triangle");
+        assert_eq!((left.draw, left.triangle), (7, Some(0)));
+        assert_eq!((right.draw, right.triangle), (7, Some(1)));
+        assert!((left.bc.0 + left.bc.1 + left.bc.2 - 1.0).abs() < 1e-5);
+        assert_eq!(left.depth, renderer.depth()[(1, 8)]);
+        // (14, 8) is at (0.75, 0.0) in model space
+        assert!((right.bc - Vec3(0.25, 0.25, 0.5)).len() < 0.05);
+        assert_eq!(renderer.pick(16, 0), None);
+
+        // Triangles drawn on their own aren't mistaken for the first one of a
+        // model
+        renderer.tri(&Flat, &matrix, Vec3(-1.0, -1.0, 0.5), Vec3(0.0, -1.0, 0.5),
+                     Vec3(-1.0, 1.0, 0.5));
+        assert_eq!(renderer.pick(1, 8).map(|pick| pick.triangle), Some(None));
+
+        let mut renderer = Renderer::with_dimensions(16, 16);
+        renderer.model(&Flat, &matrix, &model);
+        assert_eq!(renderer.pick(1, 8), None);
+    }
+}
@@ -0,0 +1,384 @@
+//! A scene graph, which arranges models, cameras, and lights in a hierarchy
+//! of transformations.
+//!
+//! A [`Scene`] is a tree of [`Node`]s. Each node has a transformation relative
+//! to its parent, so moving a node moves everything below it, and can hold a
+//! mesh to draw, a camera, or a light. Rendering a scene from one of its
+//! cameras works out where everything ended up in the world and draws each
+//! mesh with its own shader.
+//!
+//! Mesh shaders are [boxed](../shader/fn.boxed.html) so that every mesh can
+//! have a different one, and they all receive a [`SceneUniform`] with the
+//! transformations and lights they need.
+//!
+//! [`Scene`]: struct.Scene.html
+//! [`Node`]: struct.Node.html
+//! [`SceneUniform`]: struct.SceneUniform.html
+
+use std::rc::Rc;
+
+use camera::Camera;
+use cgl_math::{Vec3, Vec4, Mat4};
+use image::Color;
+use light::Light;
+use model::{Model, Vertex};
+use renderer::Renderer;
+use shader::BoxedShader;
+
+/// Identifies a node within its `Scene`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The position of the node in the scene, which is also the draw ID its
+    /// mesh is rendered with
+    pub fn index(self) -> usize { self.0 }
+}
+
+/// A model to draw along with the shader to draw it with
+pub struct Mesh<'a, V: Vertex, P = Color> {
+    pub model: Rc<Model<V>>,
+    pub shader: Rc<BoxedShader<'a, V, SceneUniform, P>>,
+}
+
+impl<'a, V: Vertex, P> Clone for Mesh<'a, V, P> {
+    fn clone(&self) -> Self {
+        Mesh { model: self.model.clone(), shader: self.shader.clone() }
+    }
+}
+
+/// What a node holds, besides its transformation
+pub enum Content<'a, V: Vertex, P = Color> {
+    /// Nothing, the node is only there to group and move its children
+    Empty,
+    Mesh(Mesh<'a, V, P>),
+    /// A camera, which looks down the node's -z axis. The camera's own
+    /// transformation is relative to the node.
+    Camera(Camera),
+    /// A light, whose position and direction are relative to the node
+    Light(Light),
+}
+
+pub struct Node<'a, V: Vertex, P = Color> {
+    /// The transformation from this node's space to its parent's
+    pub transform: Mat4<f32>,
+    pub content: Content<'a, V, P>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl<'a, V: Vertex, P> Node<'a, V, P> {
+    pub fn new(transform: Mat4<f32>, content: Content<'a, V, P>) -> Self {
+        Node { transform, content, parent: None, children: Vec::new() }
+    }
+
+    pub fn empty(transform: Mat4<f32>) -> Self {
+        Node::new(transform, Content::Empty)
+    }
+
+    pub fn mesh(transform: Mat4<f32>, model: Rc<Model<V>>,
+                shader: Rc<BoxedShader<'a, V, SceneUniform, P>>)
+                -> Self
+    {
+        Node::new(transform, Content::Mesh(Mesh { model, shader }))
+    }
+
+    pub fn camera(transform: Mat4<f32>, camera: Camera) -> Self {
+        Node::new(transform, Content::Camera(camera))
+    }
+
+    pub fn light<L: Into<Light>>(transform: Mat4<f32>, light: L) -> Self {
+        Node::new(transform, Content::Light(light.into()))
+    }
+
+    pub fn parent(&self) -> Option<NodeId> { self.parent }
+    pub fn children(&self) -> &[NodeId] { &self.children }
+}
+
+/// The uniform passed to the shaders of every mesh in a scene
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneUniform {
+    /// Takes points from the mesh's space to world space
+    pub model: Mat4<f32>,
+    /// Takes normals from the mesh's space to world space. It's the inverse
+    /// transpose of `model`, so normals stay perpendicular to their surface
+    /// even when the mesh is scaled unevenly.
+    pub normal: Mat4<f32>,
+    /// Takes points from world space to the camera's space
+    pub view: Mat4<f32>,
+    /// Takes points from the camera's space to homogeneous screen space,
+    /// including the viewport transformation
+    pub projection: Mat4<f32>,
+    /// `projection * view * model`, which is usually what the vertex stage
+    /// needs to write out
+    pub model_view_projection: Mat4<f32>,
+    /// The world-space position of the camera
+    pub eye: Vec3<f32>,
+    /// Every light in the scene, in world space
+    pub lights: Vec<Light>,
+}
+
+impl SceneUniform {
+    /// The uniform for drawing a mesh on its own, outside of a `Scene`. The
+    /// lights should already be in world space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `model` or `view` isn't invertible.
+    pub fn new(model: Mat4<f32>, view: Mat4<f32>, projection: Mat4<f32>, lights: Vec<Light>)
+               -> Self
+    {
+        let normal = model.inverted()
+            .expect("The model transformation should be invertible")
+            .transpose();
+        let eye = view.inverted()
+            .expect("The view transformation should be invertible");
+        SceneUniform {
+            model,
+            normal,
+            view,
+            projection,
+            model_view_projection: projection * view * model,
+            eye: (eye * Vec3(0.0f32, 0.0, 0.0).augment()).retro_project(),
+            lights,
+        }
+    }
+
+    /// The homogeneous screen-space position of a point in the mesh's space,
+    /// ready to be written out by the vertex stage
+    pub fn project(&self, pos: Vec3<f32>) -> Vec4<f32> {
+        self.model_view_projection * pos.augment()
+    }
+
+    /// The world-space position of a point in the mesh's space
+    pub fn world_position(&self, pos: Vec3<f32>) -> Vec3<f32> {
+        (self.model * pos.augment()).retro_project()
+    }
+
+    /// The world-space direction of a direction in the mesh's space, like a
+    /// tangent, normalized. Use `world_normal` for normals, which have to be
+    /// moved differently to stay perpendicular to their surface.
+    pub fn world_direction(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        let d = self.model * Vec4(dir.0, dir.1, dir.2, 0.0);
+        Vec3(d.0, d.1, d.2).normalized()
+    }
+
+    /// The world-space direction of a normal in the mesh's space, normalized
+    pub fn world_normal(&self, normal: Vec3<f32>) -> Vec3<f32> {
+        let n = self.normal * Vec4(normal.0, normal.1, normal.2, 0.0);
+        Vec3(n.0, n.1, n.2).normalized()
+    }
+}
+
+pub struct Scene<'a, V: Vertex, P = Color> {
+    nodes: Vec<Node<'a, V, P>>,
+}
+
+impl<'a, V: Vertex, P> Default for Scene<'a, V, P> {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+impl<'a, V: Vertex, P> Scene<'a, V, P> {
+    /// A scene containing only an empty root node
+    pub fn new() -> Self {
+        Scene { nodes: vec![Node::empty(Mat4::identity())] }
+    }
+
+    pub fn root(&self) -> NodeId { NodeId(0) }
+    pub fn node(&self, id: NodeId) -> &Node<'a, V, P> { &self.nodes[id.0] }
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node<'a, V, P> { &mut self.nodes[id.0] }
+
+    /// Add `node` to the scene as the last child of `parent`.
+    pub fn add(&mut self, parent: NodeId, mut node: Node<'a, V, P>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = Some(parent);
+        node.children.clear();
+        self.nodes.push(node);
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    /// The transformation from the node's space to world space
+    pub fn world_transform(&self, id: NodeId) -> Mat4<f32> {
+        let node = self.node(id);
+        match node.parent {
+            Some(parent) => self.world_transform(parent) * node.transform,
+            None => node.transform,
+        }
+    }
+
+    /// The camera held by the node `id`, moved into world space and with its
+    /// aspect ratio replaced by `aspect`. This is the camera `render` draws
+    /// through, so it's also the one to draw a `Background` through.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` doesn't hold a camera.
+    pub fn world_camera(&self, id: NodeId, aspect: f32) -> Camera {
+        let lens = match self.node(id).content {
+            Content::Camera(lens) => lens,
+            _ => panic!("Node {:?} should be a camera", id),
+        };
+        Camera { aspect, transform: self.world_transform(id) * lens.transform, ..lens }
+    }
+
+    /// Every light in the scene, moved into world space
+    pub fn lights(&self) -> Vec<Light> {
+        self.lights_in(&self.world_transforms())
+    }
+
+    /// Every light in the scene, moved into world space by `worlds`, which
+    /// comes from `world_transforms`
+    fn lights_in(&self, worlds: &[Mat4<f32>]) -> Vec<Light> {
+        self.nodes.iter().zip(worlds)
+            .filter_map(|(node, world)| match node.content {
+                Content::Light(light) => Some(light.transformed(world)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The world transformation of every node, indexed the same way as
+    /// `nodes`. Parents always come before their children, so this only has
+    /// to look at each node once.
+    fn world_transforms(&self) -> Vec<Mat4<f32>> {
+        let mut worlds: Vec<Mat4<f32>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let world = match node.parent {
+                Some(parent) => worlds[parent.0] * node.transform,
+                None => node.transform,
+            };
+            worlds.push(world);
+        }
+        worlds
+    }
+}
+
+impl<'a, V: Vertex + Copy, P: Copy + Default> Scene<'a, V, P> {
+    /// Draw every mesh in the scene, as seen from the node `camera`.
+    ///
+    /// The camera's aspect ratio is replaced by the renderer's, so the image
+    /// isn't stretched. Each mesh is drawn with the index of its node as the
+    /// draw ID, so with picking enabled the renderer can tell which node is
+    /// under a pixel.
+    ///
+    /// Meshes whose world transformation isn't invertible, like ones scaled to
+    /// nothing, are flat or empty on screen, so they're skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `camera` doesn't hold a camera, or if its world transformation
+    /// isn't invertible.
+    pub fn render(&self, camera: NodeId, renderer: &mut Renderer<P>) {
+        let worlds = self.world_transforms();
+        let (w, h) = (renderer.width(), renderer.height());
+        let lens = self.world_camera(camera, w as f32 / h as f32);
+        let view = lens.view();
+        let projection = Mat4::viewport(w as i32, h as i32) * lens.projection();
+
+        let mut uniform = SceneUniform {
+            model: Mat4::identity(),
+            normal: Mat4::identity(),
+            view,
+            projection,
+            model_view_projection: projection * view,
+            eye: lens.position(),
+            lights: self.lights_in(&worlds),
+        };
+
+        for (i, (node, world)) in self.nodes.iter().zip(&worlds).enumerate() {
+            if let Content::Mesh(ref mesh) = node.content {
+                let inverse = match world.inverted() {
+                    Ok(inverse) => inverse,
+                    Err(_) => continue,
+                };
+                uniform.model = *world;
+                uniform.normal = inverse.transpose();
+                uniform.model_view_projection = projection * view * *world;
+                renderer.set_draw_id(i as u32);
+                renderer.model(&**mesh.shader, &uniform, &mesh.model);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Node, Scene, SceneUniform};
+    use camera::Camera;
+    use cgl_math::{Vec3, Vec4, Mat4};
+    use image::{Color, HdrColor};
+    use light::{Light, PointLight};
+    use model::Model;
+    use renderer::Renderer;
+    use shader::{FnShader, boxed};
+
+    use std::rc::Rc;
+
+    #[test]
+    fn transforms_are_hierarchical() {
+        let mut scene: Scene<Vec3<f32>> = Scene::new();
+        let root = scene.root();
+        let arm = scene.add(root, Node::empty(Mat4::translate(Vec3(1.0, 0.0, 0.0))));
+        let hand = scene.add(arm, Node::light(Mat4::rotate_z(90f32.to_radians()),
+                                              PointLight::new(Vec3(1.0, 0.0, 0.0),
+                                                              HdrColor::gray(1.0),
+                                                              1.0, 1.0)));
+        assert_eq!(scene.node(hand).parent(), Some(arm));
+        assert_eq!(scene.node(arm).children(), &[hand]);
+
+        // The light is one unit along the hand's x axis, which points up
+        let position = |scene: &Scene<Vec3<f32>>| match scene.lights()[0] {
+            Light::Point(light) => light.position,
+            light => panic!("{:?} should be a point light", light),
+        };
+        assert!((position(&scene) - Vec3(1.0, 1.0, 0.0)).len() < 1e-6);
+
+        scene.node_mut(arm).transform = Mat4::translate(Vec3(0.0, 0.0, 2.0));
+        assert!((position(&scene) - Vec3(0.0, 1.0, 2.0)).len() < 1e-6);
+    }
+
+    #[test]
+    fn renders_meshes_from_camera() {
+        let white = FnShader::new(
+            |vert: Vec3<f32>, uniform: &SceneUniform, pos: &mut Vec4<f32>| {
+                *pos = uniform.project(vert);
+                vert
+            },
+            |_: Vec3<f32>, _: &SceneUniform| Color::white());
+        let triangle = Model {
+            vertices: vec![Vec3(-1.0, -1.0, 0.0), Vec3(1.0, -1.0, 0.0), Vec3(0.0, 1.0, 0.0)],
+            triangles: vec![[0, 1, 2]],
+        };
+
+        let mut scene = Scene::new();
+        let root = scene.root();
+        let mesh = scene.add(root, Node::mesh(Mat4::translate(Vec3(0.0, 0.0, -3.0)),
+                                              Rc::new(triangle), Rc::new(boxed(white))));
+        let camera = scene.add(root, Node::camera(Mat4::identity(), Camera::default()));
+
+        let mut renderer = Renderer::with_dimensions(32, 32);
+        renderer.enable_picking();
+        scene.render(camera, &mut renderer);
+        assert_eq!(renderer.image()[(16, 16)], Color::white());
+        assert_eq!(renderer.image()[(1, 1)], Color::black());
+        assert_eq!(renderer.pick(16, 16).map(|pick| pick.draw), Some(mesh.index() as u32));
+
+        // Turn the camera around so the triangle is behind it
+        scene.node_mut(camera).transform = Mat4::rotate_y(180f32.to_radians());
+        let mut renderer = Renderer::with_dimensions(32, 32);
+        scene.render(camera, &mut renderer);
+        assert_eq!(renderer.image()[(16, 16)], Color::black());
+
+        // A mesh scaled to nothing is skipped rather than drawn with a broken
+        // normal matrix
+        scene.node_mut(camera).transform = Mat4::identity();
+        scene.node_mut(mesh).transform = Mat4::scale(Vec3(0.0, 0.0, 0.0));
+        let mut renderer = Renderer::with_dimensions(32, 32);
+        renderer.enable_picking();
+        scene.render(camera, &mut renderer);
+        assert_eq!(renderer.pick(16, 16), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Rendering animations and turntables to a numbered series of image files.
+//!
+//! A [`Sequence`] decides which frames to render and where each one goes, and
+//! then calls back for every frame with a cleared `Renderer` to draw into. The
+//! same renderer is reused for the whole sequence, so its buffers are only
+//! allocated once.
+//!
+//! Long sequences can be split up with `range`, and with `resume` set any
+//! frames that were already written are skipped, so a render that was
+//! interrupted can pick up where it left off.
+//!
+//! ```rust,no_run
+//! use cgl::{Camera, Renderer};
+//! use cgl::controller::{Orbit, Turntable};
+//! use cgl::sequence::Sequence;
+//! # let model: cgl::Model<cgl::Vert> = unimplemented!();
+//!
+//! let camera = Camera::default();
+//! let turntable = Turntable::new(Orbit::framing(&model, &camera), 4.0);
+//! let sequence = Sequence::new("frames", "turntable", 96)
+//!     .camera(camera)
+//!     .path(turntable)
+//!     .resume(true);
+//!
+//! let mut renderer = Renderer::with_dimensions(512, 512);
+//! sequence.render(&mut renderer, |renderer, frame| {
+//!     // Draw the scene with `frame.camera.view_projection()`...
+//! }).expect("Should write frames");
+//! ```
+//!
+//! [`Sequence`]: struct.Sequence.html
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use bmp::write_bmp;
+use camera::Camera;
+use controller::Controller;
+use image::{Image, Color, HdrColor};
+use renderer::Renderer;
+use tonemap::{self, ToneMap};
+
+/// A file format that frames can be written in
+pub trait Format {
+    /// The file extension, without the dot
+    fn extension(&self) -> &str;
+    fn write(&self, image: &Image<Color>, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Writes frames as 24-bit BMP files, with `write_bmp`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Bmp;
+
+impl Format for Bmp {
+    fn extension(&self) -> &str { "bmp" }
+
+    fn write(&self, image: &Image<Color>, mut writer: &mut dyn Write) -> io::Result<()> {
+        write_bmp(image, &mut writer).map(|_| ())
+    }
+}
+
+/// Writes frames as binary PPM files, which many video encoders and image
+/// viewers can read directly
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Ppm;
+
+impl Format for Ppm {
+    fn extension(&self) -> &str { "ppm" }
+
+    fn write(&self, image: &Image<Color>, writer: &mut dyn Write) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", image.width, image.height)?;
+        let mut row = Vec::with_capacity(image.width * 3);
+        for y in 0..image.height {
+            row.clear();
+            for x in 0..image.width {
+                let color = image[(x, y)];
+                row.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+            writer.write_all(&row)?;
+        }
+        Ok(())
+    }
+}
+
+/// What to draw for one frame of a sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    /// The frame number, counting from 0 at the start of the whole sequence
+    pub index: usize,
+    /// The time of the frame in seconds, which is `index / fps`
+    pub time: f32,
+    /// The sequence's camera, moved along its path if it has one
+    pub camera: Camera,
+}
+
+/// How many frames a call to `Sequence::render` wrote, and how many it
+/// skipped because they already existed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub rendered: usize,
+    pub skipped: usize,
+}
+
+/// A series of frames to render into numbered files
+pub struct Sequence<F = Bmp> {
+    /// How many frames are in the whole sequence
+    pub frames: usize,
+    /// The frame rate, used to find the time of each frame
+    pub fps: f32,
+    /// The frames to actually render, which is all of them by default
+    pub range: Range<usize>,
+    /// Whether to skip frames whose file already exists
+    pub resume: bool,
+    /// Where the files are written. It's created if it doesn't exist.
+    pub directory: PathBuf,
+    /// The start of each file name, before the frame number
+    pub prefix: String,
+    pub format: F,
+    /// The camera passed to each frame
+    pub camera: Camera,
+    /// What moves the camera over time, if anything
+    pub path: Option<Box<dyn Controller>>,
+}
+
+impl Sequence<Bmp> {
+    /// A sequence of `frames` BMP files in `directory`, at 24 frames per
+    /// second with a default camera that stays still
+    pub fn new<D: Into<PathBuf>>(directory: D, prefix: &str, frames: usize) -> Self {
+        Sequence {
+            frames,
+            fps: 24.0,
+            range: 0..frames,
+            resume: false,
+            directory: directory.into(),
+            prefix: prefix.to_owned(),
+            format: Bmp,
+            camera: Camera::default(),
+            path: None,
+        }
+    }
+}
+
+impl<F: Format> Sequence<F> {
+    pub fn fps(self, fps: f32) -> Self {
+        Sequence { fps, ..self }
+    }
+
+    /// Only render the frames in `range`. Frames past the end of the
+    /// sequence are ignored.
+    pub fn range(self, range: Range<usize>) -> Self {
+        Sequence { range, ..self }
+    }
+
+    pub fn resume(self, resume: bool) -> Self {
+        Sequence { resume, ..self }
+    }
+
+    pub fn camera(self, camera: Camera) -> Self {
+        Sequence { camera, ..self }
+    }
+
+    /// Move the camera with `path`, e.g. a `Turntable` or `FlyPath`
+    pub fn path<C: Controller + 'static>(self, path: C) -> Self {
+        Sequence { path: Some(Box::new(path)), ..self }
+    }
+
+    /// Write the frames in a different format
+    pub fn format<G: Format>(self, format: G) -> Sequence<G> {
+        Sequence {
+            frames: self.frames,
+            fps: self.fps,
+            range: self.range,
+            resume: self.resume,
+            directory: self.directory,
+            prefix: self.prefix,
+            format,
+            camera: self.camera,
+            path: self.path,
+        }
+    }
+
+    /// The frame at `index`
+    pub fn frame(&self, index: usize) -> Frame {
+        let time = index as f32 / self.fps;
+        let camera = match self.path {
+            Some(ref path) => path.place(time, self.camera),
+            None => self.camera,
+        };
+        Frame { index, time, camera }
+    }
+
+    /// The file that the frame at `index` is written to. The number is padded
+    /// to at least four digits so that the files sort in order.
+    pub fn path_of(&self, index: usize) -> PathBuf {
+        let digits = self.frames.saturating_sub(1).to_string().len().max(4);
+        self.directory.join(format!("{}{:0width$}.{}", self.prefix, index, self.format.extension(),
+                                    width = digits))
+    }
+
+    /// Render every frame in the range into `renderer` with `draw`, and write
+    /// out its image.
+    ///
+    /// The renderer is cleared before each frame, so `draw` only has to draw
+    /// the scene for the frame it's given. Stops at the first error creating
+    /// or writing a file.
+    pub fn render<D>(&self, renderer: &mut Renderer<Color>, draw: D) -> io::Result<Progress>
+        where D: FnMut(&mut Renderer<Color>, Frame)
+    {
+        self.run(renderer, draw, |image, path| self.write(image, path))
+    }
+
+    /// The same as `render`, but for an HDR renderer whose image is tone
+    /// mapped with `op` before it's written.
+    pub fn render_hdr<D, T>(&self, renderer: &mut Renderer<HdrColor>, op: &T, draw: D)
+                            -> io::Result<Progress>
+        where D: FnMut(&mut Renderer<HdrColor>, Frame), T: ToneMap + ?Sized
+    {
+        self.run(renderer, draw, |image, path| self.write(&tonemap::resolve(image, op), path))
+    }
+
+    fn run<P, D, W>(&self, renderer: &mut Renderer<P>, mut draw: D, mut write: W)
+                    -> io::Result<Progress>
+        where P: Copy + Default, D: FnMut(&mut Renderer<P>, Frame),
+              W: FnMut(&Image<P>, &Path) -> io::Result<()>
+    {
+        fs::create_dir_all(&self.directory)?;
+        let mut progress = Progress::default();
+        for index in self.range.start..self.range.end.min(self.frames) {
+            let path = self.path_of(index);
+            if self.resume && path.is_file() {
+                progress.skipped += 1;
+                continue;
+            }
+            renderer.clear();
+            draw(renderer, self.frame(index));
+            write(renderer.image(), &path)?;
+            progress.rendered += 1;
+        }
+        Ok(progress)
+    }
+
+    /// Write `image` to `path`. It's written to a temporary file first and
+    /// then renamed, so that an interrupted render never leaves behind a
+    /// partial frame that `resume` would skip.
+    fn write(&self, image: &Image<Color>, path: &Path) -> io::Result<()> {
+        let partial = path.with_extension("partial");
+        {
+            let mut writer = BufWriter::new(File::create(&partial)?);
+            self.format.write(image, &mut writer)?;
+            writer.flush()?;
+        }
+        fs::rename(&partial, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Ppm, Progress, Sequence};
+    use cgl_math::Vec3;
+    use controller::{Orbit, Turntable};
+    use image::{Color, Image};
+    use renderer::Renderer;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    /// An empty directory for a test to write frames into
+    fn directory(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cgl-sequence-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn names_and_times_frames() {
+        let sequence = Sequence::new("frames", "shot_", 20).fps(10.0);
+        assert_eq!(sequence.path_of(7), PathBuf::from("frames/shot_0007.bmp"));
+        assert_eq!(sequence.frame(15).time, 1.5);
+        let long = Sequence::new("frames", "", 123456).format(Ppm);
+        assert_eq!(long.path_of(42), PathBuf::from("frames/000042.ppm"));
+
+        let orbit = Orbit::new(Vec3(0.0, 0.0, 0.0), 0.0, 0.0, 2.0);
+        let sequence = sequence.path(Turntable::new(orbit, 4.0));
+        let position = sequence.frame(10).camera.position();
+        assert!((position - Vec3(2.0, 0.0, 0.0)).len() < 1e-4, "{:?}", position);
+        assert_eq!(sequence.frame(0).camera.position(), orbit.eye());
+    }
+
+    #[test]
+    fn renders_ranges_and_resumes() {
+        let dir = directory("resume");
+        let sequence = Sequence::new(&dir, "f", 6).range(2..4);
+        let mut renderer = Renderer::with_dimensions(4, 3);
+        let mut drawn = Vec::new();
+        let progress = sequence.render(&mut renderer, |renderer, frame| {
+            // The previous frame should have been cleared away
+            assert_eq!(renderer.image()[(1, 1)], Color::black());
+            renderer.line(Vec3(0, 1, 0), Vec3(3, 1, 0), Color::rgb(frame.index as u8, 0, 0));
+            drawn.push(frame.index);
+        }).unwrap();
+        assert_eq!(progress, Progress { rendered: 2, skipped: 0 });
+        assert_eq!(drawn, vec![2, 3]);
+        assert!(!sequence.path_of(1).exists());
+        assert!(sequence.path_of(3).is_file());
+
+        // Picking up again only renders what's missing, and stops at the end
+        let sequence = sequence.range(0..100).resume(true);
+        drawn.clear();
+        let progress = sequence.render(&mut renderer, |_, frame| drawn.push(frame.index)).unwrap();
+        assert_eq!(progress, Progress { rendered: 4, skipped: 2 });
+        assert_eq!(drawn, vec![0, 1, 4, 5]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 6);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_ppm() {
+        let mut image = Image::with_dimensions(2, 1);
+        image[(1, 0)] = Color::rgb(1, 2, 3);
+        let mut bytes = Vec::new();
+        Ppm.write(&image, &mut bytes).unwrap();
+        assert_eq!(bytes, b"P6\n2 1\n255\n\0\0\0\x01\x02\x03");
+    }
+}
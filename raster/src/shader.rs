@@ -1,10 +1,291 @@
 use image::Color;
 use model::Vertex;
-use cgl_math::Vec4;
+use cgl_math::{Vec3, Vec4};
 
-pub trait Shader<V: Vertex, U> {
+use std::any::Any;
+
+/// A program run by the renderer for every vertex and every covered pixel.
+///
+/// `P` is the type of pixel the fragment stage produces. It defaults to
+/// `Color`, but other passes (like the geometry pass of the deferred renderer)
+/// can write out whatever per-pixel data they need.
+pub trait Shader<V: Vertex, U, P = Color> {
     type VOut: Vertex;
 
     fn vertex(&self, vertex: V, uniform: &U, pos: &mut Vec4<f32>) -> Self::VOut;
-    fn fragment(&self, input: Self::VOut, uniform: &U) -> Color;
+    fn fragment(&self, input: Self::VOut, uniform: &U) -> P;
+
+    /// The geometry stage, which runs on every triangle between the vertex
+    /// stage and rasterization.
+    ///
+    /// It receives the positions and outputs of the vertex stage for a whole
+    /// triangle, and can pass any number of primitives to `emit` to be
+    /// rasterized in its place. This allows for things like exploding a mesh
+    /// by moving each face, generating extra geometry like fins or billboards,
+    /// or culling triangles entirely. By default the triangle is passed along
+    /// unchanged.
+    fn geometry(&self, triangle: [(Vec4<f32>, Self::VOut); 3], _uniform: &U,
+                emit: &mut dyn FnMut(Primitive<Self::VOut>))
+    {
+        emit(Primitive::Triangle(triangle));
+    }
+}
+
+/// A primitive emitted by the geometry stage of a `Shader`.
+///
+/// Each vertex is the homogeneous screen-space position, in the same form the
+/// vertex stage writes it, along with the attributes to interpolate for the
+/// fragment stage.
+#[derive(Debug, Clone, Copy)]
+pub enum Primitive<V> {
+    Triangle([(Vec4<f32>, V); 3]),
+    /// A line one pixel wide
+    Line([(Vec4<f32>, V); 2]),
+    /// A single pixel
+    Point((Vec4<f32>, V)),
+}
+
+impl<V> Primitive<V> {
+    /// Apply `f` to the attributes of each vertex of the primitive.
+    pub fn map<W, F: FnMut(V) -> W>(self, mut f: F) -> Primitive<W> {
+        match self {
+            Primitive::Triangle([(p0, v0), (p1, v1), (p2, v2)]) => {
+                Primitive::Triangle([(p0, f(v0)), (p1, f(v1)), (p2, f(v2))])
+            }
+            Primitive::Line([(p0, v0), (p1, v1)]) => Primitive::Line([(p0, f(v0)), (p1, f(v1))]),
+            Primitive::Point((p, v)) => Primitive::Point((p, f(v))),
+        }
+    }
+}
+
+impl<S, V, U, P> Shader<V, U, P> for Box<S>
+    where S: Shader<V, U, P> + ?Sized, V: Vertex
+{
+    type VOut = S::VOut;
+
+    fn vertex(&self, vertex: V, uniform: &U, pos: &mut Vec4<f32>) -> S::VOut {
+        (**self).vertex(vertex, uniform, pos)
+    }
+
+    fn fragment(&self, input: S::VOut, uniform: &U) -> P {
+        (**self).fragment(input, uniform)
+    }
+
+    fn geometry(&self, triangle: [(Vec4<f32>, S::VOut); 3], uniform: &U,
+                emit: &mut dyn FnMut(Primitive<S::VOut>))
+    {
+        (**self).geometry(triangle, uniform, emit)
+    }
+}
+
+// Type Erasure ////////////////////////////////////////////////////////////////
+
+/// A shader whose vertex stage output type has been erased, so that shaders
+/// with different outputs can be stored together and chosen at runtime.
+///
+/// Create them with [`boxed`](fn.boxed.html).
+pub type BoxedShader<'a, V, U, P = Color> = Box<dyn Shader<V, U, P, VOut = Varying> + 'a>;
+
+/// Box up `shader` so that it can be used interchangeably with any other
+/// shader that takes the same vertices and uniform.
+///
+/// # Examples
+///
+/// ```rust
+/// # use cgl::{Color, Mat4, Vec2, Vec3, Vec4, Vert};
+/// use cgl::shader::{BoxedShader, FnShader, boxed};
+///
+/// let textured = FnShader::new(
+///     |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+///         *pos = *mat * vert.pos.augment();
+///         vert.tex
+///     },
+///     |tex: Vec2<f32>, _: &Mat4<f32>| Color::float_rgb(tex.0, tex.1, 0.0));
+/// let normals = FnShader::new(
+///     |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+///         *pos = *mat * vert.pos.augment();
+///         vert.norm
+///     },
+///     |norm: Vec3<f32>, _: &Mat4<f32>| Color::float_rgb(norm.0, norm.1, norm.2));
+///
+/// // The two shaders have different outputs, but they can share a list
+/// let materials: Vec<BoxedShader<Vert, Mat4<f32>>> = vec![boxed(textured), boxed(normals)];
+/// ```
+pub fn boxed<'a, S, V, U, P>(shader: S) -> BoxedShader<'a, V, U, P>
+    where S: Shader<V, U, P> + 'a, S::VOut: 'static, V: Vertex
+{
+    Box::new(Erased(shader))
+}
+
+/// Adapts a shader to pass `Varying`s between its stages instead of its own
+/// output type.
+pub struct Erased<S>(pub S);
+
+impl<S, V, U, P> Shader<V, U, P> for Erased<S>
+    where S: Shader<V, U, P>, S::VOut: 'static, V: Vertex
+{
+    type VOut = Varying;
+
+    fn vertex(&self, vertex: V, uniform: &U, pos: &mut Vec4<f32>) -> Varying {
+        Varying::new(self.0.vertex(vertex, uniform, pos))
+    }
+
+    fn fragment(&self, input: Varying, uniform: &U) -> P {
+        self.0.fragment(input.unwrap(), uniform)
+    }
+
+    fn geometry(&self, [(p0, v0), (p1, v1), (p2, v2)]: [(Vec4<f32>, Varying); 3],
+                uniform: &U, emit: &mut dyn FnMut(Primitive<Varying>))
+    {
+        let triangle = [(p0, v0.unwrap()), (p1, v1.unwrap()), (p2, v2.unwrap())];
+        self.0.geometry(triangle, uniform, &mut |primitive| emit(primitive.map(Varying::new)));
+    }
+}
+
+/// The output of a vertex stage, of a type only known to the shader that
+/// produced it.
+///
+/// Interpolating `Varying`s allocates, so boxed shaders are a bit slower than
+/// using the shader directly.
+pub struct Varying(Box<dyn AnyVertex>);
+
+impl Varying {
+    fn new<T: Vertex + 'static>(vertex: T) -> Self {
+        Varying(Box::new(vertex))
+    }
+
+    fn unwrap<T: 'static>(self) -> T {
+        *self.0.into_any().downcast()
+            .expect("Varyings should only be passed back to the shader that made them")
+    }
+}
+
+impl Vertex for Varying {
+    fn interpolate(x: Vec3<f32>, t0: &Self, t1: &Self, t2: &Self) -> Self {
+        Varying(t0.0.interpolate_dyn(x, &*t1.0, &*t2.0))
+    }
+}
+
+trait AnyVertex {
+    fn interpolate_dyn(&self, x: Vec3<f32>, t1: &dyn AnyVertex, t2: &dyn AnyVertex)
+                       -> Box<dyn AnyVertex>;
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Vertex + 'static> AnyVertex for T {
+    fn interpolate_dyn(&self, x: Vec3<f32>, t1: &dyn AnyVertex, t2: &dyn AnyVertex)
+                       -> Box<dyn AnyVertex>
+    {
+        fn same<T: 'static>(t: &dyn AnyVertex) -> &T {
+            t.as_any().downcast_ref()
+                .expect("All vertices of a primitive should come from the same shader")
+        }
+        Box::new(T::interpolate(x, self, same(t1), same(t2)))
+    }
+
+    fn as_any(&self) -> &dyn Any { self }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> { self }
+}
+
+// Closures ////////////////////////////////////////////////////////////////////
+
+/// A shader made from a pair of closures for its vertex and fragment stages.
+///
+/// # Examples
+///
+/// ```rust
+/// # use cgl::{Color, Mat4, Vec3, Vec4, Vert};
+/// use cgl::shader::FnShader;
+///
+/// let shader = FnShader::new(
+///     |vert: Vert, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+///         *pos = *mat * vert.pos.augment();
+///         vert.norm
+///     },
+///     |norm: Vec3<f32>, _: &Mat4<f32>| {
+///         let c = norm.normalized().dot(Vec3(0.0, 0.0, 1.0));
+///         Color::float_rgb(c, c, c)
+///     });
+/// ```
+pub struct FnShader<VF, FF> {
+    vertex: VF,
+    fragment: FF,
+}
+
+impl<VF, FF> FnShader<VF, FF> {
+    pub fn new<V, U, O, P>(vertex: VF, fragment: FF) -> Self
+        where VF: Fn(V, &U, &mut Vec4<f32>) -> O, FF: Fn(O, &U) -> P
+    {
+        FnShader { vertex, fragment }
+    }
+}
+
+impl<V, U, P, O, VF, FF> Shader<V, U, P> for FnShader<VF, FF>
+    where V: Vertex, O: Vertex, VF: Fn(V, &U, &mut Vec4<f32>) -> O, FF: Fn(O, &U) -> P
+{
+    type VOut = O;
+
+    fn vertex(&self, vertex: V, uniform: &U, pos: &mut Vec4<f32>) -> O {
+        (self.vertex)(vertex, uniform, pos)
+    }
+
+    fn fragment(&self, input: O, uniform: &U) -> P {
+        (self.fragment)(input, uniform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoxedShader, FnShader, Primitive, Shader, boxed};
+    use cgl_math::{Vec3, Vec4, Mat4};
+    use image::Color;
+    use renderer::Renderer;
+
+    /// Draws only the first edge of each triangle, to check that the geometry
+    /// stage still works once its output is erased
+    struct Edge;
+
+    impl Shader<Vec3<f32>, Mat4<f32>> for Edge {
+        type VOut = Vec3<f32>;
+
+        fn vertex(&self, vert: Vec3<f32>, mat: &Mat4<f32>, pos: &mut Vec4<f32>)
+                  -> Vec3<f32>
+        {
+            *pos = *mat * vert.augment();
+            vert
+        }
+
+        fn geometry(&self, [a, b, _]: [(Vec4<f32>, Vec3<f32>); 3], _: &Mat4<f32>,
+                    emit: &mut dyn FnMut(Primitive<Vec3<f32>>))
+        {
+            emit(Primitive::Line([a, b]));
+        }
+
+        fn fragment(&self, _: Vec3<f32>, _: &Mat4<f32>) -> Color {
+            Color::rgb(0, 0, 255)
+        }
+    }
+
+    #[test]
+    fn boxed_shaders_are_interchangeable() {
+        let red = FnShader::new(
+            |vert: Vec3<f32>, mat: &Mat4<f32>, pos: &mut Vec4<f32>| {
+                *pos = *mat * vert.augment();
+                Vec3(1.0, 0.0, 0.0)
+            },
+            |c: Vec3<f32>, _: &Mat4<f32>| Color::float_rgb(c.0, c.1, c.2));
+        let materials: Vec<BoxedShader<Vec3<f32>, Mat4<f32>>> = vec![boxed(red), boxed(Edge)];
+
+        let matrix = Mat4::viewport(8, 8);
+        let (t0, t1, t2) = (Vec3(-0.5, -0.5, 0.0), Vec3(0.5, -0.5, 0.0), Vec3(0.0, 0.5, 0.0));
+        let mut fill = Renderer::with_dimensions(8, 8);
+        fill.tri(&materials[0], &matrix, t0, t1, t2);
+        assert_eq!(fill.image()[(4, 4)], Color::rgb(255, 0, 0));
+
+        let mut edge = Renderer::with_dimensions(8, 8);
+        edge.tri(&*materials[1], &matrix, t0, t1, t2);
+        assert_eq!(edge.image()[(4, 4)], Color::black());
+        assert!((0..8).any(|y| edge.image()[(4, y)] == Color::rgb(0, 0, 255)));
+    }
 }
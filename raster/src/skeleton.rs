@@ -0,0 +1,475 @@
+//! Skeletal animation, where a mesh is deformed by a hierarchy of joints.
+//!
+//! Each [`SkinnedVert`] is attached to up to four joints of a [`Skeleton`],
+//! with a weight for each. Posing the skeleton gives a matrix for every joint
+//! that takes a vertex from where it was when the mesh was bound to the
+//! skeleton to where that joint has moved it, and each vertex ends up at the
+//! weighted average of where its joints put it. This is known as linear blend
+//! skinning.
+//!
+//! A [`Clip`] poses the skeleton over time from keyframes, and the
+//! [`Skinned`] shader adapter deforms the mesh on the way into any shader for
+//! `Vert`s, so an animated character can be drawn frame by frame:
+//!
+//! ```rust,no_run
+//! # use cgl::{Model, Renderer, Mat4};
+//! # use cgl::phong::BlinnPhong;
+//! # use cgl::scene::SceneUniform;
+//! use cgl::skeleton::{Clip, Skeleton, Skinned, SkinnedVert};
+//!
+//! # fn draw(skeleton: &Skeleton, clip: &Clip, model: &Model<SkinnedVert>,
+//! #         uniform: &SceneUniform, renderer: &mut Renderer) {
+//! for frame in 0..24 {
+//!     let pose = clip.sample(skeleton, frame as f32 / 24.0);
+//!     let shader = Skinned::new(BlinnPhong::default(), skeleton.skin_matrices(&pose));
+//!     renderer.model(&shader, uniform, model);
+//! }
+//! # }
+//! ```
+//!
+//! [`SkinnedVert`]: struct.SkinnedVert.html
+//! [`Skeleton`]: struct.Skeleton.html
+//! [`Clip`]: struct.Clip.html
+//! [`Skinned`]: struct.Skinned.html
+
+use cgl_math::{Vec2, Vec3, Vec4, Mat4, Quat};
+use model::{MeshVertex, Vert, Vertex};
+use shader::{Primitive, Shader};
+
+/// The most joints that can move a single vertex
+pub const MAX_INFLUENCES: usize = 4;
+
+
+// Vertices ////////////////////////////////////////////////////////////////////
+
+/// A vertex that's moved by the joints of a skeleton
+#[derive(Debug, Clone, Copy)]
+pub struct SkinnedVert {
+    pub pos: Vec3<f32>,
+    pub tex: Vec2<f32>,
+    pub norm: Vec3<f32>,
+    /// The joints that move this vertex, as indices into the skeleton
+    pub joints: [usize; MAX_INFLUENCES],
+    /// How much each joint moves this vertex. They should add up to 1, and
+    /// unused joints should have a weight of 0.
+    pub weights: [f32; MAX_INFLUENCES],
+}
+
+impl SkinnedVert {
+    /// Attach `vert` to the joints in `influences`, each with a weight.
+    /// Weights are normalized to add up to 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `MAX_INFLUENCES` joints.
+    pub fn new(vert: Vert, influences: &[(usize, f32)]) -> Self {
+        assert!(influences.len() <= MAX_INFLUENCES,
+                "A vertex can have at most {} joints, not {}", MAX_INFLUENCES, influences.len());
+        let total: f32 = influences.iter().map(|&(_, weight)| weight).sum();
+        let mut joints = [0; MAX_INFLUENCES];
+        let mut weights = [0.0; MAX_INFLUENCES];
+        for (i, &(joint, weight)) in influences.iter().enumerate() {
+            joints[i] = joint;
+            weights[i] = if total > 0.0 { weight / total } else { 0.0 };
+        }
+        SkinnedVert { pos: vert.pos, tex: vert.tex, norm: vert.norm, joints, weights }
+    }
+}
+
+/// Joint indices can't be interpolated, so they're taken from the first
+/// vertex. This only matters for shaders that pass `SkinnedVert`s along to the
+/// fragment stage, which `Skinned` doesn't.
+impl Vertex for SkinnedVert {
+    fn interpolate(x: Vec3<f32>, t0: &Self, t1: &Self, t2: &Self) -> Self {
+        let mut weights = [0.0; MAX_INFLUENCES];
+        for (i, weight) in weights.iter_mut().enumerate() {
+            *weight = t0.weights[i] * x.0 + t1.weights[i] * x.1 + t2.weights[i] * x.2;
+        }
+        SkinnedVert {
+            pos: Vertex::interpolate(x, &t0.pos, &t1.pos, &t2.pos),
+            tex: Vertex::interpolate(x, &t0.tex, &t1.tex, &t2.tex),
+            norm: Vertex::interpolate(x, &t0.norm, &t1.norm, &t2.norm),
+            joints: t0.joints,
+            weights,
+        }
+    }
+}
+
+impl MeshVertex for SkinnedVert {
+    fn pos(&self) -> Vec3<f32> { self.pos }
+    fn pos_mut(&mut self) -> &mut Vec3<f32> { &mut self.pos }
+    fn norm(&self) -> Vec3<f32> { self.norm }
+    fn norm_mut(&mut self) -> &mut Vec3<f32> { &mut self.norm }
+    fn tex(&self) -> Vec2<f32> { self.tex }
+}
+
+/// Move `vert` by the `matrices` of its joints, as returned by
+/// `Skeleton::skin_matrices`.
+///
+/// Normals are moved by the same matrices, which keeps them perpendicular to
+/// the surface as long as the joints aren't scaled unevenly. A vertex with no
+/// weight on any joint stays where it is.
+pub fn skin(vert: &SkinnedVert, matrices: &[Mat4<f32>]) -> Vert {
+    let mut pos = Vec4(0.0, 0.0, 0.0, 0.0);
+    let mut norm = Vec4(0.0, 0.0, 0.0, 0.0);
+    for (&joint, &weight) in vert.joints.iter().zip(&vert.weights) {
+        if weight != 0.0 {
+            let matrix = matrices[joint];
+            pos = pos + matrix * vert.pos.augment() * weight;
+            norm = norm + matrix * Vec4(vert.norm.0, vert.norm.1, vert.norm.2, 0.0) * weight;
+        }
+    }
+    if pos.3 <= 0.0 {
+        return Vert { pos: vert.pos, tex: vert.tex, norm: vert.norm };
+    }
+    Vert {
+        pos: pos.retro_project(),
+        tex: vert.tex,
+        norm: Vec3(norm.0, norm.1, norm.2).normalized(),
+    }
+}
+
+/// Wraps a shader for `Vert`s so that it draws `SkinnedVert`s, after moving
+/// them with `skin`
+#[derive(Debug, Clone)]
+pub struct Skinned<S> {
+    pub shader: S,
+    /// The skin matrices of the pose to draw, from `Skeleton::skin_matrices`
+    pub matrices: Vec<Mat4<f32>>,
+}
+
+impl<S> Skinned<S> {
+    pub fn new(shader: S, matrices: Vec<Mat4<f32>>) -> Self {
+        Skinned { shader, matrices }
+    }
+}
+
+impl<S, U, P> Shader<SkinnedVert, U, P> for Skinned<S> where S: Shader<Vert, U, P> {
+    type VOut = S::VOut;
+
+    fn vertex(&self, vert: SkinnedVert, uniform: &U, pos: &mut Vec4<f32>) -> S::VOut {
+        self.shader.vertex(skin(&vert, &self.matrices), uniform, pos)
+    }
+
+    fn fragment(&self, input: S::VOut, uniform: &U) -> P {
+        self.shader.fragment(input, uniform)
+    }
+
+    fn geometry(&self, triangle: [(Vec4<f32>, S::VOut); 3], uniform: &U,
+                emit: &mut dyn FnMut(Primitive<S::VOut>))
+    {
+        self.shader.geometry(triangle, uniform, emit)
+    }
+}
+
+
+// Skeleton ////////////////////////////////////////////////////////////////////
+
+/// A translation, rotation, and scale, applied in the reverse order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3<f32>,
+    pub rotation: Quat,
+    pub scale: Vec3<f32>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            translation: Vec3(0.0, 0.0, 0.0),
+            rotation: Quat::identity(),
+            scale: Vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn translation(translation: Vec3<f32>) -> Self {
+        Transform { translation, ..Transform::identity() }
+    }
+
+    pub fn to_mat4(&self) -> Mat4<f32> {
+        Mat4::translate(self.translation) * self.rotation.to_mat4() * Mat4::scale(self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self { Transform::identity() }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// The index of the parent joint, which always comes before this one
+    pub parent: Option<usize>,
+    /// The joint's transformation relative to its parent when the mesh was
+    /// bound to the skeleton
+    pub rest: Transform,
+    /// Takes vertices from the mesh's space into the joint's space, as they
+    /// were when the mesh was bound to the skeleton
+    pub inverse_bind: Mat4<f32>,
+}
+
+/// A hierarchy of joints
+#[derive(Debug, Clone, Default)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+}
+
+/// The transformation of every joint of a skeleton relative to its parent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub joints: Vec<Transform>,
+}
+
+impl Skeleton {
+    pub fn new() -> Self {
+        Skeleton { joints: Vec::new() }
+    }
+
+    /// Add a joint as a child of `parent`, or as a root if there isn't one,
+    /// and return its index. The mesh is assumed to be bound to the skeleton in
+    /// its rest pose, which is where the joint's inverse bind matrix comes
+    /// from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` isn't already in the skeleton, or if the rest pose
+    /// isn't invertible.
+    pub fn add(&mut self, name: &str, parent: Option<usize>, rest: Transform) -> usize {
+        let parent_bind = match parent {
+            Some(parent) => {
+                assert!(parent < self.joints.len(), "Joint {} should already exist", parent);
+                self.joints[parent].inverse_bind.inverted()
+                    .expect("The parent's inverse bind matrix should be invertible")
+            }
+            None => Mat4::identity(),
+        };
+        let inverse_bind = (parent_bind * rest.to_mat4()).inverted()
+            .expect("The joint's rest transformation should be invertible");
+        self.joints.push(Joint { name: name.to_owned(), parent, rest, inverse_bind });
+        self.joints.len() - 1
+    }
+
+    pub fn joints(&self) -> &[Joint] { &self.joints }
+    pub fn joint(&self, index: usize) -> &Joint { &self.joints[index] }
+    pub fn joint_mut(&mut self, index: usize) -> &mut Joint { &mut self.joints[index] }
+
+    /// The index of the first joint called `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    /// The pose the skeleton was bound to the mesh in
+    pub fn rest_pose(&self) -> Pose {
+        Pose { joints: self.joints.iter().map(|joint| joint.rest).collect() }
+    }
+
+    /// The transformation from each joint's space to the mesh's space in
+    /// `pose`
+    pub fn global_transforms(&self, pose: &Pose) -> Vec<Mat4<f32>> {
+        let mut globals: Vec<Mat4<f32>> = Vec::with_capacity(self.joints.len());
+        for (joint, local) in self.joints.iter().zip(&pose.joints) {
+            let global = match joint.parent {
+                Some(parent) => globals[parent] * local.to_mat4(),
+                None => local.to_mat4(),
+            };
+            globals.push(global);
+        }
+        globals
+    }
+
+    /// The matrices that move vertices from where they were bound to where
+    /// each joint puts them in `pose`, ready for `skin`
+    pub fn skin_matrices(&self, pose: &Pose) -> Vec<Mat4<f32>> {
+        self.global_transforms(pose).into_iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| global * joint.inverse_bind)
+            .collect()
+    }
+}
+
+
+// Animation ///////////////////////////////////////////////////////////////////
+
+/// A value at a point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+/// Keyframes for one part of a joint's transformation. Keys should be sorted
+/// by time.
+///
+/// Translations and scales are interpolated linearly, and rotations with
+/// `Quat::slerp`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Track {
+    Translation(Vec<Key<Vec3<f32>>>),
+    Rotation(Vec<Key<Quat>>),
+    Scale(Vec<Key<Vec3<f32>>>),
+}
+
+/// A track that animates a joint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub joint: usize,
+    pub track: Track,
+}
+
+/// An animation of a skeleton, which loops every `duration` seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl Clip {
+    /// A clip that doesn't animate anything yet
+    pub fn new(duration: f32) -> Self {
+        Clip { duration, channels: Vec::new() }
+    }
+
+    /// Add a channel animating `joint` with `track`
+    pub fn channel(mut self, joint: usize, track: Track) -> Self {
+        self.channels.push(Channel { joint, track });
+        self
+    }
+
+    /// The pose of `skeleton` at `time`, which wraps around the clip's
+    /// duration. Parts of joints that no channel animates are left in their
+    /// rest pose.
+    pub fn sample(&self, skeleton: &Skeleton, time: f32) -> Pose {
+        let time = if self.duration > 0.0 { time.rem_euclid(self.duration) } else { 0.0 };
+        let mut pose = skeleton.rest_pose();
+        for channel in &self.channels {
+            let transform = &mut pose.joints[channel.joint];
+            match channel.track {
+                Track::Translation(ref keys) => {
+                    transform.translation = sample(keys, time, lerp, transform.translation);
+                }
+                Track::Rotation(ref keys) => {
+                    transform.rotation = sample(keys, time, Quat::slerp, transform.rotation);
+                }
+                Track::Scale(ref keys) => {
+                    transform.scale = sample(keys, time, lerp, transform.scale);
+                }
+            }
+        }
+        pose
+    }
+}
+
+fn lerp(a: Vec3<f32>, b: Vec3<f32>, t: f32) -> Vec3<f32> {
+    a + (b - a) * t
+}
+
+/// The value of `keys` at `time`, holding the first and last values before
+/// and after them, or `default` if there aren't any
+fn sample<T, F>(keys: &[Key<T>], time: f32, interpolate: F, default: T) -> T
+    where T: Copy, F: Fn(T, T, f32) -> T
+{
+    let next = keys.iter().position(|key| key.time > time);
+    match next {
+        None => keys.last().map_or(default, |key| key.value),
+        Some(0) => keys[0].value,
+        Some(i) => {
+            let (a, b) = (&keys[i - 1], &keys[i]);
+            interpolate(a.value, b.value, (time - a.time) / (b.time - a.time))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clip, Key, Skeleton, SkinnedVert, Track, Transform, skin};
+    use cgl_math::{Vec2, Vec3, Mat4, Quat};
+    use model::Vert;
+    use std::f32::consts::PI;
+
+    fn close(a: Vec3<f32>, b: Vec3<f32>) -> bool {
+        (a - b).len() < 1e-4
+    }
+
+    /// A root at the origin with an elbow one unit up
+    fn arm() -> Skeleton {
+        let mut skeleton = Skeleton::new();
+        let root = skeleton.add("root", None, Transform::identity());
+        skeleton.add("elbow", Some(root), Transform::translation(Vec3(0.0, 1.0, 0.0)));
+        skeleton
+    }
+
+    fn vert(pos: Vec3<f32>, influences: &[(usize, f32)]) -> SkinnedVert {
+        SkinnedVert::new(Vert { pos, tex: Vec2(0.0, 0.0), norm: Vec3(0.0, 1.0, 0.0) },
+                         influences)
+    }
+
+    #[test]
+    fn rest_pose_leaves_mesh_alone() {
+        let skeleton = arm();
+        for matrix in skeleton.skin_matrices(&skeleton.rest_pose()) {
+            for row in 0..4 {
+                for col in 0..4 {
+                    assert!((matrix[(row, col)] - Mat4::identity()[(row, col)]).abs() < 1e-6);
+                }
+            }
+        }
+        assert_eq!(skeleton.find("elbow"), Some(1));
+    }
+
+    #[test]
+    fn joints_move_their_vertices() {
+        let skeleton = arm();
+        // Bend the elbow a quarter turn to the left
+        let mut pose = skeleton.rest_pose();
+        pose.joints[1].rotation = Quat::from_axis_angle(Vec3(0.0, 0.0, 1.0), PI / 2.0);
+        let matrices = skeleton.skin_matrices(&pose);
+
+        let hand = skin(&vert(Vec3(0.0, 2.0, 0.0), &[(1, 1.0)]), &matrices);
+        assert!(close(hand.pos, Vec3(-1.0, 1.0, 0.0)), "{:?}", hand.pos);
+        assert!(close(hand.norm, Vec3(-1.0, 0.0, 0.0)), "{:?}", hand.norm);
+
+        let shoulder = skin(&vert(Vec3(0.0, 0.5, 0.0), &[(0, 1.0)]), &matrices);
+        assert!(close(shoulder.pos, Vec3(0.0, 0.5, 0.0)));
+
+        // Split between the two, and normalized from weights of 2 and 2
+        let between = skin(&vert(Vec3(0.0, 2.0, 0.0), &[(0, 2.0), (1, 2.0)]), &matrices);
+        assert!(close(between.pos, Vec3(-0.5, 1.5, 0.0)), "{:?}", between.pos);
+    }
+
+    #[test]
+    fn clips_interpolate_keys() {
+        let skeleton = arm();
+        let quarter = Quat::from_axis_angle(Vec3(0.0, 0.0, 1.0), PI / 2.0);
+        let clip = Clip::new(2.0)
+            .channel(1, Track::Rotation(vec![Key { time: 0.0, value: Quat::identity() },
+                                             Key { time: 1.0, value: quarter }]))
+            .channel(0, Track::Translation(vec![Key { time: 0.5, value: Vec3(0.0, 0.0, 0.0) },
+                                                Key { time: 1.5, value: Vec3(2.0, 0.0, 0.0) }]));
+
+        let pose = clip.sample(&skeleton, 0.5);
+        let eighth = Quat::from_axis_angle(Vec3(0.0, 0.0, 1.0), PI / 4.0);
+        assert!((pose.joints[1].rotation.dot(eighth) - 1.0).abs() < 1e-6);
+        assert_eq!(pose.joints[0].translation, Vec3(0.0, 0.0, 0.0));
+        // The elbow's translation isn't animated
+        assert_eq!(pose.joints[1].translation, Vec3(0.0, 1.0, 0.0));
+
+        let pose = clip.sample(&skeleton, 1.0);
+        assert_eq!(pose.joints[0].translation, Vec3(1.0, 0.0, 0.0));
+        assert!((pose.joints[1].rotation.dot(quarter) - 1.0).abs() < 1e-6);
+
+        // Past the last key holds it, and the clip loops
+        assert_eq!(clip.sample(&skeleton, 1.75).joints[0].translation, Vec3(2.0, 0.0, 0.0));
+        assert_eq!(clip.sample(&skeleton, 3.0), clip.sample(&skeleton, 1.0));
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way() {
+        let a = Quat::from_axis_angle(Vec3(0.0, 1.0, 0.0), 0.1);
+        let b = -Quat::from_axis_angle(Vec3(0.0, 1.0, 0.0), 0.3);
+        let mid = a.slerp(b, 0.5);
+        let expected = Quat::from_axis_angle(Vec3(0.0, 1.0, 0.0), 0.2);
+        assert!((mid.dot(expected).abs() - 1.0).abs() < 1e-6, "{:?}", mid);
+        assert!(close(mid.rotate(Vec3(1.0, 0.0, 0.0)),
+                      (mid.to_mat4() * Vec3(1.0f32, 0.0, 0.0).augment()).retro_project()));
+    }
+}
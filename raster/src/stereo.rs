@@ -0,0 +1,260 @@
+//! Stereo pairs, for viewing models in 3D.
+//!
+//! A [`StereoCamera`] splits a camera into a left and right eye, a short
+//! distance apart. Both eyes look in the same direction, with their frustums
+//! sheared so that they agree on the convergence plane. Things on that plane
+//! appear at the depth of the screen, nearer things pop out of it and further
+//! things sink into it.
+//!
+//! The two views can be combined into a single [`Layout`] for viewing, like an
+//! anaglyph for red/cyan glasses.
+//!
+//! ```rust,no_run
+//! use cgl::{Camera, Renderer};
+//! use cgl::stereo::{Anaglyph, Layout, StereoCamera};
+//!
+//! let camera = StereoCamera::new(Camera::default(), 0.065, 2.0);
+//! let mut renderer = Renderer::with_dimensions(512, 512);
+//! let pair = camera.render(&mut renderer, |renderer, camera, _| {
+//!     // Draw the scene with `camera.view_projection()`...
+//! });
+//! let image = pair.combine(Layout::Anaglyph(Anaglyph::Dubois));
+//! ```
+//!
+//! [`StereoCamera`]: struct.StereoCamera.html
+//! [`Layout`]: enum.Layout.html
+
+use camera::Camera;
+use cgl_math::{Vec2, Vec3};
+use image::{Image, Color, HdrColor, luminance};
+use renderer::Renderer;
+
+/// One of the two eyes of a stereo pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// A camera split into two eyes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoCamera {
+    /// The camera halfway between the eyes
+    pub camera: Camera,
+    /// The distance between the eyes, in world units
+    pub interocular: f32,
+    /// The distance in front of the camera that the eyes converge at, which
+    /// appears at the depth of the screen
+    pub convergence: f32,
+}
+
+impl StereoCamera {
+    pub fn new(camera: Camera, interocular: f32, convergence: f32) -> Self {
+        StereoCamera { camera, interocular, convergence }
+    }
+
+    /// The camera for one eye. Perspective cameras become `Lens::Frustum`,
+    /// and orthographic cameras see the same thing from both eyes.
+    pub fn eye(&self, eye: Eye) -> Camera {
+        let offset = match eye {
+            Eye::Left => -self.interocular / 2.0,
+            Eye::Right => self.interocular / 2.0,
+        };
+        self.camera.offset_lens(Vec2(offset, 0.0), self.convergence)
+    }
+
+    /// Render both eyes into `renderer` with `draw`, which is given the camera
+    /// for each eye. The renderer is cleared before each one.
+    pub fn render<D>(&self, renderer: &mut Renderer<Color>, mut draw: D) -> StereoPair
+        where D: FnMut(&mut Renderer<Color>, Camera, Eye)
+    {
+        let mut view = |eye| {
+            renderer.clear();
+            draw(renderer, self.eye(eye), eye);
+            renderer.image().clone()
+        };
+        let left = view(Eye::Left);
+        let right = view(Eye::Right);
+        StereoPair { left, right }
+    }
+}
+
+/// How to fit a stereo pair into one image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// The left eye on the left and the right on the right, for parallel
+    /// viewing or for displays that take side-by-side input
+    SideBySide,
+    /// The right eye on the left and the left on the right, for viewing with
+    /// crossed eyes
+    CrossEye,
+    /// The left eye above the right
+    TopBottom,
+    /// Both eyes in the same image, in different colors
+    Anaglyph(Anaglyph),
+}
+
+/// How to mix a stereo pair into a red/cyan anaglyph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anaglyph {
+    /// The brightness of the left eye in red, and of the right in cyan. There's
+    /// no color, but the least ghosting.
+    Gray,
+    /// The red channel of the left eye and the green and blue of the right.
+    /// Keeps some color, but saturated reds and cyans only reach one eye.
+    Color,
+    /// Eric Dubois' least-squares projection, which is fitted to how the
+    /// filters of typical glasses actually pass light. Colors are muted, but
+    /// there's less ghosting and rivalry than with `Color`.
+    Dubois,
+}
+
+/// The Dubois matrices for red/cyan glasses, taking each eye's linear RGB to
+/// its contribution to the output
+const DUBOIS_LEFT: [[f32; 3]; 3] = [[0.456, 0.500, 0.176],
+                                    [-0.040, -0.038, -0.016],
+                                    [-0.015, -0.021, -0.005]];
+const DUBOIS_RIGHT: [[f32; 3]; 3] = [[-0.043, -0.088, -0.002],
+                                     [0.378, 0.734, -0.018],
+                                     [-0.072, -0.113, 1.226]];
+
+impl Anaglyph {
+    /// Mix one pixel from each eye
+    pub fn mix(&self, left: Color, right: Color) -> Color {
+        let (l, r) = (left.to_linear(), right.to_linear());
+        let (l, r) = (Vec3(l.r, l.g, l.b), Vec3(r.r, r.g, r.b));
+        let rgb = match *self {
+            Anaglyph::Gray => {
+                let (l, r) = (luminance(l), luminance(r));
+                Vec3(l, r, r)
+            }
+            Anaglyph::Color => Vec3(l.0, r.1, r.2),
+            Anaglyph::Dubois => {
+                let row = |i: usize| {
+                    Vec3(DUBOIS_LEFT[i][0], DUBOIS_LEFT[i][1], DUBOIS_LEFT[i][2]).dot(l) +
+                        Vec3(DUBOIS_RIGHT[i][0], DUBOIS_RIGHT[i][1], DUBOIS_RIGHT[i][2]).dot(r)
+                };
+                Vec3(row(0), row(1), row(2))
+            }
+        };
+        Color::from_linear(HdrColor::rgb(rgb.0, rgb.1, rgb.2))
+    }
+}
+
+/// The views from the left and right eye of a `StereoCamera`
+#[derive(Clone)]
+pub struct StereoPair {
+    pub left: Image<Color>,
+    pub right: Image<Color>,
+}
+
+impl StereoPair {
+    /// Both views in one image. The side-by-side and top-bottom layouts keep
+    /// each view at full size, so they make an image twice as wide or tall.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the views aren't the same size.
+    pub fn combine(&self, layout: Layout) -> Image<Color> {
+        let (w, h) = (self.left.width, self.left.height);
+        assert!(self.right.width == w && self.right.height == h,
+                "The views of a stereo pair should be the same size");
+        let (first, second) = match layout {
+            Layout::Anaglyph(anaglyph) => {
+                let mut image = Image::with_dimensions(w, h);
+                for y in 0..h {
+                    for x in 0..w {
+                        image[(x, y)] = anaglyph.mix(self.left[(x, y)], self.right[(x, y)]);
+                    }
+                }
+                return image;
+            }
+            Layout::CrossEye => (&self.right, &self.left),
+            Layout::SideBySide | Layout::TopBottom => (&self.left, &self.right),
+        };
+        let (dx, dy) = if layout == Layout::TopBottom { (0, h) } else { (w, 0) };
+        let mut image = Image::with_dimensions(w + dx, h + dy);
+        for y in 0..h {
+            for x in 0..w {
+                image[(x, y)] = first[(x, y)];
+                image[(x + dx, y + dy)] = second[(x, y)];
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anaglyph, Eye, Layout, StereoCamera, StereoPair};
+    use camera::Camera;
+    use cgl_math::{Mat4, Vec3};
+    use image::{Color, Image};
+    use renderer::Renderer;
+
+    #[test]
+    fn eyes_converge() {
+        let camera = Camera::perspective(1.0, 1.0, 0.1, 100.0)
+            .look_at(Vec3(0.0, 0.0, 5.0), Vec3(0.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+        let stereo = StereoCamera::new(camera, 0.2, 5.0);
+        let (left, right) = (stereo.eye(Eye::Left), stereo.eye(Eye::Right));
+        assert!((left.position() - Vec3(-0.1, 0.0, 5.0)).len() < 1e-5);
+        assert!((right.position() - Vec3(0.1, 0.0, 5.0)).len() < 1e-5);
+
+        let screen = |camera: Camera, point: Vec3<f32>| {
+            (Mat4::viewport(100, 100) * camera.view_projection() * point.augment())
+                .retro_project()
+        };
+        // No parallax on the convergence plane
+        let (l, r) = (screen(left, Vec3(0.5, 0.3, 0.0)), screen(right, Vec3(0.5, 0.3, 0.0)));
+        assert!((l.0 - r.0).abs() < 1e-3 && (l.1 - r.1).abs() < 1e-3, "{:?} {:?}", l, r);
+        // Nearer things are further right in the left eye, so they pop out
+        let (l, r) = (screen(left, Vec3(0.0, 0.0, 3.0)), screen(right, Vec3(0.0, 0.0, 3.0)));
+        assert!(l.0 > r.0 + 1.0, "{:?} {:?}", l, r);
+    }
+
+    fn pair() -> StereoPair {
+        let camera = StereoCamera::new(Camera::default(), 0.1, 1.0);
+        let mut renderer = Renderer::with_dimensions(2, 1);
+        camera.render(&mut renderer, |renderer, _, eye| {
+            let color = if eye == Eye::Left { Color::red() } else { Color::white() };
+            renderer.line(Vec3(0, 0, 0), Vec3(0, 0, 0), color);
+        })
+    }
+
+    #[test]
+    fn lays_out_views() {
+        let pair = pair();
+        assert_eq!(pair.left[(0, 0)], Color::red());
+        assert_eq!(pair.right[(0, 0)], Color::white());
+        // Each eye starts from a cleared renderer
+        assert_eq!(pair.right[(1, 0)], Color::black());
+
+        let pixels = |image: &Image<Color>| {
+            (0..image.height).flat_map(|y| (0..image.width).map(move |x| (x, y)))
+                .map(|p| image[p]).collect::<Vec<_>>()
+        };
+        let (r, w, k) = (Color::red(), Color::white(), Color::black());
+        assert_eq!(pixels(&pair.combine(Layout::SideBySide)), vec![r, k, w, k]);
+        assert_eq!(pixels(&pair.combine(Layout::CrossEye)), vec![w, k, r, k]);
+        let top_bottom = pair.combine(Layout::TopBottom);
+        assert_eq!((top_bottom.width, top_bottom.height), (2, 2));
+        assert_eq!(pixels(&top_bottom), vec![r, k, w, k]);
+    }
+
+    #[test]
+    fn anaglyphs_split_colors() {
+        let pair = pair();
+        let color = pair.combine(Layout::Anaglyph(Anaglyph::Color));
+        assert_eq!(color[(0, 0)], Color::white());
+        let gray = pair.combine(Layout::Anaglyph(Anaglyph::Gray))[(0, 0)];
+        assert!(gray.r < 255 && gray.g == 255 && gray.b == 255, "{:?}", gray);
+
+        // Dubois sends the left eye to red and the right eye to cyan
+        let dubois = Anaglyph::Dubois;
+        let left = dubois.mix(Color::white(), Color::black());
+        assert!(left.r > 150 && left.g == 0 && left.b == 0, "{:?}", left);
+        let right = dubois.mix(Color::black(), Color::white());
+        assert!(right.r == 0 && right.g > 200 && right.b > 200, "{:?}", right);
+    }
+}
@@ -0,0 +1,245 @@
+//! An optional stage that subdivides triangles before they're shaded.
+//!
+//! Models with few triangles look faceted, especially along their silhouettes,
+//! and a displacement map can't add any detail finer than the triangles it's
+//! applied to. A [`Tessellator`] splits each input triangle (or *patch*) into
+//! a grid of smaller ones, and then gets to place each of the new vertices
+//! before they're passed to the vertex stage of the shader.
+//!
+//! Tessellators are meant to be combined. [`Uniform`] and [`Distance`] pick how
+//! finely to split each patch, and [`Phong`] and [`Displace`] wrap another
+//! tessellator to move the vertices it produces:
+//!
+//! ```rust,no_run
+//! # use cgl::{Image, Vec3};
+//! use cgl::tessellation::{Displace, Distance, Phong};
+//!
+//! # let height: Image<f32> = Image::with_dimensions(1, 1);
+//! let tessellator = Displace::new(Phong::new(Distance {
+//!     eye: Vec3(0.0, 0.0, 3.0),
+//!     near: 1.0,
+//!     far: 5.0,
+//!     max: 8,
+//! }, 0.75), height, 0.02);
+//! ```
+//!
+//! Since the tessellation factor is chosen per patch, neighboring patches can
+//! be split differently, and once their vertices are moved around there can be
+//! small cracks along the edges they share.
+//!
+//! [`Tessellator`]: trait.Tessellator.html
+//! [`Uniform`]: struct.Uniform.html
+//! [`Distance`]: struct.Distance.html
+//! [`Phong`]: struct.Phong.html
+//! [`Displace`]: struct.Displace.html
+
+use cgl_math::{Vec3, saturate};
+use image::Image;
+use model::{MeshVertex, Vertex};
+
+pub trait Tessellator<V: Vertex> {
+    /// How many pieces to split each edge of the patch into. A factor of 1
+    /// leaves the patch as it is.
+    fn factor(&self, t0: &V, t1: &V, t2: &V) -> usize;
+
+    /// The evaluation stage, which produces the vertex at barycentric
+    /// coordinates `bc` within the patch. It's called for each vertex of the
+    /// subdivided patch, including the original corners.
+    fn evaluate(&self, bc: Vec3<f32>, t0: &V, t1: &V, t2: &V) -> V {
+        Vertex::interpolate(bc, t0, t1, t2)
+    }
+}
+
+/// Split every patch with the same factor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uniform(pub usize);
+
+impl<V: Vertex> Tessellator<V> for Uniform {
+    fn factor(&self, _: &V, _: &V, _: &V) -> usize {
+        self.0
+    }
+}
+
+/// Split patches more finely the closer they are to the eye.
+///
+/// Patches with their center closer than `near` are split `max` times, ones
+/// further away than `far` are left alone, and the factor falls off linearly
+/// in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distance {
+    /// The position of the eye, in the same space as the model's vertices
+    pub eye: Vec3<f32>,
+    pub near: f32,
+    pub far: f32,
+    pub max: usize,
+}
+
+impl<V: MeshVertex> Tessellator<V> for Distance {
+    fn factor(&self, t0: &V, t1: &V, t2: &V) -> usize {
+        let center = (t0.pos() + t1.pos() + t2.pos()) / 3.0;
+        let distance = (center - self.eye).len() as f32;
+        let t = saturate((distance - self.near) / (self.far - self.near));
+        (self.max as f32 + (1.0 - self.max as f32) * t).round() as usize
+    }
+}
+
+/// Phong tessellation, which rounds out the patches produced by another
+/// tessellator using the normals at their corners.
+///
+/// Each new vertex is projected onto the tangent planes at the three corners,
+/// and the projections are blended together. This gives curved silhouettes
+/// without any extra data. `shape` blends between the flat patch at 0 and the
+/// fully curved one at 1, with 0.75 usually looking best.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phong<T> {
+    pub tessellation: T,
+    pub shape: f32,
+}
+
+impl<T> Phong<T> {
+    pub fn new(tessellation: T, shape: f32) -> Self {
+        Phong { tessellation, shape }
+    }
+}
+
+impl<V: MeshVertex, T: Tessellator<V>> Tessellator<V> for Phong<T> {
+    fn factor(&self, t0: &V, t1: &V, t2: &V) -> usize {
+        self.tessellation.factor(t0, t1, t2)
+    }
+
+    fn evaluate(&self, bc: Vec3<f32>, t0: &V, t1: &V, t2: &V) -> V {
+        let mut vertex = self.tessellation.evaluate(bc, t0, t1, t2);
+        let p = vertex.pos();
+        let project = |corner: &V| {
+            let n = corner.norm().normalized();
+            p - n * (p - corner.pos()).dot(n)
+        };
+        let curved = project(t0) * bc.0 + project(t1) * bc.1 + project(t2) * bc.2;
+        *vertex.pos_mut() = p * (1.0 - self.shape) + curved * self.shape;
+        vertex
+    }
+}
+
+/// Displacement mapping, which moves the vertices produced by another
+/// tessellator along their normals.
+///
+/// The distance is sampled from `height` at each vertex's texture coordinates,
+/// and multiplied by `scale`. The normals are left as they are, so lighting
+/// should come from a normal map that matches the height map.
+#[derive(Clone)]
+pub struct Displace<T> {
+    pub tessellation: T,
+    pub height: Image<f32>,
+    pub scale: f32,
+}
+
+impl<T> Displace<T> {
+    pub fn new(tessellation: T, height: Image<f32>, scale: f32) -> Self {
+        Displace { tessellation, height, scale }
+    }
+}
+
+impl<V: MeshVertex, T: Tessellator<V>> Tessellator<V> for Displace<T> {
+    fn factor(&self, t0: &V, t1: &V, t2: &V) -> usize {
+        self.tessellation.factor(t0, t1, t2)
+    }
+
+    fn evaluate(&self, bc: Vec3<f32>, t0: &V, t1: &V, t2: &V) -> V {
+        let mut vertex = self.tessellation.evaluate(bc, t0, t1, t2);
+        let tex = vertex.tex();
+        let height = self.height.sample_clamp(tex.0, tex.1);
+        let offset = vertex.norm().normalized() * (height * self.scale);
+        *vertex.pos_mut() = vertex.pos() + offset;
+        vertex
+    }
+}
+
+/// Split the triangle `t0`, `t1`, `t2` with `tessellator`, and pass each of
+/// the resulting triangles to `emit`.
+///
+/// The triangles keep the winding order of the original one, so back-face
+/// culling in later stages still works.
+pub fn tessellate<V, T, F>(tessellator: &T, t0: V, t1: V, t2: V, mut emit: F)
+    where V: Vertex + Copy, T: Tessellator<V>, F: FnMut(V, V, V)
+{
+    let n = tessellator.factor(&t0, &t1, &t2).max(1);
+
+    // The vertices form a triangular grid, with row i holding the n + 1 - i
+    // vertices that are i steps along the edge from t0 to t1.
+    let mut vertices = Vec::with_capacity((n + 1) * (n + 2) / 2);
+    for i in 0..n + 1 {
+        for j in 0..n + 1 - i {
+            let bc = Vec3((n - i - j) as f32, i as f32, j as f32) / n as f32;
+            vertices.push(tessellator.evaluate(bc, &t0, &t1, &t2));
+        }
+    }
+    let index = |i: usize, j: usize| i * (2 * n + 3 - i) / 2 + j;
+
+    for i in 0..n {
+        for j in 0..n - i {
+            emit(vertices[index(i, j)],
+                 vertices[index(i + 1, j)],
+                 vertices[index(i, j + 1)]);
+            if j + 1 < n - i {
+                emit(vertices[index(i + 1, j)],
+                     vertices[index(i + 1, j + 1)],
+                     vertices[index(i, j + 1)]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Displace, Distance, Tessellator, Uniform, tessellate};
+    use cgl_math::{Vec2, Vec3};
+    use image::Image;
+    use model::Vert;
+
+    fn vert(x: f32, y: f32) -> Vert {
+        Vert { pos: Vec3(x, y, 0.0), tex: Vec2(x, y), norm: Vec3(0.0, 0.0, 1.0) }
+    }
+
+    fn area(a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>) -> f32 {
+        (b - a).cross(c - a).2 / 2.0
+    }
+
+    #[test]
+    fn uniform_covers_the_patch() {
+        for n in 1..6 {
+            let mut count = 0;
+            let mut total = 0.0;
+            tessellate(&Uniform(n), vert(0.0, 0.0), vert(1.0, 0.0), vert(0.0, 1.0),
+                       |a, b, c| {
+                           let area = area(a.pos, b.pos, c.pos);
+                           // Every triangle keeps the original winding
+                           assert!(area > 0.0);
+                           count += 1;
+                           total += area;
+                       });
+            assert_eq!(count, n * n);
+            assert!((total - 0.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn distance_falls_off() {
+        let distance = Distance { eye: Vec3(0.0, 0.0, 0.0), near: 1.0, far: 3.0, max: 5 };
+        let at = |z: f32| {
+            let v = |x: f32| Vert { pos: Vec3(x, 0.0, z), ..vert(0.0, 0.0) };
+            distance.factor(&v(-0.1), &v(0.1), &v(0.0))
+        };
+        assert_eq!(at(0.5), 5);
+        assert_eq!(at(2.0), 3);
+        assert_eq!(at(4.0), 1);
+    }
+
+    #[test]
+    fn displaces_along_normal() {
+        let height = Image::with_pixels(2, 1, &[0.0, 1.0]);
+        let displace = Displace::new(Uniform(1), height, 0.5);
+        let (t0, t1, t2) = (vert(0.0, 0.0), vert(1.0, 0.0), vert(0.0, 1.0));
+        let v = displace.evaluate(Vec3(0.0, 1.0, 0.0), &t0, &t1, &t2);
+        assert!((v.pos - Vec3(1.0, 0.0, 0.5)).len() < 1e-6);
+    }
+}
@@ -0,0 +1,116 @@
+//! Textures that are filtered in linear color space.
+//!
+//! Bitmaps store their colors sRGB-encoded, which means that a byte value of
+//! 128 is only about 22% as bright as 255, not 50%. Averaging encoded values,
+//! like `Image::sample_clamp` does, gives results that are too dark wherever
+//! light and dark texels meet. A [`Texture`] remembers how its image is
+//! encoded, and decodes texels into linear `HdrColor`s before filtering them.
+//!
+//! Not every texture holds colors, though. Normal maps, height maps, and
+//! other data textures are stored linearly, and decoding them would distort
+//! the data, so those should be loaded with `Texture::linear`.
+//!
+//! [`Texture`]: struct.Texture.html
+
+use cgl_math::saturate;
+use image::{Image, Color, HdrColor, srgb_to_linear};
+
+use std::io::{self, Read, Seek};
+
+/// How the bytes of a texture map to values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// The bytes are sRGB-encoded colors, as in most images
+    Srgb,
+    /// The bytes map linearly to [0, 1], as for normal maps and other data
+    Linear,
+}
+
+/// An image along with how its pixels are encoded
+#[derive(Clone)]
+pub struct Texture {
+    pub image: Image<Color>,
+    pub encoding: Encoding,
+    decode: Box<[f32; 256]>,
+}
+
+impl Texture {
+    pub fn new(image: Image<Color>, encoding: Encoding) -> Self {
+        let mut decode = Box::new([0.0; 256]);
+        for (i, value) in decode.iter_mut().enumerate() {
+            let c = i as f32 / 255.0;
+            *value = match encoding {
+                Encoding::Srgb => srgb_to_linear(c),
+                Encoding::Linear => c,
+            };
+        }
+        Texture { image, encoding, decode }
+    }
+
+    /// A texture holding sRGB-encoded colors
+    pub fn srgb(image: Image<Color>) -> Self {
+        Texture::new(image, Encoding::Srgb)
+    }
+
+    /// A texture holding linear data
+    pub fn linear(image: Image<Color>) -> Self {
+        Texture::new(image, Encoding::Linear)
+    }
+
+    /// Read a .bmp file as a texture with the given encoding
+    pub fn read_bmp<R: Read + Seek>(reader: &mut R, encoding: Encoding) -> io::Result<Self> {
+        Ok(Texture::new(::bmp::read_bmp(reader)?, encoding))
+    }
+
+    pub fn width(&self) -> usize { self.image.width }
+    pub fn height(&self) -> usize { self.image.height }
+
+    /// The decoded value of the texel at `(x, y)`
+    pub fn texel(&self, x: usize, y: usize) -> HdrColor {
+        let c = self.image[(x, y)];
+        HdrColor::rgb(self.decode[c.r as usize],
+                      self.decode[c.g as usize],
+                      self.decode[c.b as usize])
+    }
+
+    /// Sample the texture at a point in UV space, with the same conventions as
+    /// `Image::sample_clamp`. Texels are decoded before they're bilinearly
+    /// interpolated, so the result is in linear space.
+    pub fn sample_clamp(&self, u: f32, v: f32) -> HdrColor {
+        let x = saturate(u) * (self.width() - 1) as f32;
+        let y = (1.0 - saturate(v)) * (self.height() - 1) as f32;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = (x.ceil() as usize, y.ceil() as usize);
+        let (fx, fy) = (x.fract(), y.fract());
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x1, y0) * fx;
+        let bot = self.texel(x0, y1) * (1.0 - fx) + self.texel(x1, y1) * fx;
+        top * (1.0 - fy) + bot * fy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Texture;
+    use image::{Image, Color};
+
+    #[test]
+    fn filters_in_linear_space() {
+        let image = Image::with_pixels(2, 1, &[Color::black(), Color::white()]);
+        let srgb = Texture::srgb(image.clone()).sample_clamp(0.5, 0.5);
+        let linear = Texture::linear(image).sample_clamp(0.5, 0.5);
+        assert!((srgb.r - 0.5).abs() < 1e-6);
+        assert!((linear.r - 0.5).abs() < 1e-6);
+        // Half of the light of white is brighter than byte 128 once encoded
+        assert!(Color::from_linear(srgb).r > 128);
+    }
+
+    #[test]
+    fn decodes_texels() {
+        let image = Image::with_pixels(1, 1, &[Color::rgb(128, 0, 255)]);
+        let srgb = Texture::srgb(image.clone()).texel(0, 0);
+        let linear = Texture::linear(image).texel(0, 0);
+        assert!((srgb.r - 0.2158).abs() < 1e-3);
+        assert!((linear.r - 128.0 / 255.0).abs() < 1e-6);
+        assert_eq!((srgb.g, srgb.b), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,162 @@
+//! Operators for displaying high dynamic range images.
+//!
+//! Lighting computed in an `Image<HdrColor>` can be arbitrarily bright, but a
+//! `Color` can only go up to 255. Simply clamping makes every bright area a
+//! flat, saturated blob, so instead a tone mapping operator compresses the
+//! whole range of brightness down into [0, 1].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use cgl::{HdrColor, Renderer};
+//! use cgl::tonemap::{self, Aces};
+//!
+//! let renderer: Renderer<HdrColor> = Renderer::with_dimensions(64, 64);
+//! // ... render the scene ...
+//! let image = tonemap::resolve(renderer.image(), &Aces::default());
+//! ```
+
+use cgl_math::saturate;
+use image::{Image, Color, HdrColor};
+
+/// A function compressing HDR colors into the displayable [0, 1] range
+pub trait ToneMap {
+    fn tonemap(&self, color: HdrColor) -> HdrColor;
+}
+
+/// Tone map every pixel of `image` and encode the result as sRGB `Color`s
+pub fn resolve<T: ToneMap + ?Sized>(image: &Image<HdrColor>, op: &T) -> Image<Color> {
+    image.map(|c| Color::from_linear(op.tonemap(c)))
+}
+
+fn each<F: Fn(f32) -> f32>(c: HdrColor, f: F) -> HdrColor {
+    HdrColor::rgb(f(c.r), f(c.g), f(c.b))
+}
+
+/// Scales the image by `2^stops` and clamps, so `Clamp { stops: 0.0 }` does no
+/// tone mapping at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clamp {
+    pub stops: f32,
+}
+
+impl ToneMap for Clamp {
+    fn tonemap(&self, color: HdrColor) -> HdrColor {
+        let scale = self.stops.exp2();
+        each(color, |c| saturate(c * scale))
+    }
+}
+
+/// The exponential operator `1 - e^(-exposure * c)`.
+///
+/// This behaves like film, where each additional bit of light exposes a
+/// little less of what's left. Larger values of `exposure` brighten the
+/// image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exposure {
+    pub exposure: f32,
+}
+
+impl Default for Exposure {
+    fn default() -> Self { Exposure { exposure: 1.0 } }
+}
+
+impl ToneMap for Exposure {
+    fn tonemap(&self, color: HdrColor) -> HdrColor {
+        each(color, |c| 1.0 - (-c.max(0.0) * self.exposure).exp())
+    }
+}
+
+/// The extended Reinhard operator `c (1 + c / white²) / (1 + c)`.
+///
+/// Colors at `white` map to 1, and anything brighter is clamped. With an
+/// infinite white point, this is the simple Reinhard operator `c / (1 + c)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reinhard {
+    /// The brightness that is mapped to pure white. It should be positive, and
+    /// anything else is treated as infinite.
+    pub white: f32,
+}
+
+impl Default for Reinhard {
+    fn default() -> Self { Reinhard { white: f32::INFINITY } }
+}
+
+impl ToneMap for Reinhard {
+    fn tonemap(&self, color: HdrColor) -> HdrColor {
+        let inv_white2 = if self.white > 0.0 { 1.0 / (self.white * self.white) } else { 0.0 };
+        each(color, |c| {
+            let c = c.max(0.0);
+            (c * (1.0 + c * inv_white2) / (1.0 + c)).min(1.0)
+        })
+    }
+}
+
+/// An approximation of the ACES filmic curve, as fitted by Krzysztof
+/// Narkowicz.
+///
+/// It has a slight toe that deepens shadows and rolls highlights off smoothly,
+/// which gives more contrast than Reinhard. The input is scaled by `2^stops`
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Aces {
+    pub stops: f32,
+}
+
+impl ToneMap for Aces {
+    fn tonemap(&self, color: HdrColor) -> HdrColor {
+        // The fit is for values that have already been multiplied by 0.6
+        let scale = self.stops.exp2() * 0.6;
+        each(color, |c| {
+            let c = c.max(0.0) * scale;
+            let mapped = (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14);
+            saturate(mapped)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ToneMap, Aces, Clamp, Exposure, Reinhard};
+    use image::HdrColor;
+
+    fn ops() -> Vec<Box<dyn ToneMap>> {
+        vec![Box::new(Aces::default()),
+             Box::new(Clamp { stops: 0.0 }),
+             Box::new(Exposure::default()),
+             Box::new(Reinhard::default()),
+             Box::new(Reinhard { white: 4.0 }),
+             Box::new(Reinhard { white: 0.0 })]
+    }
+
+    #[test]
+    fn black_stays_black() {
+        for op in ops() {
+            assert!(op.tonemap(HdrColor::black()).r.abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn monotonic_and_bounded() {
+        for op in ops() {
+            let mut last = -1.0;
+            for i in 0..100 {
+                let c = op.tonemap(HdrColor::gray(i as f32 * 0.25)).g;
+                assert!(c >= last);
+                assert!(c <= 1.0);
+                last = c;
+            }
+        }
+    }
+
+    #[test]
+    fn reinhard_white_point() {
+        let op = Reinhard { white: 4.0 };
+        assert!((op.tonemap(HdrColor::gray(4.0)).r - 1.0).abs() < 1e-6);
+        assert!((Reinhard::default().tonemap(HdrColor::gray(1.0)).r - 0.5).abs() < 1e-6);
+        // A white point of 0 is as if there wasn't one, rather than 0/0
+        let op = Reinhard { white: 0.0 };
+        assert_eq!(op.tonemap(HdrColor::black()), HdrColor::black());
+        assert!((op.tonemap(HdrColor::gray(1.0)).r - 0.5).abs() < 1e-6);
+    }
+}